@@ -1,32 +1,105 @@
 use std::env;
 
+use chrono::NaiveDate;
+
 use getopts::Options;
 
 use temperatureconverter::*;
 
+use temperatureconverter::history::*;
+
 #[tokio::main]
 async fn main() -> Result<(), reqwest::Error>{
-    let mut to_print: String = "".to_string();
-    let mut to_file: String = "".to_string();
+    let to_print: String;
+    // One history entry, or one per location when several are looked up together.
+    let to_file: Vec<String>;
     
+    let mut config = match Config::load() {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("{}", e);
+            Config::default()
+        }
+    };
+    if let Err(e) = apply_retention(&config.history, chrono::Local::now()) {
+        eprintln!("unable to apply history retention: {}", e);
+    }
+
     let args: Vec<String> = env::args().collect();
     let mut opts = Options::new();
 
     opts.optopt("t", "temp", "input temperature and scale", "TEMP");
-    opts.optopt("z", "zip", "input zip code", "ZIP");
+    opts.optmulti("z", "zip", "input zip or postal code, repeat for more locations", "ZIP");
+    opts.optopt("", "country", "two letter country code for postal codes, e.g. GB", "CC");
+    opts.optmulti("", "city", "city name, optionally with region or country, e.g. \"Paris, France\"", "NAME");
+    opts.optmulti("", "coords", "latitude and longitude, e.g. 38.8977,-77.0365", "LAT,LON");
+    opts.optmulti("", "iata", "three letter airport code, e.g. JFK", "CODE");
+    opts.optflag("", "auto-ip", "locate by this machine's IP address");
+    opts.optmulti("l", "location", "any of the above, detected from the input", "LOCATION");
+    opts.optopt("", "api-key", "weather API key (overrides config and environment)", "KEY");
+    opts.optopt("", "base-url", "weather API base URL (overrides config and environment)", "URL");
+    opts.optopt("", "provider", "weather provider: weatherapi, open_meteo or nws", "NAME");
+    opts.optflag("", "no-cache", "do not read or write cached weather");
+    opts.optflag("", "refresh", "fetch current weather again even if a cached observation is fresh");
+    opts.optflag("", "offline", "never contact the weather service, only show stored observations");
+    opts.optflag("", "no-daemon", "look weather up directly even if the daemon is running");
+    opts.optflag("", "details", "show the full weather observation with a zip lookup");
+    opts.optflag("", "alerts", "also show active weather alerts with current conditions");
+    opts.optflag("", "air-quality", "also look up PM2.5, PM10, ozone, NO2 and the US EPA and UK DEFRA indices");
+    opts.optopt("", "watch", "keep polling the location every INTERVAL, e.g. 5m, until Ctrl-C", "INTERVAL");
+    opts.optflag("", "every-tick", "with --watch, print every poll rather than only changes");
+    opts.optopt("", "scales", "scales to show weather details and forecasts in, e.g. C,F (default C,K,F)", "SCALES");
+    opts.optopt("", "date", "day to look up past weather or sunrise and sunset for (YYYY-MM-DD)", "DATE");
+    opts.optopt("", "days", "number of days to forecast, 1 to 14 (default 3)", "DAYS");
+    opts.optflag("", "hourly", "show hourly temperatures in a forecast or past weather");
     opts.optflag("h", "help", "print help");
     opts.optflag("r", "read", "print use history");
+    opts.optflag("", "no-history", "do not record this command in the use history");
+    opts.optopt("", "since", "first day to include in history stats", "DATE");
+    opts.optopt("", "until", "last day to include in history stats or past weather", "DATE");
+    opts.optopt("", "before", "remove history entries older than this day", "DATE");
+    opts.optopt("", "format", "history export format: csv, json, md or html", "FORMAT");
+    opts.optopt("o", "output", "file to write a history export to", "FILE");
 
     let matches = match opts.parse(&args[1..]) {
         Ok(m) => { m }
         Err(f) => { panic!("{}", f.to_string()) }
     };
 
+    if let Some(key) = matches.opt_str("api-key") {
+        config.weather.api_key = Some(key);
+    }
+    if let Some(url) = matches.opt_str("base-url") {
+        config.weather.base_url = url;
+    }
+    if matches.opt_present("no-cache") {
+        config.cache.enabled = false;
+    }
+    // The daemon looks weather up with its own settings, so it isn't asked when they are overridden here.
+    if ["no-daemon", "api-key", "base-url", "no-cache"].iter().any(|o| matches.opt_present(o)) {
+        config.daemon.use_daemon = false;
+    }
+    if matches.opt_present("offline") {
+        config.weather.offline = true;
+    }
+    if matches.opt_present("air-quality") {
+        config.weather.air_quality = true;
+    }
+    if matches.opt_present("alerts") {
+        config.weather.alerts = true;
+    }
+    if let Some(name) = matches.opt_str("provider") {
+        match ProviderKind::from_name(&name) {
+            Some(p) => config.weather.provider = p,
+            None => eprintln!("unknown weather provider {}, using {:?}", name, config.weather.provider)
+        }
+    }
+
     if matches.opt_present("help") {
         to_print = 
-            "-= temperature-converter =-\n    -t  --temp  :  Enter a temperature and scale (ex: 12C) to convert\n    -z  --zip   :  Enter a zip code to get the current temperature\n    -r  --read  :  Print out app use history\n All entries are recorded."
+            "-= temperature-converter =-\n    -t  --temp  :  Enter a temperature and scale (ex: 12C) to convert\n    -z  --zip   :  Enter a zip or postal code to get the current temperature (--country CC for outside the US)\n        --city NAME  --coords LAT,LON  --iata CODE  --auto-ip  -l --location ANY  :  Look up by city, coordinates, airport, IP address or any of these\n        Repeat any of these to show a table of several locations at once (ex: -z 99801 -z 20500 --city Paris)\n        --refresh  --no-cache  :  Fetch again instead of reusing a recent observation, or don't use the cache at all\n        --no-daemon  :  Look weather up directly even while main-daemon is running (it is asked first otherwise)\n        --offline  :  Never contact the weather service, show the last stored observation marked STALE with its age\n        --details [--scales C,K,F]  :  Also show feels-like, wind, humidity, pressure, UV, dew point and visibility\n        --alerts  :  Also show active weather alerts such as heat advisories and freeze warnings (WeatherAPI and NWS)\n        --air-quality  :  Also show PM2.5, PM10, ozone, NO2 and the US EPA / UK DEFRA air quality indices (WeatherAPI only)\n        --watch INTERVAL [--every-tick]  :  Keep polling (ex: 30s, 5m, 1h) and print changes with the trend since the last sample, until Ctrl-C\n    forecast LOCATION [--days N] [--hourly] [--scales C,K,F]  :  Forecast daily min/max/avg temperatures, conditions and chance of precipitation\n    weather LOCATION [--date DATE [--until DATE]] [--hourly] [--scales C,K,F]  :  Current conditions, or recorded weather on a past day or range of days\n    astronomy LOCATION [--date DATE]  :  Sunrise, sunset, moonrise, moonset and moon phase in the location's time zone (today if no date)\n        --provider NAME  --api-key KEY  --base-url URL  :  Weather provider (weatherapi, open_meteo, nws), API key and endpoint (default from temperature-converter.toml or environment)\n    -r  --read  :  Print out app use history\n    --no-history  :  Do not record this command in the use history\n    history stats [--since DATE] [--until DATE]  :  Summarize app use history (dates as YYYY-MM-DD)\n    history export [--format csv|json|md|html] [-o FILE] [--since DATE] [--until DATE]  :  Export app use history\n    history prune [--before DATE]  :  Remove old history entries (configured retention if no date)\n    history clear  :  Remove all history entries and archives\n All entries are recorded unless disabled in temperature-converter.toml.\n Temperature threshold rules under [[thresholds.rules]] in temperature-converter.toml are checked on every current weather lookup."
             .to_string();
-        to_file = "Help requested".to_string();
+        to_file = vec!["Help requested".to_string()];
     } else if matches.opt_present("temp") {
         let input = match matches.opt_str("temp") {
             Some(str) => str,
@@ -41,46 +114,371 @@ async fn main() -> Result<(), reqwest::Error>{
             },
             Err(e) => to_print = e.to_string()
         }
-        to_file = format!("Temperature converted (\n{}\n)", to_print).to_string();
-    } else if matches.opt_present("zip") {
-        match matches.opt_str("zip") {
-            Some(str) => {
-                match get_current_temp(str).await {
-                    Ok(t) => {
-                        match parse_temp_input(&format!("{}C", t.2)) {
-                            Ok(x) => to_print = {
-                                    let r = calculate(x);
-                                    format!(
-                                    "-= Retrieve temperature in {}, {} =-\n    {:?}: {}\n    {:?}: {}\n    {:?}: {}", 
-                                    t.0, t.1, r.0.0, r.0.1, r.1.0, r.1.1, r.2.0, r.2.1)
-                            },
-                            Err(e) => to_print = e.to_string()
-                        }
-                    },
-                    Err(e) => to_print = e.to_string()
+        to_file = vec![format!("Temperature converted (\n{}\n)", to_print)];
+    } else if let Some(every) = matches.opt_str("watch") {
+        (to_print, to_file) = watch(&config, &matches, &every).await;
+    } else if matches.free.first().is_some_and(|c| c == "forecast") {
+        let (input, query) = single_location(location_args(&matches));
+        to_print = match (query.clone(), matches.opt_str("days").unwrap_or("3".to_string()).parse::<u32>()) {
+            (None, _) => "Enter a location to forecast (ex: forecast 99801 --days 3)".to_string(),
+            (Some(Err(e)), _) => e,
+            (Some(Ok(query)), Ok(days @ 1..=14)) => match get_forecast(&config.weather, &query, days).await {
+                Ok(f) => format_forecast(&f, &scales_opt(&matches), matches.opt_present("hourly")),
+                Err(e) => format!("Forecast failed: {}", e)
+            },
+            (Some(Ok(_)), _) => "--days must be a number from 1 to 14".to_string()
+        };
+        to_file = vec![redact_location_query(&config.history, &format!("Forecast retrieved (\n{}\n)", to_print), &input, query.as_ref().and_then(|q| q.as_ref().ok()))];
+    } else if matches.free.first().is_some_and(|c| c == "astronomy") {
+        let (input, query) = single_location(location_args(&matches));
+        to_print = match (query.clone(), parse_date(matches.opt_str("date"))) {
+            (None, _) => "Enter a location (ex: astronomy 99801 --date 2024-08-14)".to_string(),
+            (Some(Err(e)), _) | (_, Err(e)) => e,
+            (Some(Ok(query)), Ok(date)) => {
+                let date = date.unwrap_or_else(|| chrono::Local::now().date_naive());
+                match get_astronomy(&config.weather, &query, date).await {
+                    Ok(a) => format_astronomy(&a),
+                    Err(e) => format!("Astronomy lookup failed: {}", e)
+                }
+            }
+        };
+        to_file = vec![redact_location_query(&config.history, &format!("Astronomy retrieved (\n{}\n)", to_print), &input, query.as_ref().and_then(|q| q.as_ref().ok()))];
+    } else if matches.free.first().is_some_and(|c| c == "weather") && !matches.opt_present("date") && location_args(&matches).len() > 1 {
+        (to_print, to_file) = lookup_table(&config, location_args(&matches), &matches).await;
+    } else if matches.free.first().is_some_and(|c| c == "weather") {
+        let (input, query) = single_location(location_args(&matches));
+        let past = matches.opt_present("date");
+        let mut alerts_logged = Vec::new();
+        to_print = match (query.clone(), parse_date(matches.opt_str("date")), parse_date(matches.opt_str("until"))) {
+            (None, _, _) => "Enter a location (ex: weather 99801 --date 2024-08-01)".to_string(),
+            (Some(Err(e)), _, _) => e,
+            (Some(Ok(query)), Ok(Some(from)), Ok(until)) => match get_historical_weather(&config.weather, &query, from, until.unwrap_or(from)).await {
+                Ok(h) => format_weather_history(&h, &scales_opt(&matches), matches.opt_present("hourly")),
+                Err(e) => format!("Weather history lookup failed: {}", e)
+            },
+            (Some(Ok(query)), Ok(None), Ok(_)) => {
+                let (obs, (alerts, logged)) = tokio::join!(
+                    get_cached_observation(&config, &query, matches.opt_present("refresh")),
+                    alerts_text(&config, &query));
+                alerts_logged = logged;
+                match obs {
+                    Ok(obs) => format!("{}{}{}", current_text(&obs, &matches), alerts, check_rules(&config, &[(&query, &obs)]).await),
+                    Err(e) => format!("Weather lookup failed: {}", e)
+                }
+            },
+            (_, Err(e), _) | (_, _, Err(e)) => e
+        };
+        let logged = format!("{} (\n{}\n)", if past { "Weather history retrieved" } else { "Temperature retrieved" }, to_print);
+        let logged = match (past, &query) {
+            (false, Some(Ok(q))) => record_cache_key(&config, &redact_location_query(&config.history, &logged, &input, Some(q)), q),
+            _ => redact_location_query(&config.history, &logged, &input, query.as_ref().and_then(|q| q.as_ref().ok()))
+        };
+        to_file = std::iter::once(logged)
+            .chain(alerts_logged.iter().map(|t| redact_location_query(&config.history, t, &input, query.as_ref().and_then(|q| q.as_ref().ok()))))
+            .collect();
+    } else if location_opts(&matches).len() > 1 {
+        (to_print, to_file) = lookup_table(&config, location_opts(&matches), &matches).await;
+    } else if let (input, Some(query)) = single_location(location_opts(&matches)) {
+        let mut alerts_logged = Vec::new();
+        to_print = match &query {
+            Ok(q) => {
+                let (obs, (alerts, logged)) = tokio::join!(
+                    get_cached_observation(&config, q, matches.opt_present("refresh")),
+                    alerts_text(&config, q));
+                alerts_logged = logged;
+                match obs {
+                    Ok(obs) => format!("{}{}{}", current_text(&obs, &matches), alerts, check_rules(&config, &[(q, &obs)]).await),
+                    Err(e) => format!("Weather lookup failed: {}", e)
                 }
             },
-            None => to_print = "".to_string()
+            Err(e) => e.to_string()
+        };
+        let by = if matches.opt_present("zip") { " by ZIP code" } else { "" };
+        let logged = format!("Temperature retrieved{} (\n{}\n)", by, to_print);
+        let logged = match &query {
+            Ok(q) => record_cache_key(&config, &redact_location_query(&config.history, &logged, &input, Some(q)), q),
+            Err(_) => redact_location_query(&config.history, &logged, &input, None)
         };
-        to_file = format!("Temperature retrieved by ZIP code (\n{}\n)", to_print).to_string();
+        to_file = std::iter::once(logged)
+            .chain(alerts_logged.iter().map(|t| redact_location_query(&config.history, t, &input, query.as_ref().ok())))
+            .collect();
     } else if matches.opt_present("read") {
         to_print = match read_from_file() {
             Ok(t) => format!("-= Print use history =-\n{}", t),
             Err(_) => "File read error".to_string()
         };
-        to_file = "History accessed".to_string();
+        to_file = vec!["History accessed".to_string()];
+    } else if matches.free.first().is_some_and(|c| c == "history") {
+        let logged;
+        (to_print, logged) = match matches.free.get(1).map(String::as_str) {
+            Some("stats") => (
+                match (parse_date(matches.opt_str("since")), parse_date(matches.opt_str("until"))) {
+                    (Ok(since), Ok(until)) => match read_entries(&config.history) {
+                        Ok(t) => format!(
+                            "-= Summarize use history =-\n{}",
+                            format_summary(&summarize(&t, since, until), Scale::Celsius)),
+                        Err(_) => "File read error".to_string()
+                    },
+                    (Err(e), _) | (_, Err(e)) => e
+                },
+                "History summarized".to_string()
+            ),
+            Some("export") => (
+                match (
+                    parse_date(matches.opt_str("since")),
+                    parse_date(matches.opt_str("until")),
+                    matches.opt_str("format").unwrap_or("csv".to_string())
+                ) {
+                    (Ok(since), Ok(until), f) => match (ExportFormat::from_name(&f), read_entries(&config.history)) {
+                        (Some(format), Ok(t)) => {
+                            let entries: Vec<HistoryEntry> = t.into_iter().filter(|e| e.in_period(since, until)).collect();
+                            let out = export_history(&entries, format);
+                            match matches.opt_str("output") {
+                                Some(path) => match std::fs::write(&path, out) {
+                                    Ok(_) => format!("Exported {} history entries to {}", entries.len(), path),
+                                    Err(e) => e.to_string()
+                                },
+                                None => out.trim_end().to_string()
+                            }
+                        },
+                        (None, _) => format!("unknown export format {}", f),
+                        (_, Err(_)) => "File read error".to_string()
+                    },
+                    (Err(e), _, _) | (_, Err(e), _) => e
+                },
+                "History exported".to_string()
+            ),
+            Some("prune") => {
+                let pruned = match parse_date(matches.opt_str("before")) {
+                    Ok(Some(date)) => prune_before(&config.history, date).map_err(|e| e.to_string()),
+                    Ok(None) => apply_retention(&config.history, chrono::Local::now()).map_err(|e| e.to_string()),
+                    Err(e) => Err(e)
+                };
+                match pruned {
+                    Ok(n) => (format!("Removed {} history entries", n), format!("History pruned ({} entries removed)", n)),
+                    Err(e) => (e, "History prune failed".to_string())
+                }
+            },
+            Some("clear") => match clear_history(&config.history) {
+                Ok(_) => ("History cleared".to_string(), "History cleared".to_string()),
+                Err(e) => (e.to_string(), "History clear failed".to_string())
+            },
+            Some(c) => (format!("unknown history command {}", c), "Invalid entry".to_string()),
+            None => ("Enter a history command (ex: history stats)".to_string(), "Invalid entry".to_string())
+        };
+        to_file = vec![logged];
     } else {
         to_print = "Enter -h or --help to see a list of commands".to_string();
-        to_file = "Invalid entry".to_string();
+        to_file = vec!["Invalid entry".to_string()];
     }
 
-    let _ = write_to_file(&to_file, Application::CLI);
+    if !matches.opt_present("no-history") {
+        for text in &to_file {
+            let _ = write_to_file(text, Application::CLI);
+        }
+    }
 
     println!("{}", to_print);
 
     Ok(())
 }
 
+/// Renders current conditions the way -z prints them with when they were observed, saying if they came from the cache or are stale,
+/// with details if --details was given and air quality if it was looked up.
+fn current_text(cached: &CachedObservation, matches: &getopts::Matches) -> String {
+    let obs = &cached.observation;
+    let r = calculate((Scale::Celsius, obs.temp_c));
+    let note = match cached.cache_note(chrono::Utc::now().timestamp()) {
+        Some(n) => format!("\n    ({})", n),
+        None => "".to_string()
+    };
+    let observed = match format_observed_time(obs, user_zone()) {
+        Some(t) => format!("\n    Observed: {}", t),
+        None => "".to_string()
+    };
+    let out = format!(
+        "-= Retrieve temperature in {}, {} =-{}\n    {:?}: {}\n    {:?}: {}\n    {:?}: {}{}",
+        obs.place.name, obs.place.region, note, r.0.0, r.0.1, r.1.0, r.1.1, r.2.0, r.2.1, observed);
+    let out = if matches.opt_present("details") {
+        format!("{}\n-= Details =-\n{}", out, format_details(obs, &scales_opt(matches)))
+    } else {
+        out
+    };
+    match (&obs.air_quality, matches.opt_present("air-quality")) {
+        (Some(aq), _) => format!("{}\n-= Air quality =-\n{}", out, format_air_quality(aq)),
+        (None, true) => format!("{}\n-= Air quality =-\n    Not reported for this location by this provider", out),
+        (None, false) => out
+    }
+}
+
+/// Polls one location every `every` until Ctrl-C, printing each poll that changed (or every poll with --every-tick)
+/// and recording one at most every `log_interval_minutes`. Returns what to print once stopped.
+async fn watch(config: &Config, matches: &getopts::Matches, every: &str) -> (String, Vec<String>) {
+    let located = if matches.free.first().is_some_and(|c| c == "weather") { location_args(matches) } else { location_opts(matches) };
+    let (interval, input, query) = match (parse_interval(every), single_location(located)) {
+        (Err(e), _) | (_, (_, Some(Err(e)))) => return (e, vec!["Invalid entry".to_string()]),
+        (_, (_, None)) => return ("Enter a location to watch (ex: -z 99801 --watch 5m)".to_string(), vec!["Invalid entry".to_string()]),
+        (Ok(interval), (input, Some(Ok(query)))) => (interval, input, query)
+    };
+    let scale = scales_opt(matches).first().copied().unwrap_or(Scale::Celsius);
+    let every_tick = config.watch.every_tick || matches.opt_present("every-tick");
+    let mut watcher = Watcher::new(scale, every_tick, config.watch.log_interval_minutes * 60);
+    let mut ticks = tokio::time::interval(interval);
+    ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    let stop = tokio::signal::ctrl_c();
+    tokio::pin!(stop);
+    loop {
+        tokio::select! {
+            _ = &mut stop => break,
+            _ = ticks.tick() => {}
+        }
+        let fetched = tokio::select! {
+            _ = &mut stop => break,
+            fetched = get_cached_observation(config, &query, true) => fetched
+        };
+        let now = chrono::Local::now();
+        match fetched {
+            Ok(cached) => {
+                let update = watcher.sample(&cached.observation, now.timestamp());
+                if let Some(line) = update.line {
+                    println!("[{}] {}", now.format("%H:%M:%S"), line);
+                }
+                let failed = check_rules(config, &[(&query, &cached)]).await;
+                if !failed.is_empty() {
+                    println!("[{}] {}", now.format("%H:%M:%S"), failed.trim_start());
+                }
+                if update.log && !matches.opt_present("no-history") {
+                    let logged = format!("Temperature retrieved (\n{}\n)", current_text(&cached, matches));
+                    let logged = redact_location_query(&config.history, &logged, &input, Some(&query));
+                    let _ = write_to_file(&record_cache_key(config, &logged, &query), Application::CLI);
+                }
+            },
+            Err(e) => println!("[{}] Weather lookup failed: {}", now.format("%H:%M:%S"), e)
+        }
+    }
+    (format!("Stopped watching {}", input), Vec::new())
+}
+
+/// The alerts at `query` if --alerts was given (or alerts are on in the config), to print after current conditions,
+/// with a history entry for each alert.
+async fn alerts_text(config: &Config, query: &LocationQuery) -> (String, Vec<String>) {
+    if !config.weather.alerts {
+        return ("".to_string(), Vec::new());
+    }
+    match get_alerts(&config.weather, query).await {
+        Ok(alerts) => (format!("\n{}", format_alerts(&alerts)), alert_log_entries(&alerts)),
+        Err(e) => (format!("\n-= Alerts =-\n    Alert lookup failed: {}", e), Vec::new())
+    }
+}
+
+/// The locations chosen with -z (and --country), --city, --coords, --iata, --auto-ip or -l, in the order given,
+/// each with the text that was entered. Nothing is sent for a location that doesn't validate.
+fn location_opts(matches: &getopts::Matches) -> Vec<(String, Result<LocationQuery, String>)> {
+    let kinds = [
+        ("zip", QueryKind::PostalCode),
+        ("city", QueryKind::City),
+        ("coords", QueryKind::Coordinates),
+        ("iata", QueryKind::Iata),
+        ("location", QueryKind::Auto),
+    ];
+    let country = matches.opt_str("country");
+    let mut located: Vec<(usize, String, Result<LocationQuery, String>)> = kinds.iter()
+        .flat_map(|(name, kind)| matches.opt_strs_pos(name).into_iter().map(|(pos, input)| {
+            let query = LocationQuery::build(*kind, &input, country.as_deref());
+            (pos, input, query)
+        }))
+        .collect();
+    if let Some(pos) = matches.opt_positions("auto-ip").first() {
+        located.push((*pos, "auto:ip".to_string(), Ok(LocationQuery::AutoIp)));
+    }
+    located.sort_by_key(|l| l.0);
+    located.into_iter().map(|(_, input, query)| (input, query)).collect()
+}
+
+/// The locations for a subcommand: the location options, or whatever follows the subcommand as one location.
+fn location_args(matches: &getopts::Matches) -> Vec<(String, Result<LocationQuery, String>)> {
+    let located = location_opts(matches);
+    if located.is_empty() && matches.free.len() > 1 {
+        let input = matches.free[1..].join(" ");
+        let query = LocationQuery::build(QueryKind::Auto, &input, matches.opt_str("country").as_deref());
+        return vec![(input, query)];
+    }
+    located
+}
+
+/// The only location given, or an error if there were several.
+fn single_location(mut located: Vec<(String, Result<LocationQuery, String>)>) -> (String, Option<Result<LocationQuery, String>>) {
+    match located.len() {
+        0 => ("".to_string(), None),
+        1 => {
+            let (input, query) = located.remove(0);
+            (input, Some(query))
+        },
+        n => ("".to_string(), Some(Err(format!("Enter one location at a time for this command ({} given)", n))))
+    }
+}
+
+/// Looks up current conditions at every location at once, returning the combined table and a history entry for each location.
+async fn lookup_table(config: &Config, located: Vec<(String, Result<LocationQuery, String>)>, matches: &getopts::Matches) -> (String, Vec<String>) {
+    let queries: Vec<LocationQuery> = located.iter().filter_map(|(_, q)| q.clone().ok()).collect();
+    let found = get_cached_observations(config, &queries, matches.opt_present("refresh")).await;
+    let fetched: Vec<_> = queries.iter().zip(&found).filter_map(|(q, c)| Some((q, c.as_ref().ok()?))).collect();
+    let failed = check_rules(config, &fetched).await;
+    let mut found = found.into_iter();
+    let now = chrono::Utc::now().timestamp();
+    let mut rows = Vec::new();
+    let mut logged = Vec::new();
+    for (input, query) in located {
+        let observation = match &query {
+            Ok(_) => found.next().expect("one result per query").map_err(|e| e.to_string()),
+            Err(e) => Err(e.to_string())
+        };
+        let text = match &observation {
+            Ok(obs) => current_text(obs, matches),
+            Err(e) if query.is_ok() => format!("Weather lookup failed: {}", e),
+            Err(e) => e.to_string()
+        };
+        let text = redact_location_query(&config.history, &format!("Temperature retrieved (\n{}\n)", text), &input, query.as_ref().ok());
+        logged.push(match &query {
+            Ok(query) => record_cache_key(config, &text, query),
+            Err(_) => text
+        });
+        let cached = observation.as_ref().ok().and_then(|c| c.age_label(now));
+        rows.push(LocationRow { location: input, observation: observation.map(|c| c.observation), cached });
+    }
+    (format!("{}{}", format_location_table(&rows, &scales_opt(matches)), failed), logged)
+}
+
+/// Checks the threshold rules against observations just looked up, which runs the notifiers of any that trigger.
+/// Returns a line for each notifier that failed.
+async fn check_rules(config: &Config, found: &[(&LocationQuery, &CachedObservation)]) -> String {
+    check_thresholds(config, found).await.iter()
+        .flat_map(|event| event.failures.iter().map(move |f| format!("\nNotifying {} failed: {}", event.rule, f)))
+        .collect()
+}
+
+/// The scales chosen with --scales, or all of them.
+fn scales_opt(matches: &getopts::Matches) -> Vec<Scale> {
+    match matches.opt_str("scales").map(|s| parse_scales(&s)) {
+        Some(Ok(s)) => s,
+        Some(Err(e)) => {
+            eprintln!("{}, showing all scales", e);
+            vec![Scale::Celsius, Scale::Kelvin, Scale::Fahrenheit]
+        },
+        None => vec![Scale::Celsius, Scale::Kelvin, Scale::Fahrenheit]
+    }
+}
+
+fn parse_date(input: Option<String>) -> Result<Option<NaiveDate>, String> {
+    match input {
+        Some(d) => match NaiveDate::parse_from_str(&d, "%Y-%m-%d") {
+            Ok(t) => Ok(Some(t)),
+            Err(_) => Err(format!("invalid date {}", d))
+        },
+        None => Ok(None)
+    }
+}
+
 fn parse_temp_input(input: &str) -> Result<(Scale, f32), String> {
     // get all but last character
     let temp_str = input.chars().take(input.len() - 1).collect::<String>();
@@ -114,7 +512,7 @@ fn parse_temp_input(input: &str) -> Result<(Scale, f32), String> {
         }
     };
 
-    return Ok((scale, temp));
+    Ok((scale, temp))
 }
 
 macro_rules! test_input_parse_succeed {
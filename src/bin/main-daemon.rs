@@ -0,0 +1,128 @@
+use std::env;
+use std::process::ExitCode;
+use std::time::Duration;
+
+use getopts::Options;
+
+use temperatureconverter::*;
+
+use temperatureconverter::history::*;
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let mut config = match Config::load() {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("{}", e);
+            Config::default()
+        }
+    };
+    if let Err(e) = apply_retention(&config.history, chrono::Local::now()) {
+        eprintln!("unable to apply history retention: {}", e);
+    }
+
+    let args: Vec<String> = env::args().collect();
+    let mut opts = Options::new();
+    opts.optflag("h", "help", "print this help");
+    opts.optopt("", "interval", "how often to poll, e.g. 30s, 5m or 1h (overrides config)", "INTERVAL");
+    opts.optopt("", "socket", "the Unix domain socket to answer on (overrides config)", "PATH");
+    let matches = match opts.parse(&args[1..]) {
+        Ok(m) => m,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    if matches.opt_present("help") {
+        println!(
+            "-= temperature-converter daemon =-\n    Polls the locations under [daemon] in temperature-converter.toml, records each observation in the use history \
+            and checks the threshold rules.\n    The CLI and GUI ask it for current weather while it is running.\n    \
+            --interval INTERVAL  :  How often to poll (ex: 30s, 5m, 1h; default interval_minutes from the config)\n    \
+            --socket PATH  :  The Unix domain socket to answer on (default socket_path from the config)\n Stop it with Ctrl-C.");
+        return ExitCode::SUCCESS;
+    }
+    let interval = match matches.opt_str("interval").map(|i| parse_interval(&i)) {
+        Some(Ok(interval)) => interval,
+        Some(Err(e)) => {
+            eprintln!("{}", e);
+            return ExitCode::FAILURE;
+        },
+        None => Duration::from_secs(config.daemon.interval_minutes.max(1) * 60),
+    };
+    if let Some(path) = matches.opt_str("socket") {
+        config.daemon.socket_path = Some(path);
+    }
+    let located = match daemon_locations(&config.daemon) {
+        Ok(l) => l,
+        Err(e) => {
+            eprintln!("invalid location under [daemon]: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    run(config, located, interval).await
+}
+
+/// Polls `located` every `interval` and answers the socket until Ctrl-C or SIGTERM, then removes the socket.
+#[cfg(unix)]
+async fn run(config: Config, located: Vec<(String, LocationQuery)>, interval: Duration) -> ExitCode {
+    use std::sync::Arc;
+
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let socket = config.daemon.socket_file();
+    let listener = match bind_socket(&socket) {
+        Ok(l) => l,
+        Err(e) => {
+            eprintln!("unable to listen on {}: {}", socket, e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut terminate = match signal(SignalKind::terminate()) {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("unable to handle SIGTERM: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let daemon = Arc::new(Daemon::new(config, interval));
+    let server = tokio::spawn(serve(daemon.clone(), listener));
+    if located.is_empty() {
+        println!("No locations to poll, add some under [daemon] in temperature-converter.toml. Answering on {}", socket);
+    } else {
+        let names: Vec<&str> = located.iter().map(|(input, _)| input.as_str()).collect();
+        println!("Polling {} every {:?}, answering on {}", names.join("; "), interval, socket);
+    }
+
+    let mut ticks = tokio::time::interval(interval);
+    ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    let stop = tokio::signal::ctrl_c();
+    tokio::pin!(stop);
+    loop {
+        tokio::select! {
+            _ = &mut stop => break,
+            _ = terminate.recv() => break,
+            _ = ticks.tick() => {}
+        }
+        if located.is_empty() {
+            continue;
+        }
+        let polled = tokio::select! {
+            _ = &mut stop => break,
+            _ = terminate.recv() => break,
+            polled = daemon.poll(&located) => polled
+        };
+        println!("[{}] {}", chrono::Local::now().format("%H:%M:%S"), polled);
+    }
+
+    server.abort();
+    let _ = std::fs::remove_file(&socket);
+    println!("Stopped, no longer answering on {}", socket);
+    ExitCode::SUCCESS
+}
+
+#[cfg(not(unix))]
+async fn run(_config: Config, _located: Vec<(String, LocationQuery)>, _interval: Duration) -> ExitCode {
+    eprintln!("the daemon answers on a Unix domain socket, which this platform doesn't have");
+    ExitCode::FAILURE
+}
@@ -10,8 +10,13 @@ use egui::*;
 
 use temperatureconverter::*;
 
+use temperatureconverter::history::*;
+
 use tokio::runtime::Runtime;
 
+/// Amber, for weather shown from storage because the service couldn't be reached.
+const STALE_COLOR: Color32 = Color32::from_rgb(255, 200, 80);
+
 fn main() -> eframe::Result {
 
     let rt = Runtime::new().expect("Unable to create Runtime");
@@ -26,6 +31,9 @@ fn main() -> eframe::Result {
     });
 
     env_logger::init();
+    if let Err(e) = apply_retention(&Config::load().unwrap_or_default().history, chrono::Local::now()) {
+        eprintln!("unable to apply history retention: {}", e);
+    }
     let options = eframe::NativeOptions {
         viewport: ViewportBuilder::default().with_inner_size([320.0, 240.0]),
         ..Default::default()
@@ -44,33 +52,168 @@ fn main() -> eframe::Result {
 #[derive(Debug, PartialEq)]
 enum Page {
     Temp,
-    Zip,
+    Location,
+    Locations,
+    Forecast,
     Hist
 }
 
+/// What to look up: the kind of location, the text entered and, for postal codes, the country.
+struct LocationInput {
+    kind: QueryKind,
+    text: String,
+    country: String,
+}
+
+impl LocationInput {
+    fn new(text: &str) -> Self {
+        LocationInput { kind: QueryKind::Auto, text: text.to_string(), country: "".to_string() }
+    }
+
+    fn show(&mut self, ui: &mut Ui, id: usize) {
+        ComboBox::from_id_source(("query-kind", id))
+        .selected_text(self.kind.label())
+        .show_ui(ui, |ui| {
+            for kind in QueryKind::ALL {
+                ui.selectable_value(&mut self.kind, kind, kind.label());
+            }
+        });
+        if self.kind != QueryKind::AutoIp {
+            ui.text_edit_singleline(&mut self.text);
+        }
+        if matches!(self.kind, QueryKind::Auto | QueryKind::PostalCode) {
+            ui.label("Country");
+            ui.add(TextEdit::singleline(&mut self.country).desired_width(30.0).hint_text("US"));
+        }
+    }
+
+    /// The entered location, validated before anything is sent.
+    fn query(&self) -> Result<LocationQuery, String> {
+        LocationQuery::build(self.kind, &self.text, Some(&self.country))
+    }
+
+    /// The text the location was entered as, redacted from the history along with the query it was read as.
+    fn entered(&self) -> String {
+        match self.kind {
+            QueryKind::AutoIp => "".to_string(),
+            _ => self.text.trim().to_string(),
+        }
+    }
+
+    /// How the location is shown when it couldn't be looked up.
+    fn label(&self) -> String {
+        match self.kind {
+            QueryKind::AutoIp => self.kind.label().to_string(),
+            _ => self.text.trim().to_string(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum HistView {
+    Log,
+    Summary
+}
+
 struct MyApp {
-    tx: Sender<String>,
-    rx: Receiver<String>,
+    tx: Sender<Result<(String, CachedObservation), String>>,
+    rx: Receiver<Result<(String, CachedObservation), String>>,
     temperature: f32,
     scale: Scale,
-    zip: String,
-    zipout: String,
+    location: LocationInput,
+    zipout: Result<String, String>,
+    observation: Option<Observation>,
+    stale: bool,
+    offline: bool,
+    air_quality: bool,
+    show_alerts: bool,
+    alerts_tx: Sender<Result<WeatherAlerts, String>>,
+    alerts_rx: Receiver<Result<WeatherAlerts, String>>,
+    alerts: Option<Result<WeatherAlerts, String>>,
+    detail_scales: Vec<Scale>,
+    past_tx: Sender<Result<WeatherHistory, String>>,
+    past_rx: Receiver<Result<WeatherHistory, String>>,
+    on_date: bool,
+    past_date: chrono::NaiveDate,
+    past: Option<Result<WeatherHistory, String>>,
+    astronomy_tx: Sender<Result<Astronomy, String>>,
+    astronomy_rx: Receiver<Result<Astronomy, String>>,
+    astronomy_date: chrono::NaiveDate,
+    astronomy: Option<Result<Astronomy, String>>,
+    forecast_tx: Sender<Result<Forecast, String>>,
+    forecast_rx: Receiver<Result<Forecast, String>>,
+    forecast_days: u32,
+    forecast: Option<Result<Forecast, String>>,
+    locations: Vec<LocationInput>,
+    table_tx: Sender<(Vec<LocationRow>, String)>,
+    table_rx: Receiver<(Vec<LocationRow>, String)>,
+    table: Vec<LocationRow>,
+    /// A line for each threshold notifier that failed during the last table lookup.
+    table_failures: String,
     history: String,
+    hist_view: HistView,
+    summary: String,
+    summary_days: u32,
+    confirm_clear: bool,
+    export_open: bool,
+    export_format: ExportFormat,
+    export_path: String,
+    export_status: String,
     page: Page
 }
 
 impl Default for MyApp {
     fn default() -> Self {
         let (tx, rx) = std::sync::mpsc::channel();
+        let (forecast_tx, forecast_rx) = std::sync::mpsc::channel();
+        let (past_tx, past_rx) = std::sync::mpsc::channel();
+        let (table_tx, table_rx) = std::sync::mpsc::channel();
+        let (alerts_tx, alerts_rx) = std::sync::mpsc::channel();
+        let (astronomy_tx, astronomy_rx) = std::sync::mpsc::channel();
 
         Self {
             tx,
             rx,
             temperature: 32.0,
             scale: Scale::Fahrenheit,
-            zip: "20500".to_string(),
-            zipout: "Press Go!".to_string(),
+            location: LocationInput::new("20500"),
+            zipout: Ok("Press Go!".to_string()),
+            observation: None,
+            stale: false,
+            offline: false,
+            air_quality: false,
+            show_alerts: false,
+            alerts_tx,
+            alerts_rx,
+            alerts: None,
+            detail_scales: vec![Scale::Celsius, Scale::Kelvin, Scale::Fahrenheit],
+            past_tx,
+            past_rx,
+            on_date: false,
+            past_date: chrono::Local::now().date_naive() - chrono::Days::new(7),
+            past: None,
+            astronomy_tx,
+            astronomy_rx,
+            astronomy_date: chrono::Local::now().date_naive(),
+            astronomy: None,
+            forecast_tx,
+            forecast_rx,
+            forecast_days: 3,
+            forecast: None,
+            locations: vec![LocationInput::new("20500"), LocationInput::new("99801")],
+            table_tx,
+            table_rx,
+            table: Vec::new(),
+            table_failures: String::new(),
             history: "".to_string(),
+            hist_view: HistView::Log,
+            summary: "".to_string(),
+            summary_days: 0,
+            confirm_clear: false,
+            export_open: false,
+            export_format: ExportFormat::Csv,
+            export_path: "temperature-history.csv".to_string(),
+            export_status: "".to_string(),
             page: Page::Temp
         }
     }
@@ -80,19 +223,47 @@ impl eframe::App for MyApp {
     fn update(&mut self, ctx: &Context, _frame: &mut eframe::Frame) {
 
         if let Ok(zipout) = self.rx.try_recv() {
-            self.zipout = zipout;
+            match zipout {
+                Ok((out, cached)) => {
+                    self.zipout = Ok(out);
+                    self.stale = cached.is_stale();
+                    self.observation = Some(cached.observation);
+                },
+                Err(e) => {
+                    self.zipout = Err(e);
+                    self.observation = None;
+                }
+            }
+        }
+        if let Ok(alerts) = self.alerts_rx.try_recv() {
+            self.alerts = Some(alerts);
+        }
+        if let Ok(past) = self.past_rx.try_recv() {
+            self.past = Some(past);
+        }
+        if let Ok(astronomy) = self.astronomy_rx.try_recv() {
+            self.astronomy = Some(astronomy);
+        }
+        if let Ok(forecast) = self.forecast_rx.try_recv() {
+            self.forecast = Some(forecast);
+        }
+        if let Ok((table, failed)) = self.table_rx.try_recv() {
+            self.table = table;
+            self.table_failures = failed;
         }
 
         CentralPanel::default().show(ctx, |ui| {
             ui.horizontal(|ui| {
                 ui.selectable_value(&mut self.page, Page::Temp, "Converter");
-                ui.selectable_value(&mut self.page, Page::Zip, "Zip Code Lookup");
+                ui.selectable_value(&mut self.page, Page::Location, "Location Lookup");
+                ui.selectable_value(&mut self.page, Page::Locations, "Locations");
+                ui.selectable_value(&mut self.page, Page::Forecast, "Forecast");
                 ui.selectable_value(&mut self.page, Page::Hist, "History");
 
             });
 
             ui.separator();
-            if (self.page == Page::Temp) {
+            if self.page == Page::Temp {
                 ui.heading("Converter");
                 ui.horizontal(|ui| {
                     ui.label("Select Scale");
@@ -131,59 +302,612 @@ impl eframe::App for MyApp {
                 ui.label(RichText::new(conv_temps(self.temperature, self.scale)).color(Color32::from_rgb(110, 255, 110)));
             }
 
-            if (self.page == Page::Zip) {
-                ui.heading("Zip Lookup");
+            if self.page == Page::Location {
+                ui.heading("Location Lookup");
+                ui.horizontal(|ui| {
+                    ui.label("Location");
+                    self.location.show(ui, 0);
+                });
                 ui.horizontal(|ui| {
-                    ui.label("Input Zip");
-                    ui.text_edit_singleline(&mut self.zip);
+                    ui.checkbox(&mut self.on_date, "On date");
+                    if self.on_date {
+                        ui.add(egui_extras::DatePickerButton::new(&mut self.past_date));
+                    } else {
+                        ui.checkbox(&mut self.air_quality, "Air quality")
+                            .on_hover_text("Also look up PM2.5, PM10, ozone, NO2 and the US EPA / UK DEFRA indices (WeatherAPI only)");
+                        ui.checkbox(&mut self.show_alerts, "Alerts")
+                            .on_hover_text("Also look up active weather alerts (WeatherAPI and NWS)");
+                    }
                 });
-                if ui.button("Go!").clicked() {
-                    get_temps_from_zip(&self.zip, ctx.clone(), self.tx.clone());
+                let (go, refresh) = ui.horizontal(|ui| {
+                    let go = ui.button("Go!").clicked();
+                    let refresh = !self.on_date && ui.button("Refresh")
+                        .on_hover_text("Fetch again instead of reusing a recent observation").clicked();
+                    self.offline_checkbox(ui);
+                    (go, refresh)
+                }).inner;
+                if go || refresh {
+                    match (self.location.query(), self.on_date) {
+                        (Ok(query), true) => {
+                            get_past_weather(query, self.location.entered(), self.past_date, self.lookup_config(), ctx.clone(), self.past_tx.clone());
+                        },
+                        (Ok(query), false) => {
+                            self.alerts = None;
+                            if self.show_alerts {
+                                get_alerts_for(query.clone(), self.location.entered(), self.lookup_config(), ctx.clone(), self.alerts_tx.clone());
+                            }
+                            get_temps(query, self.location.entered(), refresh, self.lookup_config(), ctx.clone(), self.tx.clone())
+                        },
+                        (Err(e), true) => self.past = Some(Err(e)),
+                        (Err(e), false) => {
+                            self.zipout = Err(e);
+                            self.observation = None;
+                        }
+                    }
+                }
+                if self.on_date {
+                    self.scale_picker(ui);
+                    match self.past.clone() {
+                        Some(Ok(past)) => self.past_panel(ui, &past),
+                        Some(Err(e)) => {
+                            ui.label(RichText::new(e).color(Color32::from_rgb(255, 110, 110)));
+                        },
+                        None => {}
+                    }
+                } else {
+                    match &self.alerts {
+                        Some(Ok(alerts)) => alert_banners(ui, alerts),
+                        Some(Err(e)) => {
+                            ui.label(RichText::new(e).color(Color32::from_rgb(255, 110, 110)));
+                        },
+                        None => {}
+                    }
+                    ui.horizontal(|ui| {
+                        if let Some(obs) = &self.observation {
+                            condition_image(ui, obs);
+                        }
+                        match &self.zipout {
+                            Ok(out) if self.stale => ui.label(RichText::new(out).color(STALE_COLOR)),
+                            Ok(out) => ui.label(RichText::new(out).color(Color32::from_rgb(110, 255, 110))),
+                            Err(e) => ui.label(RichText::new(e).color(Color32::from_rgb(255, 110, 110))),
+                        };
+                    });
+                    if let Some(obs) = &self.observation {
+                        let air_quality = obs.air_quality.clone();
+                        self.details_panel(ui, obs.clone());
+                        if let Some(aq) = air_quality {
+                            air_quality_panel(ui, &aq);
+                        }
+                    }
                 }
-                ui.label(RichText::new(&self.zipout).color(Color32::from_rgb(110, 255, 110)));
+                self.astronomy_panel(ui, ctx);
             }
 
-            if (self.page == Page::Hist) {
+            if self.page == Page::Locations {
+                ui.heading("Locations");
+                let mut remove = None;
+                for (i, location) in self.locations.iter_mut().enumerate() {
+                    ui.horizontal(|ui| {
+                        location.show(ui, i + 1);
+                        if ui.button("Remove").clicked() {
+                            remove = Some(i);
+                        }
+                    });
+                }
+                if let Some(i) = remove {
+                    self.locations.remove(i);
+                }
+                ui.horizontal(|ui| {
+                    if ui.button("Add location").clicked() {
+                        self.locations.push(LocationInput::new(""));
+                    }
+                    let go = ui.button("Go!").clicked();
+                    let refresh = ui.button("Refresh").on_hover_text("Fetch again instead of reusing recent observations").clicked();
+                    self.offline_checkbox(ui);
+                    if go || refresh {
+                        let located = self.locations.iter().map(|l| (l.label(), l.entered(), l.query())).collect();
+                        get_temps_at(located, refresh, self.lookup_config(), ctx.clone(), self.table_tx.clone());
+                    }
+                });
+                self.scale_picker(ui);
+                self.location_table(ui);
+            }
+
+            if self.page == Page::Forecast {
+                ui.heading("Forecast");
+                ui.horizontal(|ui| {
+                    ui.label("Location");
+                    self.location.show(ui, 0);
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Days");
+                    ui.add(DragValue::new(&mut self.forecast_days).range(1..=14));
+                    if ui.button("Go!").clicked() {
+                        match self.location.query() {
+                            Ok(query) => {
+                                get_forecast_for(query, self.location.entered(), self.forecast_days, self.lookup_config(), ctx.clone(), self.forecast_tx.clone());
+                            },
+                            Err(e) => self.forecast = Some(Err(e))
+                        }
+                    }
+                });
+                self.scale_picker(ui);
+                match self.forecast.clone() {
+                    Some(Ok(forecast)) => self.forecast_panel(ui, &forecast),
+                    Some(Err(e)) => {
+                        ui.label(RichText::new(e).color(Color32::from_rgb(255, 110, 110)));
+                    },
+                    None => {}
+                }
+            }
+
+            if self.page == Page::Hist {
                 ui.heading("History");
-                if ui.button(RichText::new("Update").color(Color32::from_rgb(110, 255, 110))).clicked() {
-                    self.history = match read_from_file() {
-                        Ok(t) => {
-                            let _ = write_to_file(&"History accesed".to_string(), Application::GUI);
-                            t
-                        },
-                        Err(e) => e.to_string()
+                ui.horizontal(|ui| {
+                    ui.selectable_value(&mut self.hist_view, HistView::Log, "Log");
+                    ui.selectable_value(&mut self.hist_view, HistView::Summary, "Summary");
+                });
+                if self.hist_view == HistView::Summary {
+                    ui.horizontal(|ui| {
+                        ui.label("Last days (0 for all)");
+                        ui.add(DragValue::new(&mut self.summary_days).range(0..=3650));
+                    });
+                }
+                ui.horizontal(|ui| {
+                    if ui.button(RichText::new("Update").color(Color32::from_rgb(110, 255, 110))).clicked() {
+                        self.update_history();
+                    }
+                    if ui.button("Export…").clicked() {
+                        self.export_open = true;
+                        self.export_status = "".to_string();
+                    }
+                    if ui.button(RichText::new("Clear").color(Color32::from_rgb(255, 110, 110))).clicked() {
+                        self.confirm_clear = true;
                     }
+                });
+                if self.export_open {
+                    self.export_window(ctx);
+                }
+                if self.confirm_clear {
+                    ui.horizontal(|ui| {
+                        ui.label("Delete all history, including archives?");
+                        if ui.button("Yes").clicked() {
+                            if let Err(e) = clear_history(&Config::load().unwrap_or_default().history) {
+                                self.history = e.to_string();
+                            } else {
+                                let _ = write_to_file("History cleared", Application::GUI);
+                                self.update_history();
+                            }
+                            self.confirm_clear = false;
+                        }
+                        if ui.button("No").clicked() {
+                            self.confirm_clear = false;
+                        }
+                    });
                 }
                 ScrollArea::vertical().show(ui, |ui| {
-                    ui.label(&self.history);
+                    match self.hist_view {
+                        HistView::Log => ui.label(&self.history),
+                        HistView::Summary => ui.label(&self.summary)
+                    };
                 });
             }
         });
     }
 }
 
+impl MyApp {
+    fn details_panel(&mut self, ui: &mut Ui, obs: Observation) {
+        CollapsingHeader::new("Details").default_open(true).show(ui, |ui| {
+            self.scale_picker(ui);
+            Grid::new("weather-details").num_columns(2).striped(true).show(ui, |ui| {
+                for (label, value) in detail_rows(&obs, &self.detail_scales) {
+                    ui.label(label);
+                    ui.label(value);
+                    ui.end_row();
+                }
+            });
+        });
+    }
+
+    /// Sunrise, sunset and the moon at the location on a chosen day, in the location's time zone.
+    fn astronomy_panel(&mut self, ui: &mut Ui, ctx: &Context) {
+        CollapsingHeader::new("Sun and moon").show(ui, |ui| {
+            ui.horizontal(|ui| {
+                ui.add(egui_extras::DatePickerButton::new(&mut self.astronomy_date).id_source("astronomy-date"));
+                if ui.button("Get").clicked() {
+                    match self.location.query() {
+                        Ok(query) => get_astronomy_for(query, self.location.entered(), self.astronomy_date, self.lookup_config(), ctx.clone(), self.astronomy_tx.clone()),
+                        Err(e) => self.astronomy = Some(Err(e)),
+                    }
+                }
+            });
+            match &self.astronomy {
+                Some(Ok(astro)) => {
+                    ui.label(RichText::new(format!("{}, {} on {}", astro.place.name, astro.place.region, astro.date)).strong());
+                    ui.label(format!("Times in {}", astronomy_zone(astro)));
+                    Grid::new("astronomy").num_columns(2).striped(true).show(ui, |ui| {
+                        for (label, value) in astronomy_rows(astro) {
+                            ui.label(label);
+                            ui.label(value);
+                            ui.end_row();
+                        }
+                    });
+                },
+                Some(Err(e)) => {
+                    ui.label(RichText::new(e).color(Color32::from_rgb(255, 110, 110)));
+                },
+                None => {}
+            }
+        });
+    }
+
+    /// The last multi-location lookup, one row per location, with failures in red.
+    fn location_table(&mut self, ui: &mut Ui) {
+        Grid::new("location-table").num_columns(5).striped(true).show(ui, |ui| {
+            for heading in ["Location", "Local time", "Condition", "Temperature", "Cached"] {
+                ui.strong(heading);
+            }
+            ui.end_row();
+            for row in &self.table {
+                match &row.observation {
+                    Ok(obs) => {
+                        ui.label(place_name(&obs.place));
+                        ui.label(observed_time(obs));
+                        ui.label(&obs.condition);
+                        ui.label(format_temp(obs.temp_c, &self.detail_scales));
+                        match row.cached.as_deref() {
+                            Some(age) if age.starts_with("STALE") => ui.label(RichText::new(age).color(STALE_COLOR)),
+                            age => ui.label(age.unwrap_or("")),
+                        };
+                    },
+                    Err(e) => {
+                        ui.label(&row.location);
+                        ui.label("-");
+                        ui.label(RichText::new(e).color(Color32::from_rgb(255, 110, 110)));
+                    }
+                }
+                ui.end_row();
+            }
+        });
+        if !self.table_failures.is_empty() {
+            ui.label(RichText::new(&self.table_failures).color(Color32::from_rgb(255, 110, 110)));
+        }
+    }
+
+    fn offline_checkbox(&mut self, ui: &mut Ui) {
+        ui.checkbox(&mut self.offline, "Offline")
+            .on_hover_text("Never contact the weather service, only show stored observations");
+    }
+
+    /// The saved configuration, kept off the network if Offline is ticked.
+    fn lookup_config(&self) -> Config {
+        let mut config = Config::load().unwrap_or_default();
+        config.weather.offline |= self.offline;
+        config.weather.air_quality |= self.air_quality;
+        config
+    }
+
+    fn scale_picker(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Show in");
+            for scale in [Scale::Celsius, Scale::Kelvin, Scale::Fahrenheit] {
+                let mut shown = self.detail_scales.contains(&scale);
+                if ui.checkbox(&mut shown, format!("{:?}", scale)).changed() {
+                    if shown {
+                        self.detail_scales.push(scale);
+                    } else {
+                        self.detail_scales.retain(|s| *s != scale);
+                    }
+                }
+            }
+        });
+    }
+
+    fn forecast_panel(&self, ui: &mut Ui, forecast: &Forecast) {
+        ui.label(RichText::new(format!("{}, {}", forecast.place.name, forecast.place.region)).strong());
+        let temp = |celsius: f32| format_temp(celsius, &self.detail_scales);
+        ScrollArea::vertical().show(ui, |ui| {
+            for day in &forecast.days {
+                let chance = day.precip_chance.map(|c| format!(", {}% precipitation", c)).unwrap_or_default();
+                CollapsingHeader::new(format!("{}: {}{}", day.date.format("%a %Y-%m-%d"), day.condition, chance))
+                    .id_source(day.date)
+                    .show(ui, |ui| {
+                        Grid::new(("forecast-day", day.date)).num_columns(2).striped(true).show(ui, |ui| {
+                            for (label, celsius) in [("Min", day.min_c), ("Max", day.max_c), ("Avg", day.avg_c)] {
+                                ui.label(label);
+                                ui.label(temp(celsius));
+                                ui.end_row();
+                            }
+                            for hour in &day.hours {
+                                ui.label(hour.local_time.format("%H:%M").to_string());
+                                ui.label(format!("{}  {}", temp(hour.temp_c), hour.condition));
+                                ui.end_row();
+                            }
+                        });
+                    });
+            }
+        });
+    }
+
+    fn past_panel(&self, ui: &mut Ui, past: &WeatherHistory) {
+        ui.label(RichText::new(format!("{}, {}", past.place.name, past.place.region)).strong());
+        let temp = |celsius: f32| format_temp(celsius, &self.detail_scales);
+        ScrollArea::vertical().show(ui, |ui| {
+            for day in &past.days {
+                let precip = day.precip_mm.map(|mm| format!(", {} mm precipitation", mm)).unwrap_or_default();
+                ui.label(format!("{}: {}{}", day.date.format("%a %Y-%m-%d"), day.condition, precip));
+                Grid::new(("past-day", day.date)).num_columns(2).striped(true).show(ui, |ui| {
+                    for (label, celsius) in [("Min", day.min_c), ("Max", day.max_c), ("Avg", day.avg_c)] {
+                        ui.label(label);
+                        ui.label(temp(celsius));
+                        ui.end_row();
+                    }
+                    for hour in &day.hours {
+                        ui.label(hour.local_time.format("%H:%M").to_string());
+                        ui.label(format!("{}  {}", temp(hour.temp_c), hour.condition));
+                        ui.end_row();
+                    }
+                });
+            }
+        });
+    }
+
+    fn export_window(&mut self, ctx: &Context) {
+        let mut open = self.export_open;
+        Window::new("Export History").open(&mut open).collapsible(false).show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Format");
+                ComboBox::from_id_source("export-format")
+                .selected_text(self.export_format.extension())
+                .show_ui(ui, |ui| {
+                    for format in [ExportFormat::Csv, ExportFormat::Json, ExportFormat::Markdown, ExportFormat::Html] {
+                        if ui.selectable_value(&mut self.export_format, format, format.extension()).clicked() {
+                            if let Some((stem, _)) = self.export_path.rsplit_once('.') {
+                                self.export_path = format!("{}.{}", stem, format.extension());
+                            }
+                        }
+                    }
+                });
+            });
+            ui.horizontal(|ui| {
+                ui.label("File");
+                ui.text_edit_singleline(&mut self.export_path);
+            });
+            if ui.button("Export").clicked() {
+                self.export_status = match read_entries(&Config::load().unwrap_or_default().history) {
+                    Ok(entries) => match std::fs::write(&self.export_path, export_history(&entries, self.export_format)) {
+                        Ok(_) => {
+                            let _ = write_to_file("History exported", Application::GUI);
+                            format!("Exported {} entries to {}", entries.len(), self.export_path)
+                        },
+                        Err(e) => e.to_string()
+                    },
+                    Err(e) => e.to_string()
+                };
+            }
+            ui.label(&self.export_status);
+        });
+        self.export_open = open;
+    }
+
+    fn update_history(&mut self) {
+        match read_from_file() {
+            Ok(t) => {
+                let since = match self.summary_days {
+                    0 => None,
+                    d => Some(chrono::Local::now().date_naive() - chrono::Days::new(d as u64 - 1))
+                };
+                self.summary = match read_entries(&Config::load().unwrap_or_default().history) {
+                    Ok(entries) => format_summary(&summarize(&entries, since, None), self.scale),
+                    Err(e) => e.to_string()
+                };
+                self.history = t;
+                let _ = write_to_file("History accesed", Application::GUI);
+            },
+            Err(e) => {
+                self.history = e.to_string();
+                self.summary = e.to_string();
+            }
+        }
+    }
+}
+
+/// A coloured banner per active alert, most severe first, so they can't be missed above the temperature.
+fn alert_banners(ui: &mut Ui, alerts: &WeatherAlerts) {
+    for alert in &alerts.alerts {
+        let (fill, text) = match alert.severity {
+            AlertSeverity::Extreme | AlertSeverity::Severe => (Color32::from_rgb(190, 30, 30), Color32::WHITE),
+            AlertSeverity::Moderate => (Color32::from_rgb(235, 130, 20), Color32::BLACK),
+            AlertSeverity::Minor | AlertSeverity::Unknown => (Color32::from_rgb(240, 210, 60), Color32::BLACK),
+        };
+        Frame::none().fill(fill).inner_margin(Margin::same(8.0)).rounding(Rounding::same(4.0)).show(ui, |ui| {
+            ui.set_width(ui.available_width());
+            ui.label(RichText::new(format!("⚠ {}", alert_summary(alert))).strong().size(16.0).color(text));
+            if !alert.headline.is_empty() {
+                ui.label(RichText::new(&alert.headline).color(text));
+            }
+            if !alert.areas.is_empty() {
+                ui.label(RichText::new(format!("Areas: {}", alert.areas.join("; "))).small().color(text));
+            }
+        });
+        ui.add_space(4.0);
+    }
+}
+
+/// The bundled icon for the observed condition, day or night, with the condition as its tooltip.
+fn condition_image(ui: &mut Ui, obs: &Observation) {
+    if let Some(icon) = obs.icon {
+        let image = icon_image(icon, obs.is_day);
+        ui.add(Image::from_bytes(image.uri, image.bytes).fit_to_exact_size(vec2(64.0, 64.0)))
+            .on_hover_text(&obs.condition);
+    }
+}
+
+fn air_quality_panel(ui: &mut Ui, aq: &AirQuality) {
+    CollapsingHeader::new("Air quality").default_open(true).show(ui, |ui| {
+        Grid::new("air-quality").num_columns(2).striped(true).show(ui, |ui| {
+            for (label, value, category) in air_quality_rows(aq) {
+                ui.label(label);
+                match category {
+                    Some(c) => ui.label(RichText::new(value).color(level_color(c.level))),
+                    None => ui.label(value),
+                };
+                ui.end_row();
+            }
+        });
+    });
+}
+
+/// The colours the US EPA uses for its categories, lightened a little to read on a dark background.
+fn level_color(level: AirQualityLevel) -> Color32 {
+    match level {
+        AirQualityLevel::Good => Color32::from_rgb(110, 255, 110),
+        AirQualityLevel::Moderate => Color32::from_rgb(255, 255, 90),
+        AirQualityLevel::UnhealthyForSensitive => Color32::from_rgb(255, 160, 60),
+        AirQualityLevel::Unhealthy => Color32::from_rgb(255, 110, 110),
+        AirQualityLevel::VeryUnhealthy => Color32::from_rgb(200, 120, 255),
+        AirQualityLevel::Hazardous => Color32::from_rgb(200, 60, 90),
+    }
+}
+
 fn conv_temps(temp: f32, scale: Scale) -> String{
     let t = calculate((scale, temp));
-    return format!("{:?}: {}\n{:?}: {}\n{:?}: {}", 
-        t.0.0, t.0.1, t.1.0, t.1.1, t.2.0, t.2.1);
+    format!("{:?}: {}\n{:?}: {}\n{:?}: {}", 
+        t.0.0, t.0.1, t.1.0, t.1.1, t.2.0, t.2.1)
 }
 
-fn get_temps_from_zip(input: &String, ctx: Context, tx: Sender<String>) {
-    let zip = input.to_string();
+fn get_temps(query: LocationQuery, input: String, refresh: bool, config: Config, ctx: Context, tx: Sender<Result<(String, CachedObservation), String>>) {
     tokio::spawn(async move {
-        let mut output:String = "".to_string();
-        let get = get_current_temp(zip.to_string()).await;
-        match get {
-            Ok(t) => output = {
-                let r = calculate((Scale::Celsius, t.2));
-                format!(
-                "Temperature in {}, {}\n{:?}: {}\n{:?}: {}\n{:?}: {}", 
-                t.0, t.1, r.0.0, r.0.1, r.1.0, r.1.1, r.2.0, r.2.1)
+        let get = get_cached_observation(&config, &query, refresh).await;
+        let failed = match &get {
+            Ok(cached) => rule_failures(&config, &[(&query, cached)]).await,
+            Err(_) => String::new()
+        };
+        let output = match get {
+            Ok(cached) => {
+                let note = cached.cache_note(chrono::Utc::now().timestamp()).map(|n| format!("\n({})", n)).unwrap_or_default();
+                let obs = &cached.observation;
+                let r = calculate((Scale::Celsius, obs.temp_c));
+                let observed = format_observed_time(obs, user_zone()).map(|t| format!("\nObserved: {}", t)).unwrap_or_default();
+                Ok((format!(
+                "Temperature in {}, {}{}\n{:?}: {}\n{:?}: {}\n{:?}: {}{}{}", 
+                obs.place.name, obs.place.region, note, r.0.0, r.0.1, r.1.0, r.1.1, r.2.0, r.2.1, observed, failed), cached))
             },
-            Err(e) => output = e.to_string()
+            Err(e) => Err(format!("Weather lookup failed: {}", e))
+        };
+        let by = if matches!(query, LocationQuery::PostalCode { .. }) { " by ZIP code" } else { "" };
+        let to_file = format!("Temperature retrieved{} (\n{}\n)", by, output.as_ref().map_or_else(|e| e, |o| &o.0));
+        let to_file = record_cache_key(&config, &redact_location_query(&config.history, &to_file, &input, Some(&query)), &query);
+        let _ = write_to_file(&to_file, Application::GUI);
+        let _ = tx.send(output);
+        ctx.request_repaint();
+    });
+}
+/// Looks up every valid location at once, logging each one, and sends back a row per location in order
+/// with a line for each threshold notifier that failed.
+fn get_temps_at(located: Vec<(String, String, Result<LocationQuery, String>)>, refresh: bool, config: Config, ctx: Context, tx: Sender<(Vec<LocationRow>, String)>) {
+    tokio::spawn(async move {
+        let queries: Vec<LocationQuery> = located.iter().filter_map(|(_, _, q)| q.clone().ok()).collect();
+        let found = get_cached_observations(&config, &queries, refresh).await;
+        let fetched: Vec<_> = queries.iter().zip(&found).filter_map(|(q, c)| Some((q, c.as_ref().ok()?))).collect();
+        let failed = rule_failures(&config, &fetched).await;
+        let mut found = found.into_iter();
+        let now = chrono::Utc::now().timestamp();
+        let mut rows = Vec::new();
+        for (location, input, query) in located {
+            let query = match query {
+                Ok(q) => q,
+                Err(e) => {
+                    rows.push(LocationRow { location, observation: Err(e), cached: None });
+                    continue;
+                }
+            };
+            let found = found.next().expect("one result per query").map_err(|e| format!("Weather lookup failed: {}", e));
+            let cached = found.as_ref().ok().and_then(|c| c.age_label(now));
+            let text = match &found {
+                Ok(c) => {
+                    let obs = &c.observation;
+                    let note = c.cache_note(now).map(|n| format!("\n({})", n)).unwrap_or_default();
+                    let r = calculate((Scale::Celsius, obs.temp_c));
+                    format!(
+                        "Temperature in {}, {}{}\n{:?}: {}\n{:?}: {}\n{:?}: {}",
+                        obs.place.name, obs.place.region, note, r.0.0, r.0.1, r.1.0, r.1.1, r.2.0, r.2.1)
+                },
+                Err(e) => e.to_string()
+            };
+            let observation = found.map(|c| c.observation);
+            let to_file = format!("Temperature retrieved (\n{}\n)", text);
+            let to_file = record_cache_key(&config, &redact_location_query(&config.history, &to_file, &input, Some(&query)), &query);
+            let _ = write_to_file(&to_file, Application::GUI);
+            rows.push(LocationRow { location, observation, cached });
+        }
+        let _ = tx.send((rows, failed.trim_start().to_string()));
+        ctx.request_repaint();
+    });
+}
+
+/// Checks the threshold rules against observations just looked up, which runs the notifiers of any that trigger.
+/// Returns a line for each notifier that failed.
+async fn rule_failures(config: &Config, found: &[(&LocationQuery, &CachedObservation)]) -> String {
+    check_thresholds(config, found).await.iter()
+        .flat_map(|event| event.failures.iter().map(move |f| format!("\nNotifying {} failed: {}", event.rule, f)))
+        .collect()
+}
+
+fn get_forecast_for(query: LocationQuery, input: String, days: u32, config: Config, ctx: Context, tx: Sender<Result<Forecast, String>>) {
+    tokio::spawn(async move {
+        let output = get_forecast(&config.weather, &query, days).await.map_err(|e| format!("Forecast failed: {}", e));
+        let text = match &output {
+            Ok(f) => format_forecast(f, &[Scale::Celsius], false),
+            Err(e) => e.to_string()
+        };
+        let to_file = format!("Forecast retrieved (\n{}\n)", text);
+        let _ = write_to_file(&redact_location_query(&config.history, &to_file, &input, Some(&query)), Application::GUI);
+        let _ = tx.send(output);
+        ctx.request_repaint();
+    });
+}
+
+/// Looks up the alerts at `query`, recording each one in the history.
+fn get_alerts_for(query: LocationQuery, input: String, config: Config, ctx: Context, tx: Sender<Result<WeatherAlerts, String>>) {
+    tokio::spawn(async move {
+        let output = get_alerts(&config.weather, &query).await.map_err(|e| format!("Alert lookup failed: {}", e));
+        if let Ok(alerts) = &output {
+            for entry in alert_log_entries(alerts) {
+                let _ = write_to_file(&redact_location_query(&config.history, &entry, &input, Some(&query)), Application::GUI);
+            }
         }
-        let _ = write_to_file(&format!("Temperature retrieved by ZIP code (\n{}\n)", output).to_string(), Application::GUI);
         let _ = tx.send(output);
         ctx.request_repaint();
     });
-}
\ No newline at end of file
+}
+
+fn get_astronomy_for(query: LocationQuery, input: String, date: chrono::NaiveDate, config: Config, ctx: Context, tx: Sender<Result<Astronomy, String>>) {
+    tokio::spawn(async move {
+        let output = get_astronomy(&config.weather, &query, date).await.map_err(|e| format!("Astronomy lookup failed: {}", e));
+        let text = match &output {
+            Ok(a) => format_astronomy(a),
+            Err(e) => e.to_string()
+        };
+        let to_file = format!("Astronomy retrieved (\n{}\n)", text);
+        let _ = write_to_file(&redact_location_query(&config.history, &to_file, &input, Some(&query)), Application::GUI);
+        let _ = tx.send(output);
+        ctx.request_repaint();
+    });
+}
+
+fn get_past_weather(query: LocationQuery, input: String, date: chrono::NaiveDate, config: Config, ctx: Context, tx: Sender<Result<WeatherHistory, String>>) {
+    tokio::spawn(async move {
+        let output = get_historical_weather(&config.weather, &query, date, date).await
+            .map_err(|e| format!("Weather history lookup failed: {}", e));
+        let text = match &output {
+            Ok(h) => format_weather_history(h, &[Scale::Celsius], false),
+            Err(e) => e.to_string()
+        };
+        let to_file = format!("Weather history retrieved (\n{}\n)", text);
+        let _ = write_to_file(&redact_location_query(&config.history, &to_file, &input, Some(&query)), Application::GUI);
+        let _ = tx.send(output);
+        ctx.request_repaint();
+    });
+}
@@ -0,0 +1,359 @@
+use std::fs;
+
+use serde::{Serialize, Deserialize};
+
+use crate::history::{HistoryBackend, LocationPrivacy, Operation};
+use crate::weather::{ProviderKind, ThresholdRule};
+
+pub const CONFIG_FILE: &str = "temperature-converter.toml";
+
+/// Settings shared by both frontends, read from `temperature-converter.toml` in the working directory
+/// (or the file named by `TEMPERATURE_CONVERTER_CONFIG`). Every field has a default, so the file is optional.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub history: HistoryConfig,
+    pub weather: WeatherConfig,
+    pub cache: CacheConfig,
+    pub watch: WatchConfig,
+    pub thresholds: ThresholdConfig,
+    pub daemon: DaemonConfig,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WeatherConfig {
+    /// One of "weatherapi", "open_meteo" or "nws".
+    pub provider: ProviderKind,
+    /// WeatherAPI key, overridden by `TEMPERATURE_CONVERTER_API_KEY`.
+    pub api_key: Option<String>,
+    /// WeatherAPI base URL, overridden by `TEMPERATURE_CONVERTER_BASE_URL`, e.g. to go through a proxy.
+    pub base_url: String,
+    pub open_meteo_url: String,
+    /// Open-Meteo's historical weather API, used for past dates.
+    pub archive_url: String,
+    /// Used by the Open-Meteo and NWS providers to turn place names and postal codes into coordinates.
+    pub geocoding_url: String,
+    pub nws_url: String,
+    /// Never contact a weather provider, only use stored observations.
+    pub offline: bool,
+    /// Also look up air quality with current conditions. Only WeatherAPI provides it.
+    pub air_quality: bool,
+    /// Also look up active weather alerts with current conditions.
+    pub alerts: bool,
+    /// How many locations are looked up at once when several are asked for together.
+    pub concurrency: usize,
+    /// Timeouts, retries and rate limiting for every request, under `[weather.http]`.
+    pub http: HttpConfig,
+}
+
+impl Default for WeatherConfig {
+    fn default() -> Self {
+        WeatherConfig {
+            provider: ProviderKind::WeatherApi,
+            api_key: None,
+            base_url: "https://api.weatherapi.com/v1".to_string(),
+            open_meteo_url: "https://api.open-meteo.com/v1".to_string(),
+            archive_url: "https://archive-api.open-meteo.com/v1".to_string(),
+            geocoding_url: "https://geocoding-api.open-meteo.com/v1".to_string(),
+            nws_url: "https://api.weather.gov".to_string(),
+            offline: false,
+            air_quality: false,
+            alerts: false,
+            concurrency: 4,
+            http: HttpConfig::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HttpConfig {
+    pub connect_timeout_secs: u64,
+    /// How long to wait for each read of a response before giving up.
+    pub read_timeout_secs: u64,
+    /// How many times a request is tried again after a timeout, a dropped connection, HTTP 429 or a 5xx gateway error.
+    pub retries: u32,
+    /// The wait before the first retry, doubled for each one after, with up to half of it taken off at random.
+    pub backoff_ms: u64,
+    /// The longest wait between tries. A `Retry-After` asking for longer than this isn't retried.
+    pub max_backoff_ms: u64,
+    /// Requests sent per second at most, across every lookup in the process. 0 for no limit.
+    pub requests_per_second: f64,
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        HttpConfig {
+            connect_timeout_secs: 10,
+            read_timeout_secs: 30,
+            retries: 3,
+            backoff_ms: 500,
+            max_backoff_ms: 30_000,
+            requests_per_second: 5.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HistoryConfig {
+    /// Set to false to stop recording history entirely.
+    pub enabled: bool,
+    pub backend: HistoryBackend,
+    /// Defaults to a file named after the backend in the working directory.
+    pub path: Option<String>,
+    /// Operations that are never recorded, e.g. `["help", "history_read"]`.
+    pub exclude: Vec<Operation>,
+    pub locations: LocationPrivacy,
+    /// Mixed into location hashes so they can't be reversed by hashing every ZIP code.
+    pub salt: String,
+    /// Entries older than this many days are dropped when retention is applied.
+    pub max_age_days: Option<u32>,
+    /// Only the newest entries are kept when retention is applied.
+    pub max_entries: Option<usize>,
+    /// File backends compress the log into an archive once it would grow past this size.
+    pub max_bytes: Option<u64>,
+    /// Number of compressed archives kept next to a log file.
+    pub archives: usize,
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        HistoryConfig {
+            enabled: true,
+            backend: HistoryBackend::File,
+            path: None,
+            exclude: Vec::new(),
+            locations: LocationPrivacy::Keep,
+            salt: "".to_string(),
+            max_age_days: None,
+            max_entries: None,
+            max_bytes: Some(1024 * 1024),
+            archives: 5,
+        }
+    }
+}
+
+impl HistoryConfig {
+    pub fn log_path(&self) -> String {
+        match (&self.path, self.backend) {
+            (Some(p), _) => p.to_string(),
+            (None, HistoryBackend::File) => "temperature-converter-log.txt".to_string(),
+            (None, HistoryBackend::Jsonl) => "temperature-converter-log.jsonl".to_string(),
+            (None, HistoryBackend::Sqlite) => "temperature-converter-history.sqlite3".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CacheConfig {
+    /// Set to false to ask the weather provider every time.
+    pub enabled: bool,
+    /// Defaults to `temperature-converter-cache.json` in the working directory.
+    pub path: Option<String>,
+    /// How long an observation is reused, counted from when the provider last updated it.
+    pub ttl_minutes: u64,
+    /// How long an observation is kept to fall back on when the weather service can't be reached.
+    pub keep_days: u64,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        CacheConfig { enabled: true, path: None, ttl_minutes: 15, keep_days: 7 }
+    }
+}
+
+impl CacheConfig {
+    pub fn cache_path(&self) -> String {
+        self.path.clone().unwrap_or("temperature-converter-cache.json".to_string())
+    }
+}
+
+/// How `--watch` behaves, under `[watch]`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WatchConfig {
+    /// How often a watched location is recorded in the use history, however often it is polled. 0 records every poll.
+    pub log_interval_minutes: u64,
+    /// Print every poll rather than only those where the temperature or condition changed.
+    pub every_tick: bool,
+}
+
+impl Default for WatchConfig {
+    fn default() -> Self {
+        WatchConfig { log_interval_minutes: 15, every_tick: false }
+    }
+}
+
+/// Temperature rules checked on every weather lookup, under `[thresholds]` with each rule in `[[thresholds.rules]]`.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ThresholdConfig {
+    /// Where each rule's state is kept between runs. Defaults to `temperature-converter-rules.json` in the working directory.
+    pub state_path: Option<String>,
+    pub rules: Vec<ThresholdRule>,
+}
+
+impl ThresholdConfig {
+    pub fn state_file(&self) -> String {
+        self.state_path.clone().unwrap_or("temperature-converter-rules.json".to_string())
+    }
+}
+
+/// The background daemon, `main-daemon`, under `[daemon]`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DaemonConfig {
+    /// Locations the daemon keeps fresh, as entered on the command line, e.g. `["99801", "Paris, France"]`.
+    pub locations: Vec<String>,
+    pub interval_minutes: u64,
+    /// The Unix domain socket the daemon answers on. Defaults to `temperature-converter.sock` in the working directory.
+    pub socket_path: Option<String>,
+    /// Set to false to have the CLI and GUI look weather up themselves even while the daemon is running.
+    pub use_daemon: bool,
+    /// How long the CLI and GUI wait for the daemon to answer before looking weather up themselves.
+    pub timeout_secs: u64,
+}
+
+impl Default for DaemonConfig {
+    fn default() -> Self {
+        DaemonConfig { locations: Vec::new(), interval_minutes: 15, socket_path: None, use_daemon: true, timeout_secs: 30 }
+    }
+}
+
+impl DaemonConfig {
+    pub fn socket_file(&self) -> String {
+        self.socket_path.clone().unwrap_or("temperature-converter.sock".to_string())
+    }
+}
+
+impl Config {
+    pub fn load() -> Result<Config, String> {
+        let path = std::env::var("TEMPERATURE_CONVERTER_CONFIG").unwrap_or(CONFIG_FILE.to_string());
+        let mut config = match fs::read_to_string(&path) {
+            Ok(t) => Config::parse(&t).map_err(|e| format!("invalid config {}: {}", path, e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Config::default(),
+            Err(e) => return Err(format!("unable to read config {}: {}", path, e)),
+        };
+        config.apply_env(|name| std::env::var(name).ok());
+        Ok(config)
+    }
+
+    /// Applies the settings that can be overridden by environment variables.
+    pub fn apply_env(&mut self, var: impl Fn(&str) -> Option<String>) {
+        if let Some(key) = var("TEMPERATURE_CONVERTER_API_KEY") {
+            self.weather.api_key = Some(key);
+        }
+        if let Some(url) = var("TEMPERATURE_CONVERTER_BASE_URL") {
+            self.weather.base_url = url;
+        }
+    }
+
+    pub fn parse(contents: &str) -> Result<Config, String> {
+        toml::from_str(contents).map_err(|e| e.message().to_string())
+    }
+}
+
+#[test]
+fn test_config_defaults() {
+    let config = Config::parse("").unwrap();
+    assert_eq!(config, Config::default());
+    assert_eq!(config.history.log_path(), "temperature-converter-log.txt");
+}
+
+#[test]
+fn test_config_backend() {
+    let config = Config::parse("[history]\nbackend = \"sqlite\"\n").unwrap();
+    assert_eq!(config.history.backend, HistoryBackend::Sqlite);
+    assert_eq!(config.history.log_path(), "temperature-converter-history.sqlite3");
+    let config = Config::parse("[history]\nbackend = \"jsonl\"\npath = \"log.jsonl\"\n").unwrap();
+    assert_eq!(config.history.log_path(), "log.jsonl");
+}
+
+#[test]
+fn test_config_history() {
+    let config = Config::parse("[history]\nmax_entries = 10\nmax_age_days = 30\narchives = 2\n").unwrap();
+    assert_eq!(config.history.max_entries, Some(10));
+    assert_eq!(config.history.max_age_days, Some(30));
+    assert_eq!(config.history.archives, 2);
+    assert_eq!(config.history.max_bytes, Some(1024 * 1024));
+}
+
+#[test]
+fn test_config_privacy() {
+    let config = Config::parse("[history]\nenabled = false\nexclude = [\"help\", \"history_read\"]\nlocations = \"hash\"\n").unwrap();
+    assert!(!config.history.enabled);
+    assert_eq!(config.history.exclude, vec![Operation::Help, Operation::HistoryRead]);
+    assert_eq!(config.history.locations, LocationPrivacy::Hash);
+}
+
+#[test]
+fn test_config_weather() {
+    let mut config = Config::parse("[weather]\napi_key = \"from-file\"\n").unwrap();
+    assert_eq!(config.weather.api_key.as_deref(), Some("from-file"));
+    assert_eq!(config.weather.base_url, "https://api.weatherapi.com/v1");
+    config.apply_env(|name| match name {
+        "TEMPERATURE_CONVERTER_API_KEY" => Some("from-env".to_string()),
+        _ => None,
+    });
+    assert_eq!(config.weather.api_key.as_deref(), Some("from-env"));
+    assert_eq!(config.weather.base_url, "https://api.weatherapi.com/v1");
+    assert_eq!(config.weather.provider, ProviderKind::WeatherApi);
+    let config = Config::parse("[weather]\nprovider = \"open_meteo\"\n").unwrap();
+    assert_eq!(config.weather.provider, ProviderKind::OpenMeteo);
+    assert_eq!(config.weather.concurrency, 4);
+    assert_eq!(Config::parse("[weather]\nconcurrency = 8\n").unwrap().weather.concurrency, 8);
+    assert!(Config::parse("[weather]\nprovider = \"accuweather\"\n").is_err());
+    let http = Config::parse("[weather.http]\nretries = 0\nrequests_per_second = 1.5\n").unwrap().weather.http;
+    assert_eq!((http.retries, http.requests_per_second, http.read_timeout_secs), (0, 1.5, 30));
+}
+
+#[test]
+fn test_config_cache() {
+    assert_eq!(Config::default().cache.cache_path(), "temperature-converter-cache.json");
+    let config = Config::parse("[cache]\nttl_minutes = 5\npath = \"cache.json\"\n").unwrap();
+    assert!(config.cache.enabled);
+    assert_eq!((config.cache.ttl_minutes, config.cache.cache_path().as_str()), (5, "cache.json"));
+    assert_eq!(config.cache.keep_days, 7);
+}
+
+#[test]
+fn test_config_watch() {
+    assert_eq!(Config::default().watch.log_interval_minutes, 15);
+    let config = Config::parse("[watch]\nlog_interval_minutes = 60\nevery_tick = true\n").unwrap();
+    assert_eq!(config.watch, WatchConfig { log_interval_minutes: 60, every_tick: true });
+}
+
+#[test]
+fn test_config_thresholds() {
+    use crate::weather::{Comparison, Notifier, Threshold};
+    use crate::Scale;
+    assert!(Config::default().thresholds.rules.is_empty());
+    let config = Config::parse("[[thresholds.rules]]\nname = \"Hot\"\nlocation = \"99801\"\nthreshold = \"86F\"\nhysteresis = 2.0\n\
+        notify = [{ type = \"stdout\" }, { type = \"webhook\", url = \"http://localhost/hook\" }, { type = \"command\", command = \"true\" }]\n\
+        [[thresholds.rules]]\nlocation = \"Paris\"\ncomparison = \"below\"\nthreshold = \"0C\"\n").unwrap();
+    let rules = &config.thresholds.rules;
+    assert_eq!(rules.len(), 2);
+    assert_eq!(rules[0].threshold, Threshold { value: 86.0, scale: Scale::Fahrenheit });
+    assert_eq!((rules[0].comparison, rules[0].hysteresis, rules[0].cooldown_minutes), (Comparison::Above, 2.0, 60));
+    assert_eq!(rules[0].notify[1], Notifier::Webhook { url: "http://localhost/hook".to_string() });
+    assert_eq!(rules[0].notify[2], Notifier::Command { command: "true".to_string(), args: Vec::new() });
+    assert_eq!((rules[1].comparison, &rules[1].notify), (Comparison::Below, &vec![Notifier::Stdout]));
+    assert_eq!(config.thresholds.state_file(), "temperature-converter-rules.json");
+    assert!(Config::parse("[[thresholds.rules]]\nthreshold = \"hot\"\n").is_err());
+}
+
+#[test]
+fn test_config_daemon() {
+    let config = Config::parse("[daemon]\nlocations = [\"99801\", \"Paris, France\"]\ninterval_minutes = 5\n").unwrap();
+    assert_eq!(config.daemon.locations, vec!["99801", "Paris, France"]);
+    assert_eq!((config.daemon.interval_minutes, config.daemon.use_daemon), (5, true));
+    assert_eq!(config.daemon.socket_file(), "temperature-converter.sock");
+}
+
+#[test]
+fn test_config_invalid() {
+    assert!(Config::parse("[history]\nmax_entries = \"ten\"\n").is_err());
+}
@@ -0,0 +1,275 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::time::Duration;
+
+use serde::{Serialize, Deserialize};
+
+use crate::config::DaemonConfig;
+use crate::history::{redact_location_query, write_to_log};
+use crate::weather::{
+    cache_key, check_thresholds, format_observed_time, get_cached_observations, record_cache_key, CachedObservation, LocationQuery, Observation,
+    ObservationSource, ProviderKind, QueryKind, WeatherError,
+};
+use crate::{calculate, user_zone, Application, Config, Scale};
+
+/// What the CLI and GUI ask the daemon, sent as one line of JSON.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DaemonRequest {
+    /// Current conditions at each query, fetched again if `refresh` is set. The provider, air quality setting and
+    /// cache TTL are the asker's, so it isn't answered with weather it wouldn't have looked up itself.
+    Current { queries: Vec<LocationQuery>, refresh: bool, provider: ProviderKind, air_quality: bool, ttl_minutes: u64 },
+}
+
+/// The daemon's answer, sent back as one line of JSON.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DaemonResponse {
+    /// One result per query, in the same order, with the error's message where a lookup failed.
+    Current(Vec<Result<DaemonObservation, String>>),
+    /// The daemon won't answer the request, e.g. it asks for another provider. The asker looks the weather up itself.
+    Refused(String),
+}
+
+/// An observation as the daemon found it, keeping whether it was stale.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DaemonObservation {
+    pub cached: CachedObservation,
+    pub source: ObservationSource,
+}
+
+/// Reads the configured locations the way the CLI reads a location it isn't told the kind of.
+pub fn daemon_locations(config: &DaemonConfig) -> Result<Vec<(String, LocationQuery)>, String> {
+    config.locations.iter()
+        .map(|input| LocationQuery::build(QueryKind::Auto, input, None).map(|q| (input.to_string(), q)))
+        .collect()
+}
+
+/// The use history entry for a polled observation, read back like a CLI lookup of the same place.
+pub fn observation_log_text(obs: &Observation) -> String {
+    let r = calculate((Scale::Celsius, obs.temp_c));
+    let observed = format_observed_time(obs, user_zone()).map(|t| format!("\n    Observed: {}", t)).unwrap_or_default();
+    format!(
+        "Temperature retrieved (\n-= Retrieve temperature in {}, {} =-\n    {:?}: {}\n    {:?}: {}\n    {:?}: {}{}\n)",
+        obs.place.name, obs.place.region, r.0.0, r.0.1, r.1.0, r.1.1, r.2.0, r.2.1, observed)
+}
+
+/// The background daemon's state, shared by its poll loop and every connection to its socket.
+pub struct Daemon {
+    config: Config,
+    interval: Duration,
+    /// The observations fetched by the last poll, by cache key.
+    polled: tokio::sync::Mutex<BTreeMap<String, CachedObservation>>,
+    /// Lookups run one at a time so the cache and threshold state files aren't written over each other.
+    lookups: tokio::sync::Mutex<()>,
+}
+
+impl Daemon {
+    /// A daemon polling every `interval`, which answers with what it polled until the next poll is due.
+    pub fn new(mut config: Config, interval: Duration) -> Daemon {
+        // The daemon answers its own socket, so it must never ask it.
+        config.daemon.use_daemon = false;
+        Daemon { config, interval, polled: Default::default(), lookups: Default::default() }
+    }
+
+    /// Fetches every location in `located`, records each observation in the use history and checks the threshold rules.
+    /// Returns a line summing up the poll, with a line for each lookup or notifier that failed. Stored observations
+    /// handed back because the provider couldn't be reached aren't recorded again, as they weren't observed now.
+    pub async fn poll(&self, located: &[(String, LocationQuery)]) -> String {
+        let queries: Vec<LocationQuery> = located.iter().map(|(_, q)| q.clone()).collect();
+        let _lookups = self.lookups.lock().await;
+        let found = get_cached_observations(&self.config, &queries, true).await;
+        let fetched: Vec<_> = queries.iter().zip(&found).filter_map(|(q, c)| Some((q, c.as_ref().ok()?))).collect();
+        let events = check_thresholds(&self.config, &fetched).await;
+        let mut polled = self.polled.lock().await;
+        for (query, cached) in fetched.iter().filter(|(_, c)| c.source == ObservationSource::Live) {
+            polled.insert(cache_key(self.config.weather.provider, query), (*cached).clone());
+        }
+        drop(polled);
+
+        let now = chrono::Utc::now().timestamp();
+        let mut failed = Vec::new();
+        for ((input, query), result) in located.iter().zip(&found) {
+            match result {
+                Ok(cached) if cached.source != ObservationSource::Live => {
+                    failed.push(format!("\n    {}: {}", input, cached.cache_note(now).unwrap_or_default()));
+                },
+                Ok(cached) => {
+                    let text = observation_log_text(&cached.observation);
+                    let text = redact_location_query(&self.config.history, &text, input, Some(query));
+                    let text = record_cache_key(&self.config, &text, query);
+                    if let Err(e) = write_to_log(&self.config.history, &text, Application::Daemon) {
+                        failed.push(format!("\n    Recording {} failed: {}", input, e));
+                    }
+                },
+                Err(e) => failed.push(format!("\n    {}: {}", input, e)),
+            }
+        }
+        for event in &events {
+            failed.extend(event.failures.iter().map(|f| format!("\n    Notifying {} failed: {}", event.rule, f)));
+        }
+        let ok = found.iter().filter(|r| r.as_ref().is_ok_and(|c| c.source == ObservationSource::Live)).count();
+        format!("Polled {} of {} locations{}", ok, located.len(), failed.concat())
+    }
+
+    /// Answers with the last poll's observations where the next poll isn't due yet and they are within the asker's
+    /// cache TTL, unless `refresh` is asked for. Anything else is looked up as the CLI would, checking the threshold
+    /// rules against whatever is fetched. Notifiers that fail are reported on the daemon's output, as after a poll.
+    pub async fn answer(&self, request: DaemonRequest) -> DaemonResponse {
+        let DaemonRequest::Current { queries, refresh, provider, air_quality, ttl_minutes } = request;
+        if provider != self.config.weather.provider {
+            return DaemonResponse::Refused(format!("the daemon uses {:?}, not {:?}", self.config.weather.provider, provider));
+        }
+        let now = chrono::Utc::now().timestamp();
+        let max_age = self.interval.as_secs().min(ttl_minutes.saturating_mul(60)) as i64;
+        let polled = self.polled.lock().await;
+        let mut answers: Vec<Option<Result<DaemonObservation, String>>> = queries.iter().map(|q| {
+            let cached = polled.get(&cache_key(provider, q))
+                .filter(|c| !refresh && now - c.fetched_epoch < max_age)
+                .filter(|c| !air_quality || c.observation.air_quality.is_some())?;
+            Some(Ok(DaemonObservation { cached: cached.clone(), source: ObservationSource::Cache }))
+        }).collect();
+        drop(polled);
+
+        let missing: Vec<LocationQuery> = queries.iter().zip(&answers).filter(|(_, a)| a.is_none()).map(|(q, _)| q.clone()).collect();
+        if !missing.is_empty() {
+            let mut config = self.config.clone();
+            config.weather.air_quality |= air_quality;
+            config.cache.ttl_minutes = config.cache.ttl_minutes.min(ttl_minutes);
+            let _lookups = self.lookups.lock().await;
+            let found = get_cached_observations(&config, &missing, refresh).await;
+            let fetched: Vec<_> = missing.iter().zip(&found).filter_map(|(q, c)| Some((q, c.as_ref().ok()?))).collect();
+            for event in check_thresholds(&config, &fetched).await {
+                for failure in &event.failures {
+                    println!("[{}] Notifying {} failed: {}", chrono::Local::now().format("%H:%M:%S"), event.rule, failure);
+                }
+            }
+            let mut found = found.into_iter();
+            for answer in answers.iter_mut().filter(|a| a.is_none()) {
+                *answer = found.next().map(|r| r.map(|cached| DaemonObservation { source: cached.source, cached }).map_err(|e| e.to_string()));
+            }
+        }
+        DaemonResponse::Current(answers.into_iter().flatten().collect())
+    }
+}
+
+/// Asks the daemon for current conditions at `queries`. `None` if it isn't to be used, isn't running, refuses
+/// or doesn't answer within `daemon.timeout_secs`, in which case the weather should be looked up directly.
+pub async fn ask_daemon(config: &Config, queries: &[LocationQuery], refresh: bool) -> Option<Vec<Result<CachedObservation, WeatherError>>> {
+    if !config.daemon.use_daemon || config.weather.offline || queries.is_empty() {
+        return None;
+    }
+    let request = DaemonRequest::Current {
+        queries: queries.to_vec(),
+        refresh,
+        provider: config.weather.provider,
+        air_quality: config.weather.air_quality,
+        // An asker without a cache wants nothing it didn't just fetch.
+        ttl_minutes: if config.cache.enabled { config.cache.ttl_minutes } else { 0 },
+    };
+    let timeout = Duration::from_secs(config.daemon.timeout_secs);
+    let response = tokio::time::timeout(timeout, send_request(&config.daemon.socket_file(), &request)).await.ok()?.ok()?;
+    match response {
+        DaemonResponse::Current(results) if results.len() == queries.len() => Some(results.into_iter().map(|r| match r {
+            Ok(found) => {
+                let source = match found.source {
+                    ObservationSource::Stale | ObservationSource::History => found.source,
+                    _ => ObservationSource::Daemon,
+                };
+                Ok(CachedObservation { source, ..found.cached })
+            },
+            Err(e) => Err(WeatherError::Daemon(e)),
+        }).collect()),
+        _ => None,
+    }
+}
+
+#[cfg(unix)]
+async fn send_request(path: &str, request: &DaemonRequest) -> io::Result<DaemonResponse> {
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    let stream = tokio::net::UnixStream::connect(path).await?;
+    let (read, mut write) = stream.into_split();
+    write.write_all(format!("{}\n", serde_json::to_string(request)?).as_bytes()).await?;
+    let mut line = String::new();
+    BufReader::new(read).read_line(&mut line).await?;
+    Ok(serde_json::from_str(&line)?)
+}
+
+#[cfg(not(unix))]
+async fn send_request(_path: &str, _request: &DaemonRequest) -> io::Result<DaemonResponse> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "the daemon needs Unix domain sockets"))
+}
+
+/// Listens on the socket at `path`, replacing one left behind by a daemon that is no longer running.
+/// Fails if another daemon is still answering on it.
+#[cfg(unix)]
+pub fn bind_socket(path: &str) -> io::Result<tokio::net::UnixListener> {
+    if std::path::Path::new(path).exists() {
+        if std::os::unix::net::UnixStream::connect(path).is_ok() {
+            return Err(io::Error::new(io::ErrorKind::AddrInUse, format!("a daemon is already answering on {}", path)));
+        }
+        std::fs::remove_file(path)?;
+    }
+    tokio::net::UnixListener::bind(path)
+}
+
+/// Answers every connection to `listener` until the task is dropped, each request on its own task.
+#[cfg(unix)]
+pub async fn serve(daemon: std::sync::Arc<Daemon>, listener: tokio::net::UnixListener) {
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    while let Ok((stream, _)) = listener.accept().await {
+        let daemon = daemon.clone();
+        tokio::spawn(async move {
+            let (read, mut write) = stream.into_split();
+            let mut lines = BufReader::new(read).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                let response = match serde_json::from_str(&line) {
+                    Ok(request) => daemon.answer(request).await,
+                    Err(e) => DaemonResponse::Refused(format!("unreadable request: {}", e)),
+                };
+                let Ok(json) = serde_json::to_string(&response) else { break };
+                if write.write_all(format!("{}\n", json).as_bytes()).await.is_err() {
+                    break;
+                }
+            }
+        });
+    }
+}
+
+#[test]
+fn test_daemon_locations() {
+    let config = DaemonConfig { locations: vec!["99801".to_string(), "Paris, France".to_string()], ..DaemonConfig::default() };
+    let located = daemon_locations(&config).unwrap();
+    assert_eq!(located[1], ("Paris, France".to_string(), LocationQuery::City("Paris, France".to_string())));
+    let config = DaemonConfig { locations: vec!["99801".to_string(), "".to_string()], ..DaemonConfig::default() };
+    assert!(daemon_locations(&config).is_err());
+}
+
+#[test]
+fn test_observation_log_text() {
+    let obs = Observation {
+        place: crate::weather::Place { name: "Juneau".to_string(), region: "Alaska".to_string(), ..Default::default() },
+        temp_c: 11.1,
+        ..Observation::default()
+    };
+    let entry = crate::history::HistoryEntry::from_text(&observation_log_text(&obs), Some(Application::Daemon), chrono::Utc::now().fixed_offset());
+    assert_eq!(entry.operation, crate::history::Operation::WeatherLookup);
+    assert_eq!(entry.location.as_deref(), Some("Juneau, Alaska"));
+    assert_eq!(entry.input(), Some((Scale::Celsius, 11.1)));
+}
+
+#[test]
+fn test_daemon_request_json() {
+    let request = DaemonRequest::Current {
+        queries: vec![LocationQuery::postal_code("99801", None).unwrap()],
+        refresh: false,
+        provider: ProviderKind::WeatherApi,
+        air_quality: false,
+        ttl_minutes: 10,
+    };
+    let json = serde_json::to_string(&request).unwrap();
+    assert!(!json.contains('\n'));
+    assert_eq!(serde_json::from_str::<DaemonRequest>(&json).unwrap(), request);
+}
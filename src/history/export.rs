@@ -0,0 +1,170 @@
+use chrono::SecondsFormat;
+
+use serde::Serialize;
+
+use crate::Scale;
+
+use super::{app_name, HistoryEntry, Operation};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    Csv,
+    Json,
+    Markdown,
+    Html,
+}
+
+impl ExportFormat {
+    pub fn from_name(name: &str) -> Option<ExportFormat> {
+        match name.to_lowercase().as_str() {
+            "csv" => Some(ExportFormat::Csv),
+            "json" => Some(ExportFormat::Json),
+            "md" | "markdown" => Some(ExportFormat::Markdown),
+            "html" => Some(ExportFormat::Html),
+            _ => None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Json => "json",
+            ExportFormat::Markdown => "md",
+            ExportFormat::Html => "html",
+        }
+    }
+}
+
+/// A history entry flattened into the columns of an export.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ExportRow {
+    pub timestamp: String,
+    pub app: String,
+    pub operation: Operation,
+    pub input_scale: Option<Scale>,
+    pub input_value: Option<f32>,
+    pub kelvin: Option<f32>,
+    pub celsius: Option<f32>,
+    pub fahrenheit: Option<f32>,
+    pub location: Option<String>,
+}
+
+const COLUMNS: [&str; 9] = [
+    "timestamp", "app", "operation", "input_scale", "input_value", "kelvin", "celsius", "fahrenheit", "location",
+];
+
+pub fn export_row(entry: &HistoryEntry) -> ExportRow {
+    let reading = |scale: Scale| entry.readings.iter().find(|r| r.0 == scale).map(|r| r.1);
+    ExportRow {
+        timestamp: entry.timestamp.to_rfc3339_opts(SecondsFormat::Secs, false),
+        app: app_name(entry.app).to_string(),
+        operation: entry.operation,
+        input_scale: entry.input().map(|i| i.0),
+        input_value: entry.input().map(|i| i.1),
+        kelvin: reading(Scale::Kelvin),
+        celsius: reading(Scale::Celsius),
+        fahrenheit: reading(Scale::Fahrenheit),
+        location: entry.location.clone(),
+    }
+}
+
+pub fn export_history(entries: &[HistoryEntry], format: ExportFormat) -> String {
+    let rows: Vec<ExportRow> = entries.iter().map(export_row).collect();
+    match format {
+        ExportFormat::Json => serde_json::to_string_pretty(&rows).unwrap_or_default() + "\n",
+        ExportFormat::Csv => {
+            let mut out = COLUMNS.join(",") + "\n";
+            for row in &rows {
+                out += &(cells(row).iter().map(|c| csv_escape(c)).collect::<Vec<_>>().join(",") + "\n");
+            }
+            out
+        }
+        ExportFormat::Markdown => {
+            let mut out = format!("# Temperature history\n\n{} entries\n\n", rows.len());
+            out += &format!("| {} |\n", COLUMNS.join(" | "));
+            out += &format!("|{}\n", "---|".repeat(COLUMNS.len()));
+            for row in &rows {
+                out += &format!("| {} |\n", cells(row).iter().map(|c| c.replace('|', "\\|")).collect::<Vec<_>>().join(" | "));
+            }
+            out
+        }
+        ExportFormat::Html => {
+            let mut out = "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>Temperature history</title></head>\n<body>\n".to_string();
+            out += &format!("<h1>Temperature history</h1>\n<p>{} entries</p>\n<table>\n<tr>", rows.len());
+            for c in COLUMNS {
+                out += &format!("<th>{}</th>", c);
+            }
+            out += "</tr>\n";
+            for row in &rows {
+                out += "<tr>";
+                for c in cells(row) {
+                    out += &format!("<td>{}</td>", html_escape(&c));
+                }
+                out += "</tr>\n";
+            }
+            out + "</table>\n</body>\n</html>\n"
+        }
+    }
+}
+
+fn cells(row: &ExportRow) -> Vec<String> {
+    let num = |v: Option<f32>| v.map(|v| v.to_string()).unwrap_or_default();
+    vec![
+        row.timestamp.clone(),
+        row.app.clone(),
+        serde_json::to_value(row.operation).ok().and_then(|v| v.as_str().map(String::from)).unwrap_or_default(),
+        row.input_scale.map(|s| format!("{:?}", s)).unwrap_or_default(),
+        num(row.input_value),
+        num(row.kelvin),
+        num(row.celsius),
+        num(row.fahrenheit),
+        row.location.clone().unwrap_or_default(),
+    ]
+}
+
+fn csv_escape(cell: &str) -> String {
+    if cell.contains([',', '"', '\n']) {
+        format!("\"{}\"", cell.replace('"', "\"\""))
+    } else {
+        cell.to_string()
+    }
+}
+
+fn html_escape(cell: &str) -> String {
+    cell.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+#[test]
+fn test_export_csv() {
+    let entries = super::parse_history(super::SAMPLE_LOG);
+    let csv = export_history(&entries, ExportFormat::Csv);
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines.len(), 7);
+    assert_eq!(lines[0], "timestamp,app,operation,input_scale,input_value,kelvin,celsius,fahrenheit,location");
+    assert_eq!(lines[1], "2024-08-14T12:56:51-04:00,unknown,weather_lookup,Celsius,11.1,284.25,11.1,51.98,\"Juneau, Alaska\"");
+    assert_eq!(lines[2], "2024-08-14T12:57:43-04:00,unknown,help,,,,,,");
+    assert_eq!(lines[3], "2024-08-14T12:57:50-04:00,CLI,conversion,Kelvin,12,12,-261.15,-438.06998,");
+}
+
+#[test]
+fn test_export_json() {
+    let entries = super::parse_history(super::SAMPLE_LOG);
+    let json: serde_json::Value = serde_json::from_str(&export_history(&entries, ExportFormat::Json)).unwrap();
+    assert_eq!(json.as_array().unwrap().len(), 6);
+    assert_eq!(json[3]["app"], "GUI");
+    assert_eq!(json[3]["location"], "Juneau, Alaska");
+    assert_eq!(json[3]["input_scale"], "Celsius");
+    assert_eq!(json[4]["operation"], "history_read");
+}
+
+#[test]
+fn test_export_reports() {
+    let entries = super::parse_history(super::SAMPLE_LOG);
+    let md = export_history(&entries, ExportFormat::Markdown);
+    assert!(md.contains("| 2024-08-14T12:56:51-04:00 | unknown | weather_lookup | Celsius | 11.1 | 284.25 | 11.1 | 51.98 | Juneau, Alaska |"));
+    let html = export_history(&entries, ExportFormat::Html);
+    assert!(html.contains("<td>Juneau, Alaska</td>"));
+    assert_eq!(html.matches("<tr>").count(), 7);
+    assert_eq!(ExportFormat::from_name("MD"), Some(ExportFormat::Markdown));
+    assert_eq!(ExportFormat::from_name("xls"), None);
+}
@@ -0,0 +1,329 @@
+use std::io;
+
+use chrono::{DateTime, FixedOffset, NaiveDate, Utc};
+
+use serde::{Serialize, Deserialize};
+
+use crate::config::HistoryConfig;
+use crate::{parse_tz, user_zone, Application, Scale};
+
+pub mod export;
+pub mod privacy;
+pub mod retention;
+pub mod stats;
+pub mod store;
+
+pub use export::*;
+pub use privacy::*;
+pub use retention::*;
+pub use stats::*;
+pub use store::*;
+
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S%.f %:z";
+
+/// The headers that name a place in a logged message, each followed by the place and " =-".
+pub(crate) const LOCATION_HEADERS: [&str; 5] = [
+    "-= Retrieve temperature in ", "-= Forecast for ", "-= Weather history for ", "-= Alert for ", "-= Astronomy for ",
+];
+
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Operation {
+    Conversion,
+    WeatherLookup,
+    /// A weather alert that was in effect at a looked up location.
+    WeatherAlert,
+    Help,
+    HistoryRead,
+    HistoryEdit,
+    Invalid,
+    Other,
+}
+
+/// One record from the use history, with the fields the log text only implies broken out.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub timestamp: DateTime<FixedOffset>,
+    pub app: Option<Application>,
+    pub operation: Operation,
+    pub location: Option<String>,
+    pub readings: Vec<(Scale, f32)>,
+    pub text: String,
+    /// The user's time zone when the entry was logged, e.g. "America/New_York". New entries are timestamped in UTC
+    /// and carry this so they read the same from anywhere, older ones only have their UTC offset.
+    #[serde(default)]
+    pub zone: Option<String>,
+}
+
+impl HistoryEntry {
+    /// Builds an entry from the message passed to `write_to_file`, e.g. "Temperature converted (\n...\n)".
+    pub fn from_text(text: &str, app: Option<Application>, timestamp: DateTime<FixedOffset>) -> HistoryEntry {
+        let first = text.lines().next().unwrap_or("").trim();
+        let operation = if first.starts_with("Temperature converted") {
+            Operation::Conversion
+        } else if ["Temperature retrieved", "Forecast retrieved", "Weather history retrieved", "Astronomy retrieved"].iter().any(|p| first.starts_with(p)) {
+            Operation::WeatherLookup
+        } else if first.starts_with("Weather alert") {
+            Operation::WeatherAlert
+        } else if first.starts_with("Help requested") {
+            Operation::Help
+        } else if first.starts_with("History clear") || first.starts_with("History prune") {
+            Operation::HistoryEdit
+        } else if first.starts_with("History ") {
+            Operation::HistoryRead
+        } else if first.starts_with("Invalid entry") {
+            Operation::Invalid
+        } else {
+            Operation::Other
+        };
+
+        let mut location = None;
+        let mut readings = Vec::new();
+        for line in text.lines().skip(1) {
+            let line = line.trim();
+            let header = LOCATION_HEADERS.iter().find_map(|p| line.strip_prefix(p));
+            if let Some(l) = header.and_then(|l| l.strip_suffix(" =-")) {
+                location = Some(l.to_string());
+            } else if let Some(l) = line.strip_prefix("Temperature in ") {
+                location = Some(l.to_string());
+            } else if let Some(r) = parse_reading(line) {
+                readings.push(r);
+            }
+        }
+
+        HistoryEntry { timestamp, app, operation, location, readings, text: text.to_string(), zone: None }
+    }
+
+    /// The temperature that was entered or retrieved, which is always the first reading logged.
+    pub fn input(&self) -> Option<(Scale, f32)> {
+        self.readings.first().copied()
+    }
+
+    /// When the entry was logged, on the clock of the zone it was logged in.
+    pub fn local_timestamp(&self) -> DateTime<FixedOffset> {
+        match self.zone.as_deref().and_then(parse_tz) {
+            Some(tz) => self.timestamp.with_timezone(&tz).fixed_offset(),
+            None => self.timestamp,
+        }
+    }
+
+    /// Whether the entry was logged within `since..=until`, either bound being optional.
+    /// Days are those of the zone the entry was logged in.
+    pub fn in_period(&self, since: Option<NaiveDate>, until: Option<NaiveDate>) -> bool {
+        let day = self.local_timestamp().date_naive();
+        !(since.is_some_and(|s| day < s) || until.is_some_and(|u| day > u))
+    }
+
+    /// Formats the entry the way it is stored in the log file.
+    pub fn to_log_string(&self) -> String {
+        let from = match self.app {
+            Some(_) => format!(" - from {}", app_name(self.app)),
+            None => "".to_string(),
+        };
+        let zone = self.zone.as_ref().map(|z| format!(" {}", z)).unwrap_or_default();
+        format!("{}{} on {}{}\n\n", self.text, from, self.timestamp.format(TIMESTAMP_FORMAT), zone)
+    }
+}
+
+pub fn read_log(config: &HistoryConfig) -> io::Result<String> {
+    open_store(config)?.read_text()
+}
+
+pub fn read_entries(config: &HistoryConfig) -> io::Result<Vec<HistoryEntry>> {
+    open_store(config)?.entries()
+}
+
+/// Appends an entry to the configured history store, timestamped in UTC along with the user's time zone.
+/// Nothing is written if logging is disabled or the entry's operation is excluded, and locations are redacted as configured.
+pub fn write_to_log(config: &HistoryConfig, input: &str, app: Application) -> io::Result<()> {
+    let mut entry = HistoryEntry::from_text(&redact_text(config, input), Some(app), Utc::now().fixed_offset());
+    entry.zone = user_zone().map(|tz| tz.name().to_string());
+    if !should_log(config, entry.operation) {
+        return Ok(());
+    }
+    open_store(config)?.append(&entry)
+}
+
+/// Parses the contents of the log file into entries, skipping anything that isn't terminated by a timestamp.
+pub fn parse_history(contents: &str) -> Vec<HistoryEntry> {
+    let mut entries = Vec::new();
+    let mut pending: Vec<&str> = Vec::new();
+
+    for line in contents.lines() {
+        if pending.is_empty() && line.trim().is_empty() {
+            continue;
+        }
+        let Some((head, timestamp, zone)) = parse_trailer(line) else {
+            pending.push(line);
+            continue;
+        };
+
+        let (head, app) = if let Some(h) = head.strip_suffix(" - from CLI") {
+            (h, Some(Application::CLI))
+        } else if let Some(h) = head.strip_suffix(" - from GUI") {
+            (h, Some(Application::GUI))
+        } else if let Some(h) = head.strip_suffix(" - from Daemon") {
+            (h, Some(Application::Daemon))
+        } else {
+            (head, None)
+        };
+        pending.push(head);
+        entries.push(HistoryEntry { zone, ..HistoryEntry::from_text(&pending.join("\n"), app, timestamp) });
+        pending.clear();
+    }
+
+    entries
+}
+
+/// Reads the " on <timestamp>" that ends an entry, with the time zone after it if it was logged with one.
+fn parse_trailer(line: &str) -> Option<(&str, DateTime<FixedOffset>, Option<String>)> {
+    let (head, ts) = line.rsplit_once(" on ")?;
+    let ts = ts.trim();
+    if let Ok(timestamp) = DateTime::parse_from_str(ts, TIMESTAMP_FORMAT) {
+        return Some((head, timestamp, None));
+    }
+    let (ts, zone) = ts.rsplit_once(' ')?;
+    parse_tz(zone)?;
+    let timestamp = DateTime::parse_from_str(ts, TIMESTAMP_FORMAT).ok()?;
+    Some((head, timestamp, Some(zone.to_string())))
+}
+
+fn parse_reading(line: &str) -> Option<(Scale, f32)> {
+    let (name, value) = line.split_once(": ")?;
+    let scale = match name {
+        "Kelvin" => Scale::Kelvin,
+        "Celsius" => Scale::Celsius,
+        "Fahrenheit" => Scale::Fahrenheit,
+        _ => return None,
+    };
+    Some((scale, value.trim().parse().ok()?))
+}
+
+pub fn app_name(app: Option<Application>) -> &'static str {
+    match app {
+        Some(Application::CLI) => "CLI",
+        Some(Application::GUI) => "GUI",
+        Some(Application::Daemon) => "Daemon",
+        None => "unknown",
+    }
+}
+
+#[cfg(test)]
+const SAMPLE_LOG: &str = "Temperature retrieved by ZIP code (
+-= Retrieve temperature in Juneau, Alaska =-
+    Celsius: 11.1
+    Kelvin: 284.25
+    Fahrenheit: 51.98
+) on 2024-08-14 12:56:51.924484529 -04:00
+
+Help requested on 2024-08-14 12:57:43.197527933 -04:00
+
+Temperature converted (
+-= Convert input temperature =-
+    Kelvin: 12
+    Celsius: -261.15
+    Fahrenheit: -438.06998
+) - from CLI on 2024-08-14 12:57:50.210848351 -04:00
+
+Temperature retrieved by ZIP code (
+Temperature in Juneau, Alaska
+Celsius: 15.1
+Kelvin: 288.25
+Fahrenheit: 59.18
+) - from GUI on 2024-08-15 09:00:00.000000000 -04:00
+
+History accesed - from GUI on 2024-08-15 09:01:00.000000000 -04:00
+
+Temperature converted (
+unknown scale x
+) - from CLI on 2024-08-15 09:02:00.000000000 -04:00
+
+";
+
+#[test]
+fn test_parse_history_entries() {
+    let entries = parse_history(SAMPLE_LOG);
+    assert_eq!(entries.len(), 6);
+    assert_eq!(entries.iter().map(|e| e.operation).collect::<Vec<_>>(), vec![
+        Operation::WeatherLookup, Operation::Help, Operation::Conversion,
+        Operation::WeatherLookup, Operation::HistoryRead, Operation::Conversion,
+    ]);
+    assert_eq!(entries.iter().map(|e| e.app).collect::<Vec<_>>(), vec![
+        None, None, Some(Application::CLI), Some(Application::GUI), Some(Application::GUI), Some(Application::CLI),
+    ]);
+}
+
+#[test]
+fn test_parse_history_fields() {
+    let entries = parse_history(SAMPLE_LOG);
+    assert_eq!(entries[0].location.as_deref(), Some("Juneau, Alaska"));
+    assert_eq!(entries[0].input(), Some((Scale::Celsius, 11.1)));
+    assert_eq!(entries[2].input(), Some((Scale::Kelvin, 12.0)));
+    assert_eq!(entries[2].readings.len(), 3);
+    assert_eq!(entries[3].location.as_deref(), Some("Juneau, Alaska"));
+    assert_eq!(entries[5].input(), None);
+    assert_eq!(entries[1].text, "Help requested");
+
+    let forecast = HistoryEntry::from_text(
+        "Forecast retrieved (\n-= Forecast for Juneau, Alaska =-\n    Wed 2024-08-14: Overcast\n        min 9°C, max 12°C, avg 10.5°C\n)",
+        Some(Application::CLI), entries[0].timestamp);
+    assert_eq!(forecast.operation, Operation::WeatherLookup);
+    assert_eq!(forecast.location.as_deref(), Some("Juneau, Alaska"));
+    assert!(forecast.readings.is_empty());
+    let past = HistoryEntry::from_text(
+        "Weather history retrieved (\n-= Weather history for Juneau, Alaska =-\n)", None, entries[0].timestamp);
+    assert_eq!((past.operation, past.location.as_deref()), (Operation::WeatherLookup, Some("Juneau, Alaska")));
+    let alert = HistoryEntry::from_text(
+        "Weather alert issued (\n-= Alert for Juneau, Alaska =-\n    Event: Heat Advisory\n)", None, entries[0].timestamp);
+    assert_eq!((alert.operation, alert.location.as_deref()), (Operation::WeatherAlert, Some("Juneau, Alaska")));
+    let astro = HistoryEntry::from_text(
+        "Astronomy retrieved (\n-= Astronomy for Juneau, Alaska =-\n    Sunrise: 05:08\n)", None, entries[0].timestamp);
+    assert_eq!((astro.operation, astro.location.as_deref()), (Operation::WeatherLookup, Some("Juneau, Alaska")));
+}
+
+#[test]
+fn test_log_string_round_trip() {
+    let entries = parse_history(SAMPLE_LOG);
+    let rendered: String = entries.iter().map(|e| e.to_log_string()).collect();
+    assert_eq!(parse_history(&rendered), entries);
+    assert!(rendered.contains(") - from CLI on 2024-08-14 12:57:50.210848351 -04:00\n\n"));
+}
+
+#[test]
+fn test_entry_zone() {
+    let log = "Help requested - from CLI on 2024-08-15 03:30:00.123456789 +00:00 America/New_York\n\n";
+    let entries = parse_history(log);
+    assert_eq!(entries[0].zone.as_deref(), Some("America/New_York"));
+    assert_eq!(entries[0].timestamp.to_rfc3339(), "2024-08-15T03:30:00.123456789+00:00");
+    assert_eq!(entries[0].local_timestamp().to_rfc3339(), "2024-08-14T23:30:00.123456789-04:00");
+    assert!(entries[0].in_period(None, NaiveDate::from_ymd_opt(2024, 8, 14)));
+    assert_eq!(entries[0].to_log_string(), log);
+    assert!(parse_history("Help requested on 2024-08-15 03:30:00.000000000 +00:00 Mars/Olympus\n").is_empty());
+}
+
+#[test]
+fn test_write_to_log_privacy() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = HistoryConfig {
+        path: Some(dir.path().join("log.txt").to_string_lossy().to_string()),
+        exclude: vec![Operation::Help, Operation::HistoryRead],
+        locations: LocationPrivacy::Redact,
+        ..HistoryConfig::default()
+    };
+    write_to_log(&config, "Help requested", Application::CLI).unwrap();
+    write_to_log(&config, "History accessed", Application::CLI).unwrap();
+    write_to_log(&config, "Temperature retrieved by ZIP code (\nTemperature in Juneau, Alaska\nCelsius: 11.1\n)", Application::GUI).unwrap();
+    let entries = parse_history(&read_log(&config).unwrap());
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].location.as_deref(), Some("[redacted]"));
+
+    let disabled = HistoryConfig { enabled: false, ..config.clone() };
+    write_to_log(&disabled, "Invalid entry", Application::CLI).unwrap();
+    assert_eq!(parse_history(&read_log(&config).unwrap()).len(), 1);
+}
+
+#[test]
+fn test_parse_history_skips_unterminated() {
+    let entries = parse_history("Temperature converted (\n    Celsius: 1\n)");
+    assert!(entries.is_empty());
+}
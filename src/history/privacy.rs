@@ -0,0 +1,200 @@
+use serde::{Serialize, Deserialize};
+
+use sha2::{Digest, Sha256};
+
+use crate::config::HistoryConfig;
+use crate::weather::LocationQuery;
+
+use super::{Operation, LOCATION_HEADERS};
+
+/// How locations (place names and the ZIP codes or queries used to look them up) are written to history.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LocationPrivacy {
+    #[default]
+    Keep,
+    Redact,
+    /// Replaced by a salted hash, so lookups of the same place can still be grouped.
+    Hash,
+}
+
+pub fn should_log(config: &HistoryConfig, operation: Operation) -> bool {
+    config.enabled && !config.exclude.contains(&operation)
+}
+
+pub fn redact_location(config: &HistoryConfig, location: &str) -> String {
+    match config.locations {
+        LocationPrivacy::Keep => location.to_string(),
+        LocationPrivacy::Redact => "[redacted]".to_string(),
+        LocationPrivacy::Hash => {
+            let digest = Sha256::digest(format!("{}{}", config.salt, location).as_bytes());
+            let hex: String = digest.iter().take(6).map(|b| format!("{:02x}", b)).collect();
+            format!("#{}", hex)
+        }
+    }
+}
+
+/// Redacts the location headers of a history message, e.g. "-= Retrieve temperature in Juneau, Alaska =-"
+/// or "-= Forecast for Juneau, Alaska =-", the same headers the location is read back from.
+pub fn redact_text(config: &HistoryConfig, text: &str) -> String {
+    if config.locations == LocationPrivacy::Keep {
+        return text.to_string();
+    }
+    text.lines().map(|line| redact_line(config, line).unwrap_or_else(|| line.to_string())).collect::<Vec<_>>().join("\n")
+}
+
+/// The line with its place redacted, or `None` if it doesn't name one.
+fn redact_line(config: &HistoryConfig, line: &str) -> Option<String> {
+    let indent = &line[..line.len() - line.trim_start().len()];
+    let trimmed = line.trim_start();
+    let header = LOCATION_HEADERS.iter()
+        .find_map(|p| Some((p, trimmed.strip_prefix(p)?.strip_suffix(" =-")?)));
+    if let Some((prefix, l)) = header {
+        Some(format!("{}{}{} =-", indent, prefix, redact_location(config, l)))
+    } else if let Some(l) = trimmed.strip_prefix("Temperature in ") {
+        Some(format!("{}Temperature in {}", indent, redact_location(config, l)))
+    } else {
+        // An astronomy entry's time zone, e.g. America/Juneau, names the place too.
+        let (date, zone) = trimmed.strip_prefix("Date: ")?.strip_suffix(')')?.split_once(" (times in ")?;
+        Some(format!("{}Date: {} (times in {})", indent, date, redact_location(config, zone)))
+    }
+}
+
+/// Redacts the ZIP code or query a lookup was made with wherever it appears as a whole word, as it can otherwise
+/// appear in error messages. Call this before passing lookup results to `write_to_file`. Lines naming the place
+/// are left for `redact_text`, so a hashed place is hashed whole.
+pub fn redact_query(config: &HistoryConfig, text: &str, query: &str) -> String {
+    let query = query.trim();
+    if config.locations == LocationPrivacy::Keep || query.is_empty() {
+        return text.to_string();
+    }
+    let redacted = redact_location(config, query);
+    let lines: Vec<String> = text.split('\n').map(|line| match redact_line(config, line) {
+        Some(_) => line.to_string(),
+        None => replace_words(line, query, &redacted),
+    }).collect();
+    lines.join("\n")
+}
+
+/// Redacts a location from a history entry both as it was entered and as it was understood, e.g. "75001" and
+/// "75001 (FR)", since provider errors may quote either.
+pub fn redact_location_query(config: &HistoryConfig, text: &str, input: &str, query: Option<&LocationQuery>) -> String {
+    let text = redact_query(config, text, input);
+    match query {
+        Some(q) => redact_query(config, &text, &q.to_string()),
+        None => text,
+    }
+}
+
+/// Replaces `word` in `text` only where it isn't part of a longer word or number, so "Rain" leaves "Rainier"
+/// alone and "11" leaves "11.1" alone.
+fn replace_words(text: &str, word: &str, with: &str) -> String {
+    let joins = |c: Option<char>, next: Option<char>| match c {
+        Some(c) if c.is_alphanumeric() => true,
+        // A decimal point or thousands separator between digits.
+        Some('.') | Some(',') => next.is_some_and(|n| n.is_ascii_digit()),
+        _ => false,
+    };
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(at) = rest.find(word) {
+        let (before, after) = (&rest[..at], &rest[at + word.len()..]);
+        let mut back = before.chars().rev();
+        let mut ahead = after.chars();
+        let whole = !joins(back.next(), back.next()) && !joins(ahead.next(), ahead.next());
+        out.push_str(before);
+        out.push_str(if whole { with } else { word });
+        rest = after;
+    }
+    out.push_str(rest);
+    out
+}
+
+#[test]
+fn test_should_log() {
+    let config = HistoryConfig { exclude: vec![Operation::Help], ..HistoryConfig::default() };
+    assert!(should_log(&config, Operation::Conversion));
+    assert!(!should_log(&config, Operation::Help));
+    let config = HistoryConfig { enabled: false, ..HistoryConfig::default() };
+    assert!(!should_log(&config, Operation::Conversion));
+}
+
+#[test]
+fn test_redact_text() {
+    let text = "Temperature retrieved by ZIP code (\n-= Retrieve temperature in Juneau, Alaska =-\n    Celsius: 11.1\n)";
+    let config = HistoryConfig { locations: LocationPrivacy::Redact, ..HistoryConfig::default() };
+    assert_eq!(
+        redact_text(&config, text),
+        "Temperature retrieved by ZIP code (\n-= Retrieve temperature in [redacted] =-\n    Celsius: 11.1\n)");
+    let keep = HistoryConfig::default();
+    assert_eq!(redact_text(&keep, text), text);
+    assert_eq!(
+        redact_text(&config, "Weather alert issued (\n-= Alert for Juneau, Alaska =-\n    Event: Heat Advisory\n)"),
+        "Weather alert issued (\n-= Alert for [redacted] =-\n    Event: Heat Advisory\n)");
+}
+
+#[test]
+fn test_redact_forecast() {
+    let text = "Forecast retrieved (\n-= Forecast for Juneau, Alaska =-\n    2024-08-14: 9.8 to 14.2°C, Patchy rain nearby\n)";
+    for locations in [LocationPrivacy::Redact, LocationPrivacy::Hash] {
+        let config = HistoryConfig { locations, ..HistoryConfig::default() };
+        let redacted = redact_text(&config, text);
+        assert!(!redacted.contains("Juneau") && !redacted.contains("Alaska"), "{}", redacted);
+        assert!(redacted.starts_with("Forecast retrieved (\n-= Forecast for "), "{}", redacted);
+        let entry = super::HistoryEntry::from_text(&redacted, None, chrono::Utc::now().fixed_offset());
+        assert_eq!(entry.location, Some(redact_location(&config, "Juneau, Alaska")));
+    }
+}
+
+#[test]
+fn test_redact_weather_history() {
+    let text = "Weather history retrieved (\n-= Weather history for Juneau, Alaska =-\n    2024-08-01: 10.2 to 15.8°C, Overcast\n)";
+    for locations in [LocationPrivacy::Redact, LocationPrivacy::Hash] {
+        let config = HistoryConfig { locations, ..HistoryConfig::default() };
+        let redacted = redact_text(&config, text);
+        assert!(!redacted.contains("Juneau") && !redacted.contains("Alaska"), "{}", redacted);
+        assert!(redacted.contains(&format!("-= Weather history for {} =-", redact_location(&config, "Juneau, Alaska"))), "{}", redacted);
+    }
+}
+
+#[test]
+fn test_redact_hash() {
+    let config = HistoryConfig { locations: LocationPrivacy::Hash, ..HistoryConfig::default() };
+    let a = redact_location(&config, "Juneau, Alaska");
+    assert_eq!(a, redact_location(&config, "Juneau, Alaska"));
+    assert_ne!(a, redact_location(&config, "Pittsburgh, Pennsylvania"));
+    assert_eq!(a.len(), 13);
+    let salted = HistoryConfig { salt: "pepper".to_string(), ..config.clone() };
+    assert_ne!(a, redact_location(&salted, "Juneau, Alaska"));
+    assert_eq!(
+        redact_query(&config, "error sending request for url (https://example.com/?q=99801)", "99801"),
+        format!("error sending request for url (https://example.com/?q={})", redact_location(&config, "99801")));
+}
+
+#[test]
+fn test_redact_query_whole_words() {
+    let config = HistoryConfig { locations: LocationPrivacy::Redact, ..HistoryConfig::default() };
+    assert_eq!(
+        redact_query(&config, "Weather lookup failed: no location matched Rain (Rainier, Light rain)", "Rain"),
+        "Weather lookup failed: no location matched [redacted] (Rainier, Light rain)");
+    assert_eq!(redact_query(&config, "Celsius: 11.1\nKelvin: 284.11\nStation 11.", "11"), "Celsius: 11.1\nKelvin: 284.11\nStation [redacted].");
+    assert_eq!(redact_query(&config, "q=99801&days=3", "99801"), "q=[redacted]&days=3");
+}
+
+#[test]
+fn test_redact_query_leaves_place_whole() {
+    let text = "Temperature retrieved (\n-= Retrieve temperature in Juneau, Alaska =-\n    Celsius: 11.1\n)";
+    let config = HistoryConfig { locations: LocationPrivacy::Hash, ..HistoryConfig::default() };
+    let logged = redact_text(&config, &redact_query(&config, text, "Juneau"));
+    assert!(logged.contains(&format!("-= Retrieve temperature in {} =-", redact_location(&config, "Juneau, Alaska"))), "{}", logged);
+}
+
+#[test]
+fn test_redact_location_query() {
+    let config = HistoryConfig { locations: LocationPrivacy::Redact, ..HistoryConfig::default() };
+    let query = LocationQuery::postal_code("75001", Some("FR")).unwrap();
+    let text = "Weather lookup failed: no matching location found for 75001,FR";
+    assert_eq!(query.to_string(), "75001 (FR)");
+    assert_eq!(redact_query(&config, text, &query.to_string()), text);
+    assert_eq!(redact_location_query(&config, text, "75001", Some(&query)), "Weather lookup failed: no matching location found for [redacted],FR");
+}
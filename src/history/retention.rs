@@ -0,0 +1,74 @@
+use std::io;
+
+use chrono::{DateTime, Days, Local, NaiveDate, TimeZone};
+
+use crate::config::HistoryConfig;
+
+use super::open_store;
+
+/// Removes every history entry, including archived ones.
+pub fn clear_history(config: &HistoryConfig) -> io::Result<()> {
+    open_store(config)?.clear()
+}
+
+/// Removes entries logged before the start of `date` (local time). Returns the number of entries removed.
+pub fn prune_before(config: &HistoryConfig, date: NaiveDate) -> io::Result<usize> {
+    let Some(cutoff) = Local.from_local_datetime(&date.and_time(Default::default())).earliest() else {
+        return Ok(0);
+    };
+    open_store(config)?.remove_before(cutoff.fixed_offset())
+}
+
+/// Applies the configured `max_age_days` and `max_entries` limits. Returns the number of entries removed.
+pub fn apply_retention<Tz: TimeZone>(config: &HistoryConfig, now: DateTime<Tz>) -> io::Result<usize> {
+    if config.max_age_days.is_none() && config.max_entries.is_none() {
+        return Ok(0);
+    }
+    let store = open_store(config)?;
+    let mut removed = 0;
+    if let Some(cutoff) = config.max_age_days.and_then(|d| now.fixed_offset().checked_sub_days(Days::new(d as u64))) {
+        removed += store.remove_before(cutoff)?;
+    }
+    if let Some(max) = config.max_entries {
+        removed += store.keep_latest(max)?;
+    }
+    Ok(removed)
+}
+
+#[cfg(test)]
+fn test_config(dir: &tempfile::TempDir) -> HistoryConfig {
+    HistoryConfig {
+        path: Some(dir.path().join("log.txt").to_string_lossy().to_string()),
+        ..HistoryConfig::default()
+    }
+}
+
+#[test]
+fn test_prune_before() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = test_config(&dir);
+    std::fs::write(config.log_path(), super::SAMPLE_LOG).unwrap();
+    assert_eq!(prune_before(&config, NaiveDate::from_ymd_opt(2024, 8, 15).unwrap()).unwrap(), 3);
+    let entries = super::parse_history(&super::read_log(&config).unwrap());
+    assert_eq!(entries, super::parse_history(super::SAMPLE_LOG)[3..]);
+}
+
+#[test]
+fn test_apply_retention() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = HistoryConfig { max_age_days: Some(1), max_entries: Some(2), ..test_config(&dir) };
+    std::fs::write(config.log_path(), super::SAMPLE_LOG).unwrap();
+    let now = DateTime::parse_from_rfc3339("2024-08-15T12:57:45-04:00").unwrap();
+    assert_eq!(apply_retention(&config, now).unwrap(), 4);
+    let entries = super::parse_history(&super::read_log(&config).unwrap());
+    assert_eq!(entries, super::parse_history(super::SAMPLE_LOG)[4..]);
+}
+
+#[test]
+fn test_clear_history() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = test_config(&dir);
+    std::fs::write(config.log_path(), super::SAMPLE_LOG).unwrap();
+    clear_history(&config).unwrap();
+    assert_eq!(super::read_log(&config).unwrap(), "");
+}
@@ -0,0 +1,142 @@
+use chrono::NaiveDate;
+
+use crate::{Application, Scale};
+
+use super::{HistoryEntry, Operation};
+
+/// Temperatures retrieved for a single location, kept in Celsius as returned by the weather API.
+#[derive(Debug, PartialEq, Clone)]
+pub struct LocationSummary {
+    pub location: String,
+    pub lookups: usize,
+    pub min: f32,
+    pub max: f32,
+    pub mean: f32,
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct HistorySummary {
+    pub locations: Vec<LocationSummary>,
+    pub conversions: Vec<((Scale, f32), usize)>,
+    pub per_day: Vec<(NaiveDate, usize)>,
+    pub per_app: Vec<(Option<Application>, usize)>,
+}
+
+/// Aggregates entries dated within `since..=until` (either bound may be left open).
+pub fn summarize(entries: &[HistoryEntry], since: Option<NaiveDate>, until: Option<NaiveDate>) -> HistorySummary {
+    let mut summary = HistorySummary::default();
+    let mut sums: Vec<f32> = Vec::new();
+
+    for entry in entries.iter().filter(|e| e.in_period(since, until)) {
+        bump(&mut summary.per_day, entry.local_timestamp().date_naive());
+        bump(&mut summary.per_app, entry.app);
+
+        match (entry.operation, entry.input()) {
+            (Operation::Conversion, Some(input)) => bump(&mut summary.conversions, input),
+            (Operation::WeatherLookup, Some((scale, value))) => {
+                let Some(location) = &entry.location else { continue };
+                let temp = to_celsius(scale, value);
+                match summary.locations.iter().position(|l| &l.location == location) {
+                    Some(i) => {
+                        let l = &mut summary.locations[i];
+                        l.lookups += 1;
+                        l.min = l.min.min(temp);
+                        l.max = l.max.max(temp);
+                        sums[i] += temp;
+                    }
+                    None => {
+                        summary.locations.push(LocationSummary {
+                            location: location.to_string(), lookups: 1, min: temp, max: temp, mean: temp,
+                        });
+                        sums.push(temp);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    for (l, sum) in summary.locations.iter_mut().zip(sums) {
+        l.mean = sum / l.lookups as f32;
+    }
+    summary.locations.sort_by(|a, b| b.lookups.cmp(&a.lookups).then(a.location.cmp(&b.location)));
+    summary.conversions.sort_by_key(|c| std::cmp::Reverse(c.1));
+    summary.per_day.sort_by_key(|d| d.0);
+    summary
+}
+
+/// Renders a summary with location temperatures shown in `scale`.
+pub fn format_summary(summary: &HistorySummary, scale: Scale) -> String {
+    let mut out = "Locations:".to_string();
+    if summary.locations.is_empty() {
+        out += "\n    none";
+    }
+    for l in &summary.locations {
+        out += &format!(
+            "\n    {}: {} lookup(s), min {}, max {}, mean {} ({:?})",
+            l.location, l.lookups, crate::from_celsius(l.min, scale), crate::from_celsius(l.max, scale), crate::from_celsius(l.mean, scale), scale);
+    }
+    out += "\nMost converted values:";
+    if summary.conversions.is_empty() {
+        out += "\n    none";
+    }
+    for ((s, v), count) in summary.conversions.iter().take(5) {
+        out += &format!("\n    {} {:?}: {}", v, s, count);
+    }
+    out += "\nUses per day:";
+    for (day, count) in &summary.per_day {
+        out += &format!("\n    {}: {}", day, count);
+    }
+    out += "\nUses per frontend:";
+    for (app, count) in &summary.per_app {
+        out += &format!("\n    {}: {}", super::app_name(*app), count);
+    }
+    out
+}
+
+fn bump<K: PartialEq>(counts: &mut Vec<(K, usize)>, key: K) {
+    match counts.iter_mut().find(|c| c.0 == key) {
+        Some(c) => c.1 += 1,
+        None => counts.push((key, 1)),
+    }
+}
+
+fn to_celsius(scale: Scale, value: f32) -> f32 {
+    match scale {
+        Scale::Celsius => value,
+        _ => crate::to_cels(&scale, value),
+    }
+}
+
+#[test]
+fn test_summarize_locations() {
+    let summary = summarize(&super::parse_history(super::SAMPLE_LOG), None, None);
+    assert_eq!(summary.locations.len(), 1);
+    let juneau = &summary.locations[0];
+    assert_eq!(juneau.location, "Juneau, Alaska");
+    assert_eq!(juneau.lookups, 2);
+    assert_eq!(juneau.min, 11.1);
+    assert_eq!(juneau.max, 15.1);
+    assert!((juneau.mean - 13.1).abs() < 0.0001);
+}
+
+#[test]
+fn test_summarize_counts() {
+    let summary = summarize(&super::parse_history(super::SAMPLE_LOG), None, None);
+    assert_eq!(summary.conversions, vec![((Scale::Kelvin, 12.0), 1)]);
+    assert_eq!(summary.per_day, vec![
+        (NaiveDate::from_ymd_opt(2024, 8, 14).unwrap(), 3),
+        (NaiveDate::from_ymd_opt(2024, 8, 15).unwrap(), 3),
+    ]);
+    assert_eq!(summary.per_app, vec![(None, 2), (Some(Application::CLI), 2), (Some(Application::GUI), 2)]);
+}
+
+#[test]
+fn test_summarize_period() {
+    let day = NaiveDate::from_ymd_opt(2024, 8, 15);
+    let summary = summarize(&super::parse_history(super::SAMPLE_LOG), day, day);
+    assert_eq!(summary.locations[0].lookups, 1);
+    assert_eq!(summary.locations[0].mean, 15.1);
+    assert!(summary.conversions.is_empty());
+    assert_eq!(summary.per_day.len(), 1);
+}
@@ -0,0 +1,346 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, prelude::*};
+
+use chrono::{DateTime, FixedOffset};
+
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+
+use crate::config::HistoryConfig;
+
+use super::super::{parse_history, HistoryEntry};
+use super::HistoryStore;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FileFormat {
+    /// The human-readable log, one blank-line separated block per entry.
+    Text,
+    /// One JSON object per line.
+    Jsonl,
+}
+
+/// History kept in a single file, compressed into numbered archives as it outgrows `max_bytes`.
+#[derive(Debug, Clone)]
+pub struct FileStore {
+    config: HistoryConfig,
+    format: FileFormat,
+}
+
+impl FileStore {
+    pub fn text(config: &HistoryConfig) -> FileStore {
+        FileStore { config: config.clone(), format: FileFormat::Text }
+    }
+
+    pub fn jsonl(config: &HistoryConfig) -> FileStore {
+        FileStore { config: config.clone(), format: FileFormat::Jsonl }
+    }
+
+    pub fn path(&self) -> String {
+        self.config.log_path()
+    }
+
+    pub fn archive_path(&self, n: usize) -> String {
+        format!("{}.{}.gz", self.path(), n)
+    }
+
+    /// Rotates the file if appending `incoming` more bytes would take it past `max_bytes`.
+    pub fn rotate_if_needed(&self, incoming: u64) -> io::Result<bool> {
+        let Some(max) = self.config.max_bytes else { return Ok(false) };
+        let size = match fs::metadata(self.path()) {
+            Ok(m) => m.len(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+            Err(e) => return Err(e),
+        };
+        if size == 0 || size + incoming <= max {
+            return Ok(false);
+        }
+        self.rotate()?;
+        Ok(true)
+    }
+
+    /// Compresses the current file into archive 1, shifting older archives up and dropping the oldest.
+    pub fn rotate(&self) -> io::Result<()> {
+        let archives = self.config.archives;
+        if archives > 0 {
+            remove_if_exists(&self.archive_path(archives))?;
+            for n in (1..archives).rev() {
+                match fs::rename(self.archive_path(n), self.archive_path(n + 1)) {
+                    Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                    _ => {}
+                }
+            }
+            let mut encoder = GzEncoder::new(File::create(self.archive_path(1))?, Compression::default());
+            io::copy(&mut File::open(self.path())?, &mut encoder)?;
+            encoder.finish()?;
+        }
+        remove_if_exists(&self.path())
+    }
+
+    fn render(&self, entry: &HistoryEntry) -> io::Result<String> {
+        match self.format {
+            FileFormat::Text => Ok(entry.to_log_string()),
+            FileFormat::Jsonl => Ok(serde_json::to_string(entry)? + "\n"),
+        }
+    }
+
+    fn parse(&self, contents: &str) -> Vec<HistoryEntry> {
+        match self.format {
+            FileFormat::Text => parse_history(contents),
+            FileFormat::Jsonl => contents.lines().filter_map(|l| serde_json::from_str(l).ok()).collect(),
+        }
+    }
+
+    fn read(&self) -> io::Result<String> {
+        match fs::read_to_string(self.path()) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok("".to_string()),
+            r => r,
+        }
+    }
+
+    fn retain(&self, filter: impl FnOnce(&mut Vec<HistoryEntry>)) -> io::Result<usize> {
+        let mut entries = self.parse(&self.read()?);
+        let before = entries.len();
+        filter(&mut entries);
+        let removed = before - entries.len();
+        if removed > 0 {
+            let mut out = String::new();
+            for entry in &entries {
+                out += &self.render(entry)?;
+            }
+            write_atomic(&self.path(), out.as_bytes())?;
+        }
+        Ok(removed)
+    }
+
+    /// Like `retain` for archive `n`, which is deleted if nothing is left in it.
+    fn retain_archive(&self, n: usize, filter: impl FnOnce(&mut Vec<HistoryEntry>)) -> io::Result<usize> {
+        let path = self.archive_path(n);
+        let mut entries = self.parse(&read_archive(&path)?);
+        let before = entries.len();
+        filter(&mut entries);
+        let removed = before - entries.len();
+        if entries.is_empty() {
+            remove_if_exists(&path)?;
+        } else if removed > 0 {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            for entry in &entries {
+                encoder.write_all(self.render(entry)?.as_bytes())?;
+            }
+            write_atomic(&path, &encoder.finish()?)?;
+        }
+        Ok(removed)
+    }
+}
+
+impl HistoryStore for FileStore {
+    fn append(&self, entry: &HistoryEntry) -> io::Result<()> {
+        let line = self.render(entry)?;
+        self.rotate_if_needed(line.len() as u64)?;
+        OpenOptions::new().create(true).append(true).open(self.path())?.write_all(line.as_bytes())
+    }
+
+    fn entries(&self) -> io::Result<Vec<HistoryEntry>> {
+        Ok(self.parse(&self.read()?))
+    }
+
+    fn read_text(&self) -> io::Result<String> {
+        match self.format {
+            FileFormat::Text => fs::read_to_string(self.path()),
+            FileFormat::Jsonl => Ok(self.entries()?.iter().map(|e| e.to_log_string()).collect()),
+        }
+    }
+
+    /// Also deletes archives last written before `cutoff`, and drops the older entries from archives that straddle it.
+    fn remove_before(&self, cutoff: DateTime<FixedOffset>) -> io::Result<usize> {
+        let mut removed = 0;
+        for n in 1..=self.config.archives {
+            let path = self.archive_path(n);
+            let Ok(modified) = fs::metadata(&path).and_then(|m| m.modified()) else { continue };
+            if DateTime::<chrono::Utc>::from(modified) < cutoff {
+                removed += self.parse(&read_archive(&path)?).len();
+                remove_if_exists(&path)?;
+            } else {
+                removed += self.retain_archive(n, |entries| entries.retain(|e| e.timestamp >= cutoff))?;
+            }
+        }
+        Ok(removed + self.retain(|entries| entries.retain(|e| e.timestamp >= cutoff))?)
+    }
+
+    /// Keeps the latest `count` entries across the file and its archives, newest first.
+    fn keep_latest(&self, count: usize) -> io::Result<usize> {
+        let mut left = count;
+        let mut keep = |entries: &mut Vec<HistoryEntry>| {
+            let excess = entries.len().saturating_sub(left);
+            entries.drain(..excess);
+            left -= entries.len();
+        };
+        let mut removed = self.retain(&mut keep)?;
+        for n in 1..=self.config.archives {
+            if fs::metadata(self.archive_path(n)).is_ok() {
+                removed += self.retain_archive(n, &mut keep)?;
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Empties the file and deletes every archive.
+    fn clear(&self) -> io::Result<()> {
+        File::create(self.path())?;
+        let mut n = 1;
+        while n <= self.config.archives || fs::metadata(self.archive_path(n)).is_ok() {
+            remove_if_exists(&self.archive_path(n))?;
+            n += 1;
+        }
+        Ok(())
+    }
+}
+
+fn read_archive(path: &str) -> io::Result<String> {
+    let mut contents = String::new();
+    GzDecoder::new(File::open(path)?).read_to_string(&mut contents)?;
+    Ok(contents)
+}
+
+/// Replaces the file at `path` with `contents` by writing a temporary file next to it and renaming it over,
+/// so a crash or a full disk partway through leaves the old file as it was. The temporary file is named for the
+/// process, so processes writing the same file at once don't write into each other's.
+pub(crate) fn write_atomic(path: &str, contents: &[u8]) -> io::Result<()> {
+    let temp = format!("{}.{}.tmp", path, std::process::id());
+    let written = File::create(&temp).and_then(|mut f| {
+        f.write_all(contents)?;
+        f.sync_all()
+    });
+    match written.and_then(|_| fs::rename(&temp, path)) {
+        Ok(()) => Ok(()),
+        Err(e) => {
+            let _ = fs::remove_file(&temp);
+            Err(e)
+        }
+    }
+}
+
+fn remove_if_exists(path: &str) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+fn test_config(dir: &tempfile::TempDir, name: &str) -> HistoryConfig {
+    HistoryConfig {
+        path: Some(dir.path().join(name).to_string_lossy().to_string()),
+        ..HistoryConfig::default()
+    }
+}
+
+#[test]
+fn test_text_store() {
+    let dir = tempfile::tempdir().unwrap();
+    super::test_store_behaviour(&FileStore::text(&test_config(&dir, "log.txt")));
+}
+
+#[test]
+fn test_jsonl_store() {
+    let dir = tempfile::tempdir().unwrap();
+    let store = FileStore::jsonl(&test_config(&dir, "log.jsonl"));
+    super::test_store_behaviour(&store);
+
+    store.append(&parse_history(super::super::SAMPLE_LOG)[0]).unwrap();
+    let line = fs::read_to_string(store.path()).unwrap();
+    assert_eq!(line.lines().count(), 1);
+    assert!(line.contains("\"operation\":\"weather_lookup\""));
+    assert!(line.contains("\"location\":\"Juneau, Alaska\""));
+}
+
+#[test]
+fn test_rotate_when_full() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = HistoryConfig { max_bytes: Some(100), archives: 2, ..test_config(&dir, "log.txt") };
+    let store = FileStore::text(&config);
+    for _ in 0..3 {
+        super::super::write_to_log(&config, &"x".repeat(80), crate::Application::CLI).unwrap();
+    }
+    assert!(fs::metadata(store.archive_path(1)).is_ok());
+    assert!(fs::metadata(store.archive_path(2)).is_ok());
+    assert!(fs::metadata(store.archive_path(3)).is_err());
+    assert_eq!(store.entries().unwrap().len(), 1);
+
+    let mut archived = String::new();
+    flate2::read::GzDecoder::new(File::open(store.archive_path(1)).unwrap()).read_to_string(&mut archived).unwrap();
+    assert_eq!(parse_history(&archived).len(), 1);
+
+    store.clear().unwrap();
+    assert!(fs::metadata(store.archive_path(1)).is_err());
+    assert!(store.entries().unwrap().is_empty());
+}
+
+#[test]
+fn test_retain_replaces_log() {
+    let dir = tempfile::tempdir().unwrap();
+    let store = FileStore::text(&test_config(&dir, "log.txt"));
+    let entries = parse_history(super::super::SAMPLE_LOG);
+    for entry in &entries {
+        store.append(entry).unwrap();
+    }
+    assert_eq!(store.keep_latest(2).unwrap(), entries.len() - 2);
+    assert_eq!(store.entries().unwrap().len(), 2);
+    assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+}
+
+#[test]
+fn test_remove_before_filters_archives() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = HistoryConfig { archives: 2, ..test_config(&dir, "log.txt") };
+    let store = FileStore::text(&config);
+    // SAMPLE_LOG runs from 2024-08-14 to 2024-08-15, and the archive is only written now, after the cutoff.
+    let entries = parse_history(super::super::SAMPLE_LOG);
+    for entry in &entries {
+        store.append(entry).unwrap();
+    }
+    store.rotate().unwrap();
+    let cutoff = DateTime::parse_from_rfc3339("2024-08-15T00:00:00-04:00").unwrap();
+    let older = entries.iter().filter(|e| e.timestamp < cutoff).count();
+    assert!(older > 0);
+    assert_eq!(store.remove_before(cutoff).unwrap(), older);
+
+    let archived = parse_history(&read_archive(&store.archive_path(1)).unwrap());
+    assert_eq!(archived.len(), entries.len() - older);
+    assert!(archived.iter().all(|e| e.timestamp >= cutoff));
+
+    let later = DateTime::parse_from_rfc3339("2024-08-16T00:00:00-04:00").unwrap();
+    assert_eq!(store.remove_before(later).unwrap(), entries.len() - older);
+    assert!(fs::metadata(store.archive_path(1)).is_err());
+
+    // An archive written before the cutoff is deleted whole, and its entries are counted.
+    for entry in &entries {
+        store.append(entry).unwrap();
+    }
+    store.rotate().unwrap();
+    let tomorrow = (chrono::Utc::now() + chrono::Duration::days(1)).fixed_offset();
+    assert_eq!(store.remove_before(tomorrow).unwrap(), entries.len());
+    assert!(fs::metadata(store.archive_path(1)).is_err());
+}
+
+#[test]
+fn test_keep_latest_across_archives() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = HistoryConfig { archives: 2, ..test_config(&dir, "log.txt") };
+    let store = FileStore::text(&config);
+    let entries = parse_history(super::super::SAMPLE_LOG);
+    assert!(entries.len() >= 3);
+    for rotated in [true, true, false] {
+        for entry in &entries {
+            store.append(entry).unwrap();
+        }
+        if rotated {
+            store.rotate().unwrap();
+        }
+    }
+    // The live file's entries are the newest, then archive 1's, then archive 2's.
+    let keep = entries.len() + 2;
+    assert_eq!(store.keep_latest(keep).unwrap(), 3 * entries.len() - keep);
+    assert_eq!(store.entries().unwrap().len(), entries.len());
+    let archived = parse_history(&read_archive(&store.archive_path(1)).unwrap());
+    assert_eq!(archived, entries[entries.len() - 2..]);
+    assert!(fs::metadata(store.archive_path(2)).is_err());
+}
@@ -0,0 +1,78 @@
+use std::io;
+
+use chrono::{DateTime, FixedOffset};
+
+use serde::{Serialize, Deserialize};
+
+use crate::config::HistoryConfig;
+
+use super::HistoryEntry;
+
+pub mod file;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+
+pub use file::*;
+#[cfg(feature = "sqlite")]
+pub use sqlite::*;
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HistoryBackend {
+    /// The plain text log both frontends have always written.
+    #[default]
+    File,
+    Jsonl,
+    /// Requires the `sqlite` cargo feature.
+    Sqlite,
+}
+
+/// Somewhere history entries are kept. Entries are returned oldest first.
+pub trait HistoryStore {
+    fn append(&self, entry: &HistoryEntry) -> io::Result<()>;
+
+    fn entries(&self) -> io::Result<Vec<HistoryEntry>>;
+
+    /// The history as it is printed by `--read` and the GUI History page.
+    fn read_text(&self) -> io::Result<String> {
+        Ok(self.entries()?.iter().map(|e| e.to_log_string()).collect())
+    }
+
+    /// Removes entries logged before `cutoff`, returning how many were removed.
+    fn remove_before(&self, cutoff: DateTime<FixedOffset>) -> io::Result<usize>;
+
+    /// Removes all but the newest `count` entries, returning how many were removed.
+    fn keep_latest(&self, count: usize) -> io::Result<usize>;
+
+    fn clear(&self) -> io::Result<()>;
+}
+
+pub fn open_store(config: &HistoryConfig) -> io::Result<Box<dyn HistoryStore>> {
+    match config.backend {
+        HistoryBackend::File => Ok(Box::new(FileStore::text(config))),
+        HistoryBackend::Jsonl => Ok(Box::new(FileStore::jsonl(config))),
+        #[cfg(feature = "sqlite")]
+        HistoryBackend::Sqlite => Ok(Box::new(SqliteStore::open(&config.log_path())?)),
+        #[cfg(not(feature = "sqlite"))]
+        HistoryBackend::Sqlite => Err(io::Error::other("the sqlite history backend requires the `sqlite` feature")),
+    }
+}
+
+#[cfg(test)]
+pub(crate) fn test_store_behaviour(store: &dyn HistoryStore) {
+    let sample = super::parse_history(super::SAMPLE_LOG);
+    for entry in &sample {
+        store.append(entry).unwrap();
+    }
+    assert_eq!(store.entries().unwrap(), sample);
+    assert_eq!(super::parse_history(&store.read_text().unwrap()), sample);
+
+    let cutoff = DateTime::parse_from_rfc3339("2024-08-15T00:00:00-04:00").unwrap();
+    assert_eq!(store.remove_before(cutoff).unwrap(), 3);
+    assert_eq!(store.entries().unwrap(), sample[3..]);
+    assert_eq!(store.keep_latest(1).unwrap(), 2);
+    assert_eq!(store.entries().unwrap(), sample[5..]);
+
+    store.clear().unwrap();
+    assert!(store.entries().unwrap().is_empty());
+}
@@ -0,0 +1,140 @@
+use std::io;
+
+use chrono::{DateTime, FixedOffset};
+
+use rusqlite::{params, Connection};
+
+use super::super::HistoryEntry;
+use super::HistoryStore;
+
+/// History kept in an SQLite database, one row per entry.
+pub struct SqliteStore {
+    conn: Connection,
+}
+
+impl SqliteStore {
+    pub fn open(path: &str) -> io::Result<SqliteStore> {
+        SqliteStore::with_connection(Connection::open(path).map_err(io::Error::other)?)
+    }
+
+    pub fn in_memory() -> io::Result<SqliteStore> {
+        SqliteStore::with_connection(Connection::open_in_memory().map_err(io::Error::other)?)
+    }
+
+    fn with_connection(conn: Connection) -> io::Result<SqliteStore> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS history (
+                id INTEGER PRIMARY KEY,
+                unix_ms INTEGER NOT NULL,
+                timestamp TEXT NOT NULL,
+                app TEXT,
+                operation TEXT NOT NULL,
+                location TEXT,
+                readings TEXT NOT NULL,
+                text TEXT NOT NULL,
+                zone TEXT
+            );
+            CREATE INDEX IF NOT EXISTS history_unix_ms ON history (unix_ms);"
+        ).map_err(io::Error::other)?;
+        // Databases made before entries recorded their time zone.
+        if conn.prepare("SELECT zone FROM history LIMIT 0").is_err() {
+            conn.execute("ALTER TABLE history ADD COLUMN zone TEXT", []).map_err(io::Error::other)?;
+        }
+        Ok(SqliteStore { conn })
+    }
+}
+
+impl HistoryStore for SqliteStore {
+    fn append(&self, entry: &HistoryEntry) -> io::Result<()> {
+        self.conn.execute(
+            "INSERT INTO history (unix_ms, timestamp, app, operation, location, readings, text, zone)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                entry.timestamp.timestamp_millis(),
+                entry.timestamp.to_rfc3339(),
+                serde_json::to_string(&entry.app)?,
+                serde_json::to_string(&entry.operation)?,
+                entry.location,
+                serde_json::to_string(&entry.readings)?,
+                entry.text,
+                entry.zone,
+            ],
+        ).map_err(io::Error::other)?;
+        Ok(())
+    }
+
+    fn entries(&self) -> io::Result<Vec<HistoryEntry>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT timestamp, app, operation, location, readings, text, zone FROM history ORDER BY id"
+        ).map_err(io::Error::other)?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, Option<String>>(3)?,
+                row.get::<_, String>(4)?,
+                row.get::<_, String>(5)?,
+                row.get::<_, Option<String>>(6)?,
+            ))
+        }).map_err(io::Error::other)?;
+
+        let mut entries = Vec::new();
+        for row in rows {
+            let (timestamp, app, operation, location, readings, text, zone) = row.map_err(io::Error::other)?;
+            entries.push(HistoryEntry {
+                timestamp: DateTime::parse_from_rfc3339(&timestamp).map_err(io::Error::other)?,
+                app: serde_json::from_str(&app)?,
+                operation: serde_json::from_str(&operation)?,
+                location,
+                readings: serde_json::from_str(&readings)?,
+                text,
+                zone,
+            });
+        }
+        Ok(entries)
+    }
+
+    fn remove_before(&self, cutoff: DateTime<FixedOffset>) -> io::Result<usize> {
+        self.conn.execute("DELETE FROM history WHERE unix_ms < ?1", params![cutoff.timestamp_millis()])
+            .map_err(io::Error::other)
+    }
+
+    fn keep_latest(&self, count: usize) -> io::Result<usize> {
+        self.conn.execute(
+            "DELETE FROM history WHERE id NOT IN (SELECT id FROM history ORDER BY id DESC LIMIT ?1)",
+            params![count as i64],
+        ).map_err(io::Error::other)
+    }
+
+    fn clear(&self) -> io::Result<()> {
+        self.conn.execute("DELETE FROM history", []).map_err(io::Error::other)?;
+        Ok(())
+    }
+}
+
+#[test]
+fn test_sqlite_store() {
+    super::test_store_behaviour(&SqliteStore::in_memory().unwrap());
+}
+
+#[test]
+fn test_sqlite_store_persists() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("history.sqlite3").to_string_lossy().to_string();
+    let entries = super::super::parse_history(super::super::SAMPLE_LOG);
+    SqliteStore::open(&path).unwrap().append(&entries[0]).unwrap();
+    assert_eq!(SqliteStore::open(&path).unwrap().entries().unwrap(), entries[..1]);
+}
+
+#[test]
+fn test_sqlite_store_adds_zone() {
+    let conn = Connection::open_in_memory().unwrap();
+    conn.execute_batch(
+        "CREATE TABLE history (id INTEGER PRIMARY KEY, unix_ms INTEGER NOT NULL, timestamp TEXT NOT NULL, app TEXT,
+            operation TEXT NOT NULL, location TEXT, readings TEXT NOT NULL, text TEXT NOT NULL);").unwrap();
+    let store = SqliteStore::with_connection(conn).unwrap();
+    let entry = HistoryEntry { zone: Some("America/Juneau".to_string()), ..super::super::parse_history(super::super::SAMPLE_LOG)[0].clone() };
+    store.append(&entry).unwrap();
+    assert_eq!(store.entries().unwrap(), vec![entry]);
+}
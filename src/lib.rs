@@ -1,149 +1,110 @@
-use std::fs::File;
-
 use serde::{Serialize, Deserialize};
 
-use std::io::prelude::*;
+pub mod config;
+pub mod daemon;
+pub mod history;
+pub mod timezone;
+pub mod weather;
 
-use chrono;
+pub use config::Config;
+pub use daemon::*;
+pub use timezone::*;
+pub use weather::*;
 
-#[derive(Debug, PartialEq, Clone, Copy)]
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
 pub enum Scale {
     Kelvin,
     Celsius,
     Fahrenheit,
 }
 
-#[derive(Debug, PartialEq)]
-pub enum Application {
-    CLI,
-    GUI
-}
-
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-pub struct Todo {
-    pub location: Location,
-    pub current: Current,
-}
-
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-pub struct Location {
-    pub name: String,
-    pub region: String,
-    pub country: String,
-    pub lat: f64,
-    pub lon: f64,
-    pub tz_id: String,
-    pub localtime_epoch: i64,
-    pub localtime: String,
-}
+impl Scale {
+    /// Reads a scale name or its initial, e.g. "celsius" or "C".
+    pub fn from_name(name: &str) -> Option<Scale> {
+        match name.trim().to_lowercase().as_str() {
+            "k" | "kelvin" => Some(Scale::Kelvin),
+            "c" | "celsius" => Some(Scale::Celsius),
+            "f" | "fahrenheit" => Some(Scale::Fahrenheit),
+            _ => None,
+        }
+    }
 
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-pub struct Current {
-    pub last_updated_epoch: i64,
-    pub last_updated: String,
-    pub temp_c: f32,
-    pub temp_f: f32,
-    pub is_day: i64,
-    pub condition: Condition,
-    pub wind_mph: f64,
-    pub wind_kph: f64,
-    pub wind_degree: i64,
-    pub wind_dir: String,
-    pub pressure_mb: f64,
-    pub pressure_in: f64,
-    pub precip_mm: f64,
-    pub precip_in: f64,
-    pub humidity: i64,
-    pub cloud: i64,
-    pub feelslike_c: f64,
-    pub feelslike_f: f64,
-    pub windchill_c: f64,
-    pub windchill_f: f64,
-    pub heatindex_c: f64,
-    pub heatindex_f: f64,
-    pub dewpoint_c: f64,
-    pub dewpoint_f: f64,
-    pub vis_km: f64,
-    pub vis_miles: f64,
-    pub uv: f64,
-    pub gust_mph: f64,
-    pub gust_kph: f64,
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Scale::Kelvin => "K",
+            Scale::Celsius => "°C",
+            Scale::Fahrenheit => "°F",
+        }
+    }
 }
 
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-pub struct Condition {
-    pub text: String,
-    pub icon: String,
-    pub code: i64,
+/// Reads a comma separated list of scales, e.g. "C,F".
+pub fn parse_scales(input: &str) -> Result<Vec<Scale>, String> {
+    input.split(',')
+        .map(|s| Scale::from_name(s).ok_or(format!("unknown scale {}", s.trim())))
+        .collect()
 }
 
-pub fn read_from_file() -> std::io::Result<(String)> {
-    let mut file = File::open("temperature-converter-log.txt")?;
-    let mut contents = String::new();
-    file.read_to_string(&mut contents)?;
-    Ok((contents))
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub enum Application {
+    CLI,
+    GUI,
+    /// The background daemon, which records each location it polls.
+    Daemon
 }
 
-pub fn write_to_file(input: &String, app: Application) -> std::io::Result<()> {
-    let mut file = File::open("temperature-converter-log.txt")?;
-    let mut contents= match read_from_file() {
-        Ok(t) => t,
-        Err(_) => "".to_string()
-    };
-    let mut ad = format!("on {}", chrono::offset::Local::now()).to_string();
-    if app == Application::CLI {
-        ad = format!(" - from CLI {}", ad);
-    } else {
-        ad = format!(" - from GUI {}", ad);
-    }
-    file = File::create("temperature-converter-log.txt")?;
-    file.write_all((contents + input + &ad + "\n\n").as_bytes())?;
-    Ok(())
+pub fn read_from_file() -> std::io::Result<String> {
+    history::read_log(&Config::load().unwrap_or_default().history)
 }
 
-pub async fn get_current_temp(zip: String) -> Result<(String, String, f32), reqwest::Error> {
-    let resp: Todo = reqwest::Client::new().get(
-        (format!("http://api.weatherapi.com/v1/current.json?key=93433e17da654845a4b23402241708&q={}&aqi=no", zip)))
-        .send().await?.json().await?;
-    Ok(((resp.location.name, resp.location.region, resp.current.temp_c)))
+pub fn write_to_file(input: &str, app: Application) -> std::io::Result<()> {
+    history::write_to_log(&Config::load().unwrap_or_default().history, input, app)
 }
 
 pub fn calculate(input: (Scale, f32)) -> ((Scale, f32), (Scale, f32), (Scale, f32)) {
     //Changed input from unparsed string to output of parse_temp_input and made changes to main method accordingly
-    let mut conversions: ((Scale, f32), (Scale, f32)) = ((Scale::Kelvin, 0.0), (Scale::Kelvin, 0.0));
-    conversions = convert(&input.0, input.1);
-    return ((input.0, input.1), (conversions.0.0, conversions.0.1), (conversions.1.0, conversions.1.1));
+    let conversions: ((Scale, f32), (Scale, f32)) = convert(&input.0, input.1);
+    ((input.0, input.1), (conversions.0.0, conversions.0.1), (conversions.1.0, conversions.1.1))
+}
+
+/// Converts a Celsius temperature into `scale`.
+pub fn from_celsius(celsius: f32, scale: Scale) -> f32 {
+    match scale {
+        Scale::Kelvin => to_kelv(&Scale::Celsius, celsius),
+        Scale::Celsius => celsius,
+        Scale::Fahrenheit => to_fahr(&Scale::Celsius, celsius),
+    }
 }
 
 pub fn convert(scale: &Scale, value: f32) -> ((Scale, f32), (Scale, f32)) {
     match scale {
-        Scale::Kelvin => return ((Scale::Celsius, to_cels(&scale, value)), (Scale::Fahrenheit, to_fahr(&scale, value))),
-        Scale::Celsius => return ((Scale::Kelvin, to_kelv(&scale, value)), (Scale::Fahrenheit, to_fahr(&scale, value))),
-        Scale::Fahrenheit => return ((Scale::Kelvin, to_kelv(&scale, value)), (Scale::Celsius, to_cels(&scale, value)))
+        Scale::Kelvin => ((Scale::Celsius, to_cels(scale, value)), (Scale::Fahrenheit, to_fahr(scale, value))),
+        Scale::Celsius => ((Scale::Kelvin, to_kelv(scale, value)), (Scale::Fahrenheit, to_fahr(scale, value))),
+        Scale::Fahrenheit => ((Scale::Kelvin, to_kelv(scale, value)), (Scale::Celsius, to_cels(scale, value)))
     }
 }
 
 pub fn to_cels(scale: &Scale, value: f32) -> f32 {
     match scale {
-        Scale::Kelvin => return value - 273.15,
-        Scale::Fahrenheit => return (value - 32.0) * 5.0/9.0,
-        _=> return 0.0
+        Scale::Kelvin => value - 273.15,
+        Scale::Fahrenheit => (value - 32.0) * 5.0/9.0,
+        _=> 0.0
     }
 }
 
 pub fn to_fahr(scale: &Scale, value: f32) -> f32 {
     match scale {
-        Scale::Kelvin => return (value - 273.15) * 9.0/5.0 + 32.0,
-        Scale::Celsius => return value * 9.0/5.0 + 32.0,
-        _=> return 0.0
+        Scale::Kelvin => (value - 273.15) * 9.0/5.0 + 32.0,
+        Scale::Celsius => value * 9.0/5.0 + 32.0,
+        _=> 0.0
     }
 }
 
 pub fn to_kelv(scale: &Scale, value: f32) -> f32 {
     match scale {
-        Scale::Fahrenheit => return (value - 32.0) * 5.0/9.0 + 273.15,
-        Scale::Celsius => return value + 273.15,
-        _=> return 0.0
+        Scale::Fahrenheit => (value - 32.0) * 5.0/9.0 + 273.15,
+        Scale::Celsius => value + 273.15,
+        _=> 0.0
     }
 }
 
@@ -229,4 +190,11 @@ test_convert_round![
     test_convert_round_15: (Scale::Celsius, -1234.0) => (-960.85, -2189.2)
     test_convert_round_16: (Scale::Fahrenheit, -1234.0) => (-430.1833, -703.3333)
     test_convert_round_17: (Scale::Kelvin, -1234.0) => (-1507.15, -2680.87)
-];
\ No newline at end of file
+];
+#[test]
+fn test_parse_scales() {
+    assert_eq!(parse_scales("C,f, kelvin"), Ok(vec![Scale::Celsius, Scale::Fahrenheit, Scale::Kelvin]));
+    assert_eq!(parse_scales("C,R"), Err("unknown scale R".to_string()));
+    assert_eq!(from_celsius(100.0, Scale::Fahrenheit), 212.0);
+    assert_eq!(Scale::Kelvin.symbol(), "K");
+}
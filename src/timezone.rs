@@ -0,0 +1,46 @@
+use chrono::{DateTime, Local, TimeZone, Utc};
+
+use chrono_tz::Tz;
+
+/// Reads an IANA time zone name such as "America/Juneau", the form providers report as `tz_id`.
+pub fn parse_tz(tz_id: &str) -> Option<Tz> {
+    tz_id.trim().trim_start_matches(':').parse().ok()
+}
+
+/// The user's own time zone: `TZ` if it names one, otherwise the system setting.
+pub fn user_zone() -> Option<Tz> {
+    std::env::var("TZ").ok().and_then(|tz| parse_tz(&tz))
+        .or_else(|| iana_time_zone::get_timezone().ok().and_then(|tz| parse_tz(&tz)))
+}
+
+/// A Unix timestamp in `tz`.
+pub fn zone_time(epoch: i64, tz: Tz) -> Option<DateTime<Tz>> {
+    tz.timestamp_opt(epoch, 0).single()
+}
+
+/// A time with its zone abbreviation, e.g. "2024-08-14 08:55 AKDT".
+pub fn format_zone_time<T: TimeZone>(time: &DateTime<T>) -> String
+where
+    T::Offset: std::fmt::Display,
+{
+    format!("{} {}", time.format("%Y-%m-%d %H:%M"), time.offset())
+}
+
+/// A Unix timestamp in the user's time zone, falling back to the system's UTC offset when the zone can't be named.
+pub fn format_user_time(epoch: i64, user: Option<Tz>) -> Option<String> {
+    match user {
+        Some(tz) => zone_time(epoch, tz).map(|t| format_zone_time(&t)),
+        None => DateTime::<Utc>::from_timestamp(epoch, 0)
+            .map(|t| t.with_timezone(&Local).format("%Y-%m-%d %H:%M %:z").to_string()),
+    }
+}
+
+#[test]
+fn test_zone_times() {
+    let juneau = parse_tz("America/Juneau").unwrap();
+    assert_eq!(parse_tz(":Europe/London"), Some(Tz::Europe__London));
+    assert_eq!(parse_tz("Mars/Olympus_Mons"), None);
+    assert_eq!(format_zone_time(&zone_time(1723654500, juneau).unwrap()), "2024-08-14 08:55 AKDT");
+    assert_eq!(format_user_time(1723654500, parse_tz("Europe/London")).unwrap(), "2024-08-14 17:55 BST");
+    assert_eq!(format_user_time(1704067200, Some(Tz::UTC)).unwrap(), "2024-01-01 00:00 UTC");
+}
@@ -0,0 +1,107 @@
+use serde::{Serialize, Deserialize};
+
+use super::details::round;
+
+/// Pollutant concentrations in µg/m³ and the indices derived from them, whichever provider they came from.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AirQuality {
+    pub pm2_5: Option<f32>,
+    pub pm10: Option<f32>,
+    pub o3: Option<f32>,
+    pub no2: Option<f32>,
+    /// The US EPA index, from 1 (good) to 6 (hazardous).
+    pub us_epa_index: Option<u8>,
+    /// The UK DEFRA daily air quality index, from 1 (low) to 10 (very high).
+    pub gb_defra_index: Option<u8>,
+}
+
+/// How bad an index reading is, on one scale for both indices so they can be shown alike.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum AirQualityLevel {
+    Good,
+    Moderate,
+    UnhealthyForSensitive,
+    Unhealthy,
+    VeryUnhealthy,
+    Hazardous,
+}
+
+/// An index reading's official category name, e.g. "Unhealthy for sensitive groups".
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AirQualityCategory {
+    pub name: &'static str,
+    pub level: AirQualityLevel,
+}
+
+pub fn us_epa_category(index: u8) -> Option<AirQualityCategory> {
+    let (name, level) = match index {
+        1 => ("Good", AirQualityLevel::Good),
+        2 => ("Moderate", AirQualityLevel::Moderate),
+        3 => ("Unhealthy for sensitive groups", AirQualityLevel::UnhealthyForSensitive),
+        4 => ("Unhealthy", AirQualityLevel::Unhealthy),
+        5 => ("Very unhealthy", AirQualityLevel::VeryUnhealthy),
+        6 => ("Hazardous", AirQualityLevel::Hazardous),
+        _ => return None,
+    };
+    Some(AirQualityCategory { name, level })
+}
+
+/// The DEFRA bands, mapped onto the EPA levels they advise the same precautions for.
+pub fn gb_defra_category(index: u8) -> Option<AirQualityCategory> {
+    let (name, level) = match index {
+        1..=3 => ("Low", AirQualityLevel::Good),
+        4..=6 => ("Moderate", AirQualityLevel::Moderate),
+        7..=9 => ("High", AirQualityLevel::Unhealthy),
+        10 => ("Very high", AirQualityLevel::VeryUnhealthy),
+        _ => return None,
+    };
+    Some(AirQualityCategory { name, level })
+}
+
+/// The readings as label and value pairs, with each index's category where it has one.
+/// Readings the provider didn't report are left out.
+pub fn air_quality_rows(aq: &AirQuality) -> Vec<(&'static str, String, Option<AirQualityCategory>)> {
+    let mut rows = Vec::new();
+    for (label, value) in [("PM2.5", aq.pm2_5), ("PM10", aq.pm10), ("Ozone (O3)", aq.o3), ("Nitrogen dioxide (NO2)", aq.no2)] {
+        if let Some(v) = value {
+            rows.push((label, format!("{} µg/m³", round(v)), None));
+        }
+    }
+    let indices = [
+        ("US EPA index", aq.us_epa_index, us_epa_category as fn(u8) -> Option<AirQualityCategory>),
+        ("UK DEFRA index", aq.gb_defra_index, gb_defra_category),
+    ];
+    for (label, index, category) in indices {
+        if let Some(i) = index {
+            let category = category(i);
+            rows.push((label, category.map_or(i.to_string(), |c| format!("{} ({})", i, c.name)), category));
+        }
+    }
+    rows
+}
+
+/// Renders the readings the way the CLI prints them, one indented field per line.
+pub fn format_air_quality(aq: &AirQuality) -> String {
+    air_quality_rows(aq).iter()
+        .map(|(label, value, _)| format!("    {}: {}", label, value))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[test]
+fn test_categories() {
+    assert_eq!(us_epa_category(3).map(|c| c.name), Some("Unhealthy for sensitive groups"));
+    assert_eq!(us_epa_category(6).map(|c| c.level), Some(AirQualityLevel::Hazardous));
+    assert_eq!(us_epa_category(7), None);
+    assert_eq!(gb_defra_category(5).map(|c| (c.name, c.level)), Some(("Moderate", AirQualityLevel::Moderate)));
+    assert_eq!(gb_defra_category(10).map(|c| c.name), Some("Very high"));
+    assert_eq!(gb_defra_category(0), None);
+}
+
+#[test]
+fn test_air_quality_rows() {
+    let aq = AirQuality { pm2_5: Some(5.55), o3: Some(62.0), us_epa_index: Some(2), gb_defra_index: Some(11), ..AirQuality::default() };
+    assert_eq!(format_air_quality(&aq), "    PM2.5: 5.6 µg/m³\n    Ozone (O3): 62 µg/m³\n    US EPA index: 2 (Moderate)\n    UK DEFRA index: 11");
+    assert_eq!(air_quality_rows(&aq)[2].2.map(|c| c.level), Some(AirQualityLevel::Moderate));
+    assert!(air_quality_rows(&AirQuality::default()).is_empty());
+}
@@ -0,0 +1,181 @@
+use std::fmt;
+
+use chrono::{DateTime, FixedOffset};
+
+use serde::{Serialize, Deserialize};
+
+use super::Place;
+
+/// How much harm an alert warns of, using the Common Alerting Protocol levels both providers report.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum AlertSeverity {
+    #[default]
+    Unknown,
+    Minor,
+    Moderate,
+    Severe,
+    Extreme,
+}
+
+impl AlertSeverity {
+    pub fn from_name(name: &str) -> AlertSeverity {
+        match name.trim().to_lowercase().as_str() {
+            "minor" => AlertSeverity::Minor,
+            "moderate" => AlertSeverity::Moderate,
+            "severe" => AlertSeverity::Severe,
+            "extreme" => AlertSeverity::Extreme,
+            _ => AlertSeverity::Unknown,
+        }
+    }
+}
+
+/// How soon to act on an alert, from the Common Alerting Protocol.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum AlertUrgency {
+    #[default]
+    Unknown,
+    Past,
+    Future,
+    Expected,
+    Immediate,
+}
+
+impl AlertUrgency {
+    pub fn from_name(name: &str) -> AlertUrgency {
+        match name.trim().to_lowercase().as_str() {
+            "past" => AlertUrgency::Past,
+            "future" => AlertUrgency::Future,
+            "expected" => AlertUrgency::Expected,
+            "immediate" => AlertUrgency::Immediate,
+            _ => AlertUrgency::Unknown,
+        }
+    }
+}
+
+impl fmt::Display for AlertSeverity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl fmt::Display for AlertUrgency {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+/// An active watch, warning or advisory, e.g. a heat advisory.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WeatherAlert {
+    /// The kind of alert, e.g. "Heat Advisory".
+    pub event: String,
+    pub headline: String,
+    pub severity: AlertSeverity,
+    pub urgency: AlertUrgency,
+    /// The counties or zones covered.
+    pub areas: Vec<String>,
+    pub effective: Option<DateTime<FixedOffset>>,
+    pub expires: Option<DateTime<FixedOffset>>,
+    pub description: String,
+    pub instruction: String,
+}
+
+/// The alerts in effect at a place, most severe first.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WeatherAlerts {
+    pub place: Place,
+    pub alerts: Vec<WeatherAlert>,
+}
+
+impl WeatherAlerts {
+    pub fn new(place: Place, mut alerts: Vec<WeatherAlert>) -> WeatherAlerts {
+        alerts.sort_by_key(|a| std::cmp::Reverse((a.severity, a.urgency)));
+        WeatherAlerts { place, alerts }
+    }
+}
+
+/// Reads an alert timestamp, e.g. "2024-08-14T11:00:00-08:00". Blank or unreadable times are left out.
+pub fn parse_alert_time(time: &str) -> Option<DateTime<FixedOffset>> {
+    DateTime::parse_from_rfc3339(time.trim()).ok()
+}
+
+/// Splits an area list such as "Juneau Borough; Haines Borough" into its areas.
+pub fn split_areas(areas: &str) -> Vec<String> {
+    areas.split(';').map(str::trim).filter(|a| !a.is_empty()).map(String::from).collect()
+}
+
+/// One line summing up an alert, e.g. "Heat Advisory (Moderate, Expected) until 2024-08-14 20:00 -08:00".
+pub fn alert_summary(alert: &WeatherAlert) -> String {
+    let until = alert.expires.map(|t| format!(" until {}", t.format("%Y-%m-%d %H:%M %:z"))).unwrap_or_default();
+    format!("{} ({}, {}){}", alert.event, alert.severity, alert.urgency, until)
+}
+
+/// Renders the alerts the way the CLI prints them, with each headline and area list under its summary.
+pub fn format_alerts(alerts: &WeatherAlerts) -> String {
+    if alerts.alerts.is_empty() {
+        return "-= Alerts =-\n    No active alerts".to_string();
+    }
+    let mut out = vec![format!("-= Alerts ({}) =-", alerts.alerts.len())];
+    for alert in &alerts.alerts {
+        out.push(format!("    {}", alert_summary(alert)));
+        if !alert.headline.is_empty() {
+            out.push(format!("        {}", alert.headline));
+        }
+        if !alert.areas.is_empty() {
+            out.push(format!("        Areas: {}", alert.areas.join("; ")));
+        }
+    }
+    out.join("\n")
+}
+
+/// A use history message for each alert, so alerts that were in effect are recorded along with the lookup.
+pub fn alert_log_entries(alerts: &WeatherAlerts) -> Vec<String> {
+    let place = format!("{}, {}", alerts.place.name, alerts.place.region);
+    alerts.alerts.iter().map(|alert| {
+        let time = |t: Option<DateTime<FixedOffset>>| t.map_or("-".to_string(), |t| t.to_rfc3339());
+        format!(
+            "Weather alert issued (\n-= Alert for {} =-\n    Event: {}\n    Headline: {}\n    Severity: {}\n    Urgency: {}\n    Areas: {}\n    Effective: {}\n    Expires: {}\n)",
+            place, alert.event, alert.headline, alert.severity, alert.urgency, alert.areas.join("; "),
+            time(alert.effective), time(alert.expires))
+    }).collect()
+}
+
+#[cfg(test)]
+fn heat_advisory() -> WeatherAlert {
+    WeatherAlert {
+        event: "Heat Advisory".to_string(),
+        headline: "Heat Advisory issued August 14 until 8PM".to_string(),
+        severity: AlertSeverity::Moderate,
+        urgency: AlertUrgency::Expected,
+        areas: split_areas("Juneau Borough;  Haines Borough;"),
+        effective: parse_alert_time("2024-08-14T11:00:00-08:00"),
+        expires: parse_alert_time("2024-08-14T20:00:00-08:00"),
+        ..WeatherAlert::default()
+    }
+}
+
+#[test]
+fn test_alert_levels() {
+    assert_eq!(AlertSeverity::from_name("Severe"), AlertSeverity::Severe);
+    assert_eq!(AlertSeverity::from_name(""), AlertSeverity::Unknown);
+    assert_eq!(AlertUrgency::from_name("immediate"), AlertUrgency::Immediate);
+    assert!(AlertSeverity::Extreme > AlertSeverity::Moderate);
+    let freeze = WeatherAlert { event: "Freeze Warning".to_string(), severity: AlertSeverity::Severe, ..WeatherAlert::default() };
+    let alerts = WeatherAlerts::new(Place::default(), vec![heat_advisory(), freeze]);
+    assert_eq!(alerts.alerts[0].event, "Freeze Warning");
+}
+
+#[test]
+fn test_format_alerts() {
+    let place = Place { name: "Juneau".to_string(), region: "Alaska".to_string(), ..Place::default() };
+    let alerts = WeatherAlerts::new(place.clone(), vec![heat_advisory()]);
+    assert_eq!(
+        format_alerts(&alerts),
+        "-= Alerts (1) =-\n    Heat Advisory (Moderate, Expected) until 2024-08-14 20:00 -08:00\n        \
+        Heat Advisory issued August 14 until 8PM\n        Areas: Juneau Borough; Haines Borough");
+    assert_eq!(format_alerts(&WeatherAlerts::new(place, Vec::new())), "-= Alerts =-\n    No active alerts");
+    let logged = alert_log_entries(&alerts);
+    assert_eq!(logged.len(), 1);
+    assert!(logged[0].starts_with("Weather alert issued (\n-= Alert for Juneau, Alaska =-\n    Event: Heat Advisory\n"));
+    assert!(logged[0].contains("\n    Expires: 2024-08-14T20:00:00-08:00\n)"));
+}
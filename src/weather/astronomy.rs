@@ -0,0 +1,123 @@
+use chrono::{NaiveDate, NaiveTime};
+
+use serde::{Serialize, Deserialize};
+
+use super::Place;
+
+/// Length of the lunar cycle in days.
+const SYNODIC_MONTH: f64 = 29.530588853;
+
+/// Sun and moon times at a place on one day, in the place's own time zone (`place.tz_id`).
+/// A time is `None` when the sun or moon doesn't rise or set that day, or the provider doesn't report it.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Astronomy {
+    pub place: Place,
+    pub date: NaiveDate,
+    pub sunrise: Option<NaiveTime>,
+    pub sunset: Option<NaiveTime>,
+    pub moonrise: Option<NaiveTime>,
+    pub moonset: Option<NaiveTime>,
+    /// e.g. "Waxing Gibbous".
+    pub moon_phase: String,
+    /// The lit fraction of the moon's disc, in percent.
+    pub moon_illumination: Option<f32>,
+    /// Whether the sun and moon are above the horizon right now, which is only known for today.
+    pub is_sun_up: Option<bool>,
+    pub is_moon_up: Option<bool>,
+}
+
+/// Reads a 12-hour time such as "05:08 AM". Anything else, like "No moonset", is `None`.
+pub fn parse_clock_time(time: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(time.trim(), "%I:%M %p").ok()
+}
+
+/// The moon's phase name and illumination in percent on `date`, worked out from the mean lunar cycle
+/// for providers that don't report it. Good to within about a day.
+pub fn moon_phase(date: NaiveDate) -> (&'static str, f32) {
+    // A new moon at 18:14 UTC on 6 January 2000.
+    let new_moon = NaiveDate::from_ymd_opt(2000, 1, 6).unwrap().and_hms_opt(18, 14, 0).unwrap();
+    let noon = date.and_hms_opt(12, 0, 0).unwrap();
+    let days = (noon - new_moon).num_seconds() as f64 / 86_400.0;
+    let age = days.rem_euclid(SYNODIC_MONTH);
+    let illumination = (1.0 - (2.0 * std::f64::consts::PI * age / SYNODIC_MONTH).cos()) / 2.0 * 100.0;
+    const PHASES: [&str; 8] = [
+        "New Moon", "Waxing Crescent", "First Quarter", "Waxing Gibbous",
+        "Full Moon", "Waning Gibbous", "Last Quarter", "Waning Crescent",
+    ];
+    let phase = PHASES[((age / SYNODIC_MONTH * 8.0).round() as usize) % 8];
+    (phase, illumination.round() as f32)
+}
+
+/// The times and moon phase as label and value pairs, times as "05:08" or "none".
+pub fn astronomy_rows(astro: &Astronomy) -> Vec<(&'static str, String)> {
+    let time = |t: Option<NaiveTime>| t.map_or("none".to_string(), |t| t.format("%H:%M").to_string());
+    let mut rows = vec![
+        ("Sunrise", time(astro.sunrise)),
+        ("Sunset", time(astro.sunset)),
+        ("Moonrise", time(astro.moonrise)),
+        ("Moonset", time(astro.moonset)),
+        ("Moon phase", match astro.moon_illumination {
+            Some(i) => format!("{} ({}% illuminated)", astro.moon_phase, i),
+            None => astro.moon_phase.clone(),
+        }),
+    ];
+    let up = |up: bool| if up { "up" } else { "down" };
+    if let (Some(sun), Some(moon)) = (astro.is_sun_up, astro.is_moon_up) {
+        rows.push(("Now", format!("sun {}, moon {}", up(sun), up(moon))));
+    }
+    rows
+}
+
+/// The time zone the times are in, for labelling them.
+pub fn astronomy_zone(astro: &Astronomy) -> &str {
+    if astro.place.tz_id.is_empty() { "local time" } else { &astro.place.tz_id }
+}
+
+/// Renders the times the way the CLI prints them, one indented field per line under the place and date.
+pub fn format_astronomy(astro: &Astronomy) -> String {
+    let mut out = vec![
+        format!("-= Astronomy for {}, {} =-", astro.place.name, astro.place.region),
+        format!("    Date: {} (times in {})", astro.date, astronomy_zone(astro)),
+    ];
+    out.extend(astronomy_rows(astro).iter().map(|(label, value)| format!("    {}: {}", label, value)));
+    out.join("\n")
+}
+
+#[test]
+fn test_parse_clock_time() {
+    assert_eq!(parse_clock_time("05:08 AM"), NaiveTime::from_hms_opt(5, 8, 0));
+    assert_eq!(parse_clock_time("09:23 PM"), NaiveTime::from_hms_opt(21, 23, 0));
+    assert_eq!(parse_clock_time("12:05 AM"), NaiveTime::from_hms_opt(0, 5, 0));
+    assert_eq!(parse_clock_time("No moonset"), None);
+}
+
+#[test]
+fn test_moon_phase() {
+    let day = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+    // Full moon on 19 August 2024, new moon on 4 August 2024.
+    assert_eq!(moon_phase(day(2024, 8, 19)).0, "Full Moon");
+    assert!(moon_phase(day(2024, 8, 19)).1 > 98.0);
+    assert_eq!(moon_phase(day(2024, 8, 4)).0, "New Moon");
+    assert!(moon_phase(day(2024, 8, 4)).1 < 2.0);
+    assert_eq!(moon_phase(day(2024, 8, 14)).0, "Waxing Gibbous");
+    assert_eq!(moon_phase(day(1999, 12, 22)).0, "Full Moon");
+}
+
+#[test]
+fn test_format_astronomy() {
+    let astro = Astronomy {
+        place: Place { name: "Juneau".to_string(), region: "Alaska".to_string(), tz_id: "America/Juneau".to_string(), ..Place::default() },
+        date: NaiveDate::from_ymd_opt(2024, 8, 14).unwrap(),
+        sunrise: parse_clock_time("05:08 AM"),
+        sunset: parse_clock_time("09:23 PM"),
+        moonrise: parse_clock_time("03:12 PM"),
+        moon_phase: "Waxing Gibbous".to_string(),
+        moon_illumination: Some(72.0),
+        ..Astronomy::default()
+    };
+    assert_eq!(format_astronomy(&astro), "-= Astronomy for Juneau, Alaska =-\n    Date: 2024-08-14 (times in America/Juneau)\n    \
+        Sunrise: 05:08\n    Sunset: 21:23\n    Moonrise: 15:12\n    Moonset: none\n    Moon phase: Waxing Gibbous (72% illuminated)");
+    let today = Astronomy { is_sun_up: Some(true), is_moon_up: Some(false), place: Place::default(), ..astro };
+    assert_eq!(astronomy_rows(&today).last().unwrap().1, "sun up, moon down");
+    assert_eq!(astronomy_zone(&today), "local time");
+}
@@ -0,0 +1,346 @@
+use std::collections::BTreeMap;
+use std::{fs, io};
+
+use serde::{Serialize, Deserialize};
+
+use crate::history::{read_entries, redact_location, write_atomic, HistoryEntry, LocationPrivacy, Operation};
+use crate::config::WeatherConfig;
+use crate::{ask_daemon, Config, Scale};
+
+use super::{get_observations, LocationQuery, Observation, Place, ProviderKind, WeatherError};
+
+/// Where an observation handed back by `get_cached_observations` came from.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ObservationSource {
+    /// Fetched from the provider just now.
+    #[default]
+    Live,
+    /// A cached observation that is still fresh.
+    Cache,
+    /// A cached observation past its TTL, used because the provider couldn't be reached.
+    Stale,
+    /// The last temperature recorded in the use history, used because the provider couldn't be reached
+    /// and nothing was cached. Only the place and temperature are known.
+    History,
+    /// Fetched by the background daemon, which answered in place of the provider.
+    Daemon,
+}
+
+/// An observation with when it was fetched from the provider.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CachedObservation {
+    pub observation: Observation,
+    pub fetched_epoch: i64,
+    /// Never stored, always `Live` when read back from the cache file.
+    #[serde(skip)]
+    pub source: ObservationSource,
+}
+
+impl CachedObservation {
+    /// Whether the observation can still be served, measured from the provider's last update
+    /// so an observation that was already old when fetched isn't kept for a whole `ttl_secs` more.
+    pub fn is_fresh(&self, ttl_secs: i64, now: i64) -> bool {
+        let updated = match self.observation.observed_epoch {
+            0 => self.fetched_epoch,
+            epoch => epoch,
+        };
+        now - updated < ttl_secs
+    }
+
+    pub fn is_stale(&self) -> bool {
+        matches!(self.source, ObservationSource::Stale | ObservationSource::History)
+    }
+
+    /// Says where a stored result came from and how old it is, e.g. "from cache, fetched 3 min ago".
+    /// `None` if it was fetched just now.
+    pub fn cache_note(&self, now: i64) -> Option<String> {
+        let age = format_age(now - self.fetched_epoch);
+        match self.source {
+            ObservationSource::Live => None,
+            ObservationSource::Cache => Some(format!("from cache, fetched {}", age)),
+            ObservationSource::Daemon => Some(format!("from the daemon, fetched {}", age)),
+            ObservationSource::Stale => Some(format!("STALE: the weather service was not reached, showing the observation fetched {}", age)),
+            ObservationSource::History => Some(format!("STALE: the weather service was not reached, showing the temperature recorded {}", age)),
+        }
+    }
+
+    /// A short age for tables, e.g. "3 min ago" or "STALE 2 h ago". `None` if it was fetched just now.
+    pub fn age_label(&self, now: i64) -> Option<String> {
+        let age = format_age(now - self.fetched_epoch);
+        match self.source {
+            ObservationSource::Live => None,
+            ObservationSource::Cache | ObservationSource::Daemon => Some(age),
+            ObservationSource::Stale | ObservationSource::History => Some(format!("STALE {}", age)),
+        }
+    }
+}
+
+/// Observations kept on disk between runs, keyed by provider and normalized location.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WeatherCache {
+    pub entries: BTreeMap<String, CachedObservation>,
+}
+
+impl WeatherCache {
+    /// Reads the cache file, starting empty if it is missing or can't be read.
+    pub fn load(path: &str) -> WeatherCache {
+        fs::read_to_string(path).ok().and_then(|t| serde_json::from_str(&t).ok()).unwrap_or_default()
+    }
+
+    /// Writes the cache file through a temporary file, as the CLI, GUI and daemon may all save it at once.
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        write_atomic(path, serde_json::to_string(self)?.as_bytes())
+    }
+
+    /// The observation stored under `key`, if it is still fresh.
+    pub fn get(&self, key: &str, ttl_secs: i64, now: i64) -> Option<CachedObservation> {
+        let entry = self.entries.get(key).filter(|e| e.is_fresh(ttl_secs, now))?;
+        Some(CachedObservation { source: ObservationSource::Cache, ..entry.clone() })
+    }
+
+    /// The observation stored under `key` however old it is, marked as stale.
+    pub fn get_stale(&self, key: &str) -> Option<CachedObservation> {
+        let entry = self.entries.get(key)?;
+        Some(CachedObservation { source: ObservationSource::Stale, ..entry.clone() })
+    }
+
+    pub fn insert(&mut self, key: String, observation: Observation, now: i64) {
+        self.entries.insert(key, CachedObservation { observation, fetched_epoch: now, source: ObservationSource::Live });
+    }
+
+    /// Drops every entry older than `keep_secs`.
+    pub fn prune(&mut self, keep_secs: i64, now: i64) {
+        self.entries.retain(|_, e| e.is_fresh(keep_secs, now));
+    }
+}
+
+/// The last temperature looked up for `query` in the use history, matched by the cache key recorded with the lookup.
+/// Entries logged without one are matched by place name, so they are only found for city queries or places the
+/// query names exactly.
+pub fn history_observation(config: &Config, entries: &[HistoryEntry], query: &LocationQuery) -> Option<CachedObservation> {
+    let key = logged_cache_key(config, query);
+    let first_part = |s: &str| s.split(',').next().unwrap_or("").trim().to_lowercase();
+    let matches = |location: &str| match query {
+        LocationQuery::City(name) => first_part(location) == first_part(name),
+        _ => location.eq_ignore_ascii_case(&query.to_string()),
+    };
+    let recorded = |e: &HistoryEntry| e.text.lines().find_map(|l| l.trim().strip_prefix("Query: ")).map(str::to_string);
+    let entry = entries.iter().rev().find(|e| {
+        // A stale result shown earlier is logged like any lookup, but its reading is older than its timestamp.
+        e.operation == Operation::WeatherLookup && !e.text.contains("STALE:")
+            && ((key.is_some() && recorded(e) == key) || e.location.as_deref().is_some_and(matches))
+    })?;
+    let (_, temp_c) = entry.readings.iter().find(|r| r.0 == Scale::Celsius)?;
+    let location = entry.location.as_deref().unwrap_or_default();
+    let (name, region) = location.split_once(", ").unwrap_or((location, ""));
+    Some(CachedObservation {
+        observation: Observation {
+            place: Place { name: name.to_string(), region: region.to_string(), ..Place::default() },
+            observed_epoch: entry.timestamp.timestamp(),
+            temp_c: *temp_c,
+            ..Observation::default()
+        },
+        fetched_epoch: entry.timestamp.timestamp(),
+        source: ObservationSource::History,
+    })
+}
+
+/// The cache key for a query to a provider, so "Paris,  france" and "paris, France" share an entry.
+pub fn cache_key(provider: ProviderKind, query: &LocationQuery) -> String {
+    let location = query.to_string().to_lowercase().split_whitespace().collect::<Vec<_>>().join(" ");
+    format!("{:?}:{}", provider, location)
+}
+
+/// Adds the cache key `query` was looked up under to a logged lookup, e.g. "    Query: WeatherApi:99801", so the history
+/// can stand in for the cache whatever kind of query it was. The key is hashed under `locations = "hash"` and left out
+/// under "redact". Add it after `redact_query`, which would otherwise redact the query inside the key.
+pub fn record_cache_key(config: &Config, text: &str, query: &LocationQuery) -> String {
+    match (logged_cache_key(config, query), text.strip_suffix("\n)")) {
+        (Some(key), Some(body)) => format!("{}\n    Query: {}\n)", body, key),
+        _ => text.to_string(),
+    }
+}
+
+/// The cache key of `query` as it is written to the use history, if it is.
+fn logged_cache_key(config: &Config, query: &LocationQuery) -> Option<String> {
+    match config.history.locations {
+        LocationPrivacy::Redact => None,
+        _ => Some(redact_location(&config.history, &cache_key(config.weather.provider, query))),
+    }
+}
+
+/// Current conditions at each of `queries`, in the same order, from the background daemon if it is running and otherwise
+/// reusing fresh cached observations unless `refresh` is set.
+/// Everything fetched is written back to the cache, which is shared by every run using the same cache file.
+/// When the provider can't be reached, or `weather.offline` is set, the last stored observation is returned
+/// instead, from the cache at any age or else from the use history, marked as stale.
+pub async fn get_cached_observations(config: &Config, queries: &[LocationQuery], refresh: bool) -> Vec<Result<CachedObservation, WeatherError>> {
+    if let Some(answered) = ask_daemon(config, queries, refresh).await {
+        return answered;
+    }
+    let cache_config = &config.cache;
+    let now = chrono::Utc::now().timestamp();
+    let ttl_secs = cache_config.ttl_minutes as i64 * 60;
+    let path = cache_config.cache_path();
+    let mut cache = match cache_config.enabled {
+        true => WeatherCache::load(&path),
+        false => WeatherCache::default(),
+    };
+
+    let keys: Vec<String> = queries.iter().map(|q| cache_key(config.weather.provider, q)).collect();
+    let cached: Vec<Option<CachedObservation>> = keys.iter()
+        .map(|k| if cache_config.enabled && !refresh { cache.get(k, ttl_secs, now) } else { None })
+        // Observations cached without air quality don't answer a lookup that wants it.
+        .map(|c| c.filter(|c| !wants_air_quality(&config.weather) || c.observation.air_quality.is_some()))
+        .collect();
+    let missing: Vec<LocationQuery> = queries.iter().zip(&cached).filter(|(_, c)| c.is_none()).map(|(q, _)| q.clone()).collect();
+    let mut fetched = get_observations(&config.weather, &missing).await.into_iter();
+
+    let mut history: Option<Vec<HistoryEntry>> = None;
+    let mut results = Vec::with_capacity(queries.len());
+    for ((key, hit), query) in keys.into_iter().zip(cached).zip(queries) {
+        if let Some(hit) = hit {
+            results.push(Ok(hit));
+            continue;
+        }
+        results.push(match fetched.next().expect("one result per missing query") {
+            Ok(observation) => {
+                cache.insert(key, observation.clone(), now);
+                Ok(CachedObservation { observation, fetched_epoch: now, source: ObservationSource::Live })
+            },
+            Err(e) if e.is_unreachable() => {
+                let entries = history.get_or_insert_with(|| read_entries(&config.history).unwrap_or_default());
+                cache.get_stale(&key).or_else(|| history_observation(config, entries, query)).ok_or(e)
+            },
+            Err(e) => Err(e),
+        });
+    }
+
+    if cache_config.enabled && !missing.is_empty() {
+        cache.prune(cache_config.keep_days as i64 * 86_400, now);
+        // A cache that can't be written only costs another request next time.
+        let _ = cache.save(&path);
+    }
+    results
+}
+
+fn wants_air_quality(config: &WeatherConfig) -> bool {
+    config.air_quality && config.provider == ProviderKind::WeatherApi
+}
+
+/// Current conditions at `query`, from the cache if it holds a fresh observation and `refresh` isn't set.
+/// Falls back on stored weather like `get_cached_observations`.
+pub async fn get_cached_observation(config: &Config, query: &LocationQuery, refresh: bool) -> Result<CachedObservation, WeatherError> {
+    let mut results = get_cached_observations(config, std::slice::from_ref(query), refresh).await;
+    results.remove(0)
+}
+
+/// How long ago something happened, e.g. "40 s ago", "3 min ago" or "2 h ago".
+pub fn format_age(secs: i64) -> String {
+    match secs.max(0) {
+        s if s < 60 => format!("{} s ago", s),
+        s if s < 3600 => format!("{} min ago", s / 60),
+        s => format!("{} h ago", s / 3600),
+    }
+}
+
+#[cfg(test)]
+fn observation(observed_epoch: i64) -> Observation {
+    Observation { observed_epoch, temp_c: 11.1, ..Observation::default() }
+}
+
+#[test]
+fn test_cache_freshness() {
+    let mut cache = WeatherCache::default();
+    cache.insert("k".to_string(), observation(1_000), 1_300);
+    assert!(cache.get("k", 900, 1_899).is_some_and(|c| c.source == ObservationSource::Cache));
+    // The observation was 300 s old when fetched, so it expires 900 s after the provider updated it.
+    assert!(cache.get("k", 900, 1_900).is_none());
+    assert!(cache.get("other", 900, 1_300).is_none());
+
+    cache.insert("unknown".to_string(), observation(0), 1_300);
+    assert!(cache.get("unknown", 900, 2_000).is_some());
+    cache.prune(900, 2_000);
+    assert_eq!(cache.entries.keys().collect::<Vec<_>>(), vec!["unknown"]);
+    assert!(cache.get_stale("unknown").is_some_and(|c| c.is_stale()));
+    assert!(cache.get_stale("k").is_none());
+}
+
+#[test]
+fn test_cache_round_trip() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("cache.json").to_string_lossy().to_string();
+    assert_eq!(WeatherCache::load(&path), WeatherCache::default());
+    let mut cache = WeatherCache::default();
+    cache.insert("k".to_string(), observation(1_000), 1_300);
+    cache.save(&path).unwrap();
+    assert_eq!(WeatherCache::load(&path), cache);
+    assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    fs::write(&path, "not json").unwrap();
+    assert_eq!(WeatherCache::load(&path), WeatherCache::default());
+}
+
+#[test]
+fn test_cache_key() {
+    let key = |s: &str| cache_key(ProviderKind::WeatherApi, &s.parse().unwrap());
+    assert_eq!(key("Paris,  france"), key("paris, France"));
+    assert_eq!(key("sw1a 1aa"), "WeatherApi:sw1a 1aa");
+    assert_ne!(key("20500"), cache_key(ProviderKind::Nws, &"20500".parse().unwrap()));
+}
+
+#[test]
+fn test_cache_note() {
+    let entry = CachedObservation { observation: observation(1_000), fetched_epoch: 1_000, source: ObservationSource::Cache };
+    assert_eq!(entry.cache_note(1_200).as_deref(), Some("from cache, fetched 3 min ago"));
+    assert_eq!(entry.age_label(1_200).as_deref(), Some("3 min ago"));
+    let stale = CachedObservation { source: ObservationSource::Stale, ..entry.clone() };
+    assert_eq!(
+        stale.cache_note(8_300).as_deref(),
+        Some("STALE: the weather service was not reached, showing the observation fetched 2 h ago"));
+    assert_eq!(stale.age_label(8_300).as_deref(), Some("STALE 2 h ago"));
+    assert_eq!(CachedObservation { source: ObservationSource::Live, ..entry }.cache_note(1_200), None);
+    assert_eq!(format_age(45), "45 s ago");
+    assert_eq!(format_age(7_300), "2 h ago");
+}
+
+#[test]
+fn test_history_observation() {
+    let at = |t: &str| chrono::DateTime::parse_from_rfc3339(t).unwrap();
+    let entries = vec![
+        HistoryEntry::from_text("Temperature retrieved (\n-= Retrieve temperature in Paris, Ile-de-France =-\n    Celsius: 18.2\n)", None, at("2024-08-14T09:00:00Z")),
+        HistoryEntry::from_text("Temperature retrieved (\n-= Retrieve temperature in Paris, Ile-de-France =-\n    Celsius: 21.5\n)", None, at("2024-08-14T10:00:00Z")),
+        HistoryEntry::from_text(
+            "Temperature retrieved (\n-= Retrieve temperature in Paris, Ile-de-France =-\n    (STALE: the weather service was not reached, showing the temperature recorded 1 h ago)\n    Celsius: 21.5\n)",
+            None,
+            at("2024-08-14T11:00:00Z")),
+        HistoryEntry::from_text("Temperature converted (\n    Celsius: 30\n)", None, at("2024-08-14T12:00:00Z")),
+    ];
+    let config = Config::default();
+    let found = history_observation(&config, &entries, &LocationQuery::city("paris, france").unwrap()).unwrap();
+    assert_eq!(found.source, ObservationSource::History);
+    assert_eq!((found.observation.place.name.as_str(), found.observation.place.region.as_str()), ("Paris", "Ile-de-France"));
+    assert_eq!(found.observation.temp_c, 21.5);
+    assert_eq!(found.fetched_epoch, at("2024-08-14T10:00:00Z").timestamp());
+    assert!(history_observation(&config, &entries, &LocationQuery::city("Lyon").unwrap()).is_none());
+    assert!(history_observation(&config, &entries, &"99801".parse().unwrap()).is_none());
+}
+
+#[test]
+fn test_history_observation_by_cache_key() {
+    use crate::config::HistoryConfig;
+    let zip: LocationQuery = "99801".parse().unwrap();
+    let text = "Temperature retrieved by ZIP code (\n-= Retrieve temperature in Juneau, Alaska =-\n    Celsius: 11.1\n)";
+    for locations in [LocationPrivacy::Keep, LocationPrivacy::Hash] {
+        let config = Config { history: HistoryConfig { locations, ..HistoryConfig::default() }, ..Config::default() };
+        let logged = crate::history::redact_text(&config.history, &record_cache_key(&config, text, &zip));
+        let entries = vec![HistoryEntry::from_text(&logged, None, chrono::Utc::now().fixed_offset())];
+        let found = history_observation(&config, &entries, &zip).unwrap();
+        assert_eq!(found.observation.temp_c, 11.1);
+        assert!(history_observation(&config, &entries, &"20500".parse().unwrap()).is_none());
+    }
+    let config = Config::default();
+    assert_eq!(record_cache_key(&config, text, &zip),
+        "Temperature retrieved by ZIP code (\n-= Retrieve temperature in Juneau, Alaska =-\n    Celsius: 11.1\n    Query: WeatherApi:99801\n)");
+    let redact = Config { history: HistoryConfig { locations: LocationPrivacy::Redact, ..HistoryConfig::default() }, ..Config::default() };
+    assert_eq!(record_cache_key(&redact, text, &zip), text);
+}
@@ -0,0 +1,125 @@
+use chrono_tz::Tz;
+
+use crate::{format_user_time, format_zone_time, from_celsius, Scale};
+
+use super::Observation;
+
+/// The observation as label and value pairs, with every temperature shown in each of `scales`.
+/// Fields the provider didn't report are left out.
+pub fn detail_rows(obs: &Observation, scales: &[Scale]) -> Vec<(&'static str, String)> {
+    let temp = |celsius: f32| format_temp(celsius, scales);
+    let mut rows = vec![
+        ("Condition", format!("{} ({})", obs.condition, if obs.is_day { "day" } else { "night" })),
+        ("Temperature", temp(obs.temp_c)),
+    ];
+    let mut push = |label: &'static str, value: Option<String>| {
+        if let Some(v) = value {
+            rows.push((label, v));
+        }
+    };
+    push("Feels like", obs.feelslike_c.map(temp));
+    push("Wind chill", obs.windchill_c.map(temp));
+    push("Heat index", obs.heatindex_c.map(temp));
+    push("Dew point", obs.dewpoint_c.map(temp));
+    push("Humidity", obs.humidity.map(|h| format!("{}%", round(h))));
+    push("Wind", obs.wind_kph.map(|w| match obs.wind_degree {
+        Some(d) => format!("{} km/h from {} ({}°)", round(w), compass(d), round(d)),
+        None => format!("{} km/h", round(w)),
+    }));
+    push("Gusts", obs.gust_kph.map(|g| format!("{} km/h", round(g))));
+    push("Pressure", obs.pressure_mb.map(|p| format!("{} mb", round(p))));
+    push("Precipitation", obs.precip_mm.map(|p| format!("{} mm", round(p))));
+    push("Cloud cover", obs.cloud.map(|c| format!("{}%", round(c))));
+    push("UV index", obs.uv.map(|u| round(u).to_string()));
+    push("Visibility", obs.vis_km.map(|v| format!("{} km", round(v))));
+    rows
+}
+
+/// When the observation was made, both at the place and where the user is,
+/// e.g. "2024-08-14 08:55 AKDT (2024-08-14 12:55 EDT your time)". `None` if the provider gave no time.
+pub fn format_observed_time(obs: &Observation, user: Option<Tz>) -> Option<String> {
+    if obs.observed_epoch == 0 {
+        return None;
+    }
+    let yours = format_user_time(obs.observed_epoch, user)?;
+    let there = match (obs.observed_at(), obs.local_time) {
+        (Some(at), _) => format_zone_time(&at),
+        (None, Some(local)) => format!("{} local time", local.format("%Y-%m-%d %H:%M")),
+        (None, None) => return Some(format!("{} your time", yours)),
+    };
+    Some(format!("{} ({} your time)", there, yours))
+}
+
+/// Shows a Celsius temperature in each of `scales`, e.g. "11.1°C / 52°F".
+pub fn format_temp(celsius: f32, scales: &[Scale]) -> String {
+    scales.iter()
+        .map(|s| format!("{}{}", round(from_celsius(celsius, *s)), s.symbol()))
+        .collect::<Vec<_>>()
+        .join(" / ")
+}
+
+/// Renders the detail rows the way the CLI prints them, one indented field per line.
+pub fn format_details(obs: &Observation, scales: &[Scale]) -> String {
+    detail_rows(obs, scales).iter()
+        .map(|(label, value)| format!("    {}: {}", label, value))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Names the 16-point compass direction of a bearing in degrees, e.g. "SSE" for 150.
+pub fn compass(degree: f32) -> &'static str {
+    const POINTS: [&str; 16] = [
+        "N", "NNE", "NE", "ENE", "E", "ESE", "SE", "SSE", "S", "SSW", "SW", "WSW", "W", "WNW", "NW", "NNW",
+    ];
+    POINTS[((degree.rem_euclid(360.0) / 22.5).round() as usize) % 16]
+}
+
+pub(crate) fn round(value: f32) -> f32 {
+    (value * 10.0).round() / 10.0
+}
+
+#[test]
+fn test_format_observed_time() {
+    use super::Place;
+    let place = Place { tz_id: "America/Juneau".to_string(), ..Place::default() };
+    let obs = Observation { place, observed_epoch: 1723654500, ..Observation::default() };
+    assert_eq!(
+        format_observed_time(&obs, crate::parse_tz("America/New_York")).unwrap(),
+        "2024-08-14 08:55 AKDT (2024-08-14 12:55 EDT your time)");
+    let unnamed = Observation { place: Place::default(), local_time: obs.observed_at().map(|t| t.naive_local()), ..obs.clone() };
+    assert_eq!(format_observed_time(&unnamed, Some(Tz::UTC)).unwrap(), "2024-08-14 08:55 local time (2024-08-14 16:55 UTC your time)");
+    assert_eq!(format_observed_time(&Observation::default(), Some(Tz::UTC)), None);
+}
+
+#[test]
+fn test_compass() {
+    assert_eq!(compass(0.0), "N");
+    assert_eq!(compass(150.0), "SSE");
+    assert_eq!(compass(350.0), "N");
+    assert_eq!(compass(-90.0), "W");
+}
+
+#[test]
+fn test_detail_rows() {
+    let obs = Observation {
+        temp_c: 11.1,
+        condition: "Overcast".to_string(),
+        is_day: true,
+        feelslike_c: Some(10.6),
+        dewpoint_c: Some(9.0),
+        wind_kph: Some(6.1),
+        wind_degree: Some(150.0),
+        humidity: Some(87.0),
+        ..Observation::default()
+    };
+    let rows = detail_rows(&obs, &[Scale::Celsius, Scale::Fahrenheit]);
+    assert_eq!(rows, vec![
+        ("Condition", "Overcast (day)".to_string()),
+        ("Temperature", "11.1°C / 52°F".to_string()),
+        ("Feels like", "10.6°C / 51.1°F".to_string()),
+        ("Dew point", "9°C / 48.2°F".to_string()),
+        ("Humidity", "87%".to_string()),
+        ("Wind", "6.1 km/h from SSE (150°)".to_string()),
+    ]);
+    assert_eq!(format_details(&obs, &[Scale::Kelvin]).lines().nth(2), Some("    Feels like: 283.8K"));
+}
@@ -0,0 +1,138 @@
+use chrono::{NaiveDate, NaiveDateTime};
+
+use serde::{Serialize, Deserialize};
+
+use crate::Scale;
+
+use super::details::{format_temp, round};
+use super::Place;
+
+/// The coming days at a place, whichever provider they came from.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Forecast {
+    pub place: Place,
+    pub days: Vec<DailyForecast>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DailyForecast {
+    /// The day in the place's own time zone.
+    pub date: NaiveDate,
+    pub min_c: f32,
+    pub max_c: f32,
+    pub avg_c: f32,
+    /// The highest chance of rain or snow during the day, in percent.
+    pub precip_chance: Option<f32>,
+    pub condition: String,
+    pub hours: Vec<HourlyForecast>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HourlyForecast {
+    pub time_epoch: i64,
+    /// The hour in the place's own time zone.
+    pub local_time: NaiveDateTime,
+    pub temp_c: f32,
+    pub precip_chance: Option<f32>,
+    pub condition: String,
+}
+
+/// Groups hourly forecasts into days, for providers that only forecast by the hour.
+/// The day's condition is the one forecast for the most hours.
+pub fn days_from_hours(hours: Vec<HourlyForecast>) -> Vec<DailyForecast> {
+    let mut days: Vec<DailyForecast> = Vec::new();
+    for hour in hours {
+        let date = hour.local_time.date();
+        match days.last_mut() {
+            Some(day) if day.date == date => day.hours.push(hour),
+            _ => days.push(DailyForecast { date, hours: vec![hour], ..DailyForecast::default() }),
+        }
+    }
+    for day in &mut days {
+        let temps = day.hours.iter().map(|h| h.temp_c);
+        day.min_c = temps.clone().fold(f32::MAX, f32::min);
+        day.max_c = temps.clone().fold(f32::MIN, f32::max);
+        day.avg_c = temps.sum::<f32>() / day.hours.len() as f32;
+        day.precip_chance = day.hours.iter().filter_map(|h| h.precip_chance).reduce(f32::max);
+        day.condition = most_common(day.hours.iter().map(|h| h.condition.as_str()));
+    }
+    days
+}
+
+/// The condition that occurs most often, the earliest one on a tie.
+pub(crate) fn most_common<'a>(conditions: impl Iterator<Item = &'a str>) -> String {
+    let mut counts: Vec<(&str, usize)> = Vec::new();
+    for condition in conditions {
+        match counts.iter_mut().find(|c| c.0 == condition) {
+            Some(c) => c.1 += 1,
+            None => counts.push((condition, 1)),
+        }
+    }
+    // max_by_key keeps the last of equal counts, so search from the back to prefer the earliest.
+    counts.iter().rev().max_by_key(|c| c.1).map(|c| c.0.to_string()).unwrap_or_default()
+}
+
+/// Renders a forecast with every temperature shown in each of `scales`, optionally hour by hour.
+pub fn format_forecast(forecast: &Forecast, scales: &[Scale], hourly: bool) -> String {
+    let temp = |celsius: f32| format_temp(celsius, scales);
+    let chance = |c: Option<f32>| c.map(|c| format!(", {}% chance of precipitation", round(c))).unwrap_or_default();
+
+    let mut out = format!("-= Forecast for {}, {} =-", forecast.place.name, forecast.place.region);
+    for day in &forecast.days {
+        out += &format!(
+            "\n    {}: {}{}\n        min {}, max {}, avg {}",
+            day.date.format("%a %Y-%m-%d"), day.condition, chance(day.precip_chance),
+            temp(day.min_c), temp(day.max_c), temp(day.avg_c));
+        if hourly {
+            for hour in &day.hours {
+                out += &format!(
+                    "\n        {}  {}  {}{}",
+                    hour.local_time.format("%H:%M"), temp(hour.temp_c), hour.condition, chance(hour.precip_chance));
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+fn hour(time: &str, temp_c: f32, precip_chance: Option<f32>, condition: &str) -> HourlyForecast {
+    HourlyForecast {
+        local_time: NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M").unwrap(),
+        temp_c,
+        precip_chance,
+        condition: condition.to_string(),
+        ..HourlyForecast::default()
+    }
+}
+
+#[test]
+fn test_days_from_hours() {
+    let days = days_from_hours(vec![
+        hour("2024-08-14 22:00", 10.0, Some(10.0), "Clear"),
+        hour("2024-08-14 23:00", 8.0, Some(30.0), "Cloudy"),
+        hour("2024-08-15 00:00", 7.0, None, "Rain"),
+        hour("2024-08-15 01:00", 6.0, None, "Rain"),
+        hour("2024-08-15 02:00", 8.0, None, "Cloudy"),
+    ]);
+    assert_eq!(days.len(), 2);
+    assert_eq!(days[0].date, NaiveDate::from_ymd_opt(2024, 8, 14).unwrap());
+    assert_eq!((days[0].min_c, days[0].max_c, days[0].avg_c), (8.0, 10.0, 9.0));
+    assert_eq!(days[0].precip_chance, Some(30.0));
+    assert_eq!(days[0].condition, "Clear");
+    assert_eq!((days[1].min_c, days[1].max_c, days[1].avg_c), (6.0, 8.0, 7.0));
+    assert_eq!(days[1].precip_chance, None);
+    assert_eq!(days[1].condition, "Rain");
+    assert_eq!(days[1].hours.len(), 3);
+}
+
+#[test]
+fn test_format_forecast() {
+    let forecast = Forecast {
+        place: Place { name: "Juneau".to_string(), region: "Alaska".to_string(), ..Place::default() },
+        days: days_from_hours(vec![hour("2024-08-14 22:00", 10.0, Some(40.0), "Light rain")]),
+    };
+    assert_eq!(
+        format_forecast(&forecast, &[Scale::Celsius, Scale::Fahrenheit], true),
+        "-= Forecast for Juneau, Alaska =-\n    Wed 2024-08-14: Light rain, 40% chance of precipitation\n        \
+        min 10°C / 50°F, max 10°C / 50°F, avg 10°C / 50°F\n        22:00  10°C / 50°F  Light rain, 40% chance of precipitation");
+}
@@ -0,0 +1,142 @@
+use chrono::{NaiveDate, NaiveDateTime};
+
+use serde::{Serialize, Deserialize};
+
+use crate::Scale;
+
+use super::details::{format_temp, round};
+use super::forecast::most_common;
+use super::Place;
+
+/// Recorded weather at a place over one or more past days, whichever provider it came from.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WeatherHistory {
+    pub place: Place,
+    pub days: Vec<HistoricalDay>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoricalDay {
+    /// The day in the place's own time zone.
+    pub date: NaiveDate,
+    pub min_c: f32,
+    pub max_c: f32,
+    pub avg_c: f32,
+    /// Total precipitation over the day.
+    pub precip_mm: Option<f32>,
+    pub condition: String,
+    pub hours: Vec<HistoricalHour>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoricalHour {
+    pub time_epoch: i64,
+    /// The hour in the place's own time zone.
+    pub local_time: NaiveDateTime,
+    pub temp_c: f32,
+    pub precip_mm: Option<f32>,
+    /// Relative humidity in percent.
+    pub humidity: Option<f32>,
+    pub wind_kph: Option<f32>,
+    pub condition: String,
+}
+
+/// Groups hourly records into days, for providers that only report by the hour.
+pub fn historical_days(hours: Vec<HistoricalHour>) -> Vec<HistoricalDay> {
+    let mut days: Vec<HistoricalDay> = Vec::new();
+    for hour in hours {
+        let date = hour.local_time.date();
+        match days.last_mut() {
+            Some(day) if day.date == date => day.hours.push(hour),
+            _ => days.push(HistoricalDay { date, hours: vec![hour], ..HistoricalDay::default() }),
+        }
+    }
+    for day in &mut days {
+        let temps = day.hours.iter().map(|h| h.temp_c);
+        day.min_c = temps.clone().fold(f32::MAX, f32::min);
+        day.max_c = temps.clone().fold(f32::MIN, f32::max);
+        day.avg_c = temps.sum::<f32>() / day.hours.len() as f32;
+        day.precip_mm = day.hours.iter().filter_map(|h| h.precip_mm).reduce(|a, b| a + b);
+        day.condition = most_common(day.hours.iter().map(|h| h.condition.as_str()));
+    }
+    days
+}
+
+/// Checks a `from..=to` range can be looked up: in order and not in the future.
+pub fn check_date_range(from: NaiveDate, to: NaiveDate, today: NaiveDate) -> Result<(), String> {
+    if from > to {
+        Err(format!("the start date {} is after the end date {}", from, to))
+    } else if to > today {
+        Err(format!("{} is in the future, use a forecast instead", to))
+    } else {
+        Ok(())
+    }
+}
+
+/// Renders past weather with every temperature shown in each of `scales`, optionally hour by hour.
+pub fn format_weather_history(history: &WeatherHistory, scales: &[Scale], hourly: bool) -> String {
+    let temp = |celsius: f32| format_temp(celsius, scales);
+    let precip = |mm: Option<f32>| mm.map(|mm| format!(", {} mm precipitation", round(mm))).unwrap_or_default();
+
+    let mut out = format!("-= Weather history for {}, {} =-", history.place.name, history.place.region);
+    for day in &history.days {
+        out += &format!(
+            "\n    {}: {}{}\n        min {}, max {}, avg {}",
+            day.date.format("%a %Y-%m-%d"), day.condition, precip(day.precip_mm),
+            temp(day.min_c), temp(day.max_c), temp(day.avg_c));
+        if hourly {
+            for hour in &day.hours {
+                out += &format!(
+                    "\n        {}  {}  {}{}",
+                    hour.local_time.format("%H:%M"), temp(hour.temp_c), hour.condition, precip(hour.precip_mm));
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+fn hour(time: &str, temp_c: f32, precip_mm: Option<f32>, condition: &str) -> HistoricalHour {
+    HistoricalHour {
+        local_time: NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M").unwrap(),
+        temp_c,
+        precip_mm,
+        condition: condition.to_string(),
+        ..HistoricalHour::default()
+    }
+}
+
+#[test]
+fn test_historical_days() {
+    let days = historical_days(vec![
+        hour("2024-08-01 00:00", 10.0, Some(0.5), "Rain"),
+        hour("2024-08-01 12:00", 16.0, Some(1.5), "Rain"),
+        hour("2024-08-02 00:00", 9.0, None, "Clear"),
+    ]);
+    assert_eq!(days.len(), 2);
+    assert_eq!((days[0].min_c, days[0].max_c, days[0].avg_c), (10.0, 16.0, 13.0));
+    assert_eq!(days[0].precip_mm, Some(2.0));
+    assert_eq!(days[0].condition, "Rain");
+    assert_eq!(days[1].precip_mm, None);
+}
+
+#[test]
+fn test_check_date_range() {
+    let day = |d: u32| NaiveDate::from_ymd_opt(2024, 8, d).unwrap();
+    assert!(check_date_range(day(1), day(3), day(14)).is_ok());
+    assert!(check_date_range(day(14), day(14), day(14)).is_ok());
+    assert_eq!(check_date_range(day(3), day(1), day(14)), Err("the start date 2024-08-03 is after the end date 2024-08-01".to_string()));
+    assert!(check_date_range(day(10), day(15), day(14)).is_err());
+}
+
+#[test]
+fn test_format_weather_history() {
+    let history = WeatherHistory {
+        place: Place { name: "Juneau".to_string(), region: "Alaska".to_string(), ..Place::default() },
+        days: historical_days(vec![hour("2024-08-01 12:00", 16.0, Some(1.5), "Rain")]),
+    };
+    assert_eq!(
+        format_weather_history(&history, &[Scale::Kelvin], true),
+        "-= Weather history for Juneau, Alaska =-\n    Thu 2024-08-01: Rain, 1.5 mm precipitation\n        \
+        min 289.2K, max 289.2K, avg 289.2K\n        12:00  289.2K  Rain, 1.5 mm precipitation");
+}
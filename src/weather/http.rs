@@ -0,0 +1,181 @@
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+
+use reqwest::header::RETRY_AFTER;
+use reqwest::{RequestBuilder, Response, StatusCode, Url};
+
+use serde::de::DeserializeOwned;
+
+use tokio::time::Instant;
+
+use crate::config::HttpConfig;
+
+use super::{read_json, WeatherError};
+
+/// The `reqwest::Client` every provider sends through, with the configured timeouts, retries and rate limit.
+/// Clones share the same connections and rate limit.
+#[derive(Debug, Clone)]
+pub struct HttpClient {
+    client: reqwest::Client,
+    config: HttpConfig,
+    limiter: Arc<RateLimiter>,
+}
+
+impl HttpClient {
+    /// Fails if the client can't be set up, e.g. the system's TLS certificates can't be loaded.
+    pub fn new(config: &HttpConfig) -> Result<HttpClient, WeatherError> {
+        let client = reqwest::Client::builder()
+            .connect_timeout(Duration::from_secs(config.connect_timeout_secs))
+            .read_timeout(Duration::from_secs(config.read_timeout_secs))
+            .build()
+            .map_err(|e| WeatherError::Client(e.to_string()))?;
+        Ok(HttpClient { client, config: config.clone(), limiter: Arc::new(RateLimiter::new(config.requests_per_second)) })
+    }
+
+    /// The client for `config`, kept for the life of the process so lookups reuse connections
+    /// and share one rate limit. A different config replaces it.
+    pub fn shared(config: &HttpConfig) -> Result<HttpClient, WeatherError> {
+        static SHARED: Mutex<Option<HttpClient>> = Mutex::new(None);
+        let mut shared = SHARED.lock().unwrap_or_else(|e| e.into_inner());
+        match shared.as_ref().filter(|c| c.config == *config) {
+            Some(client) => Ok(client.clone()),
+            None => Ok(shared.insert(HttpClient::new(config)?).clone()),
+        }
+    }
+
+    pub fn get(&self, url: Url) -> RequestBuilder {
+        self.client.get(url)
+    }
+
+    pub fn post(&self, url: Url) -> RequestBuilder {
+        self.client.post(url)
+    }
+
+    /// Sends a GET request and reads its JSON body, see `send`.
+    pub async fn get_json<T: DeserializeOwned>(&self, url: Url, query: &str) -> Result<T, WeatherError> {
+        read_json(self.send(self.get(url)).await?, query).await
+    }
+
+    /// Sends `request` once the rate limit allows, trying again after timeouts, dropped connections,
+    /// HTTP 429 and 5xx gateway errors. Waits as long as `Retry-After` asks, or backs off exponentially.
+    /// The last error response is returned as is once the retries run out.
+    pub async fn send(&self, request: RequestBuilder) -> Result<Response, WeatherError> {
+        let mut attempt = 0;
+        loop {
+            self.limiter.wait().await;
+            let this = request.try_clone().expect("requests without a streamed body can be cloned");
+            let retry = attempt < self.config.retries;
+            let delay = match this.send().await {
+                Ok(resp) if !retry || !is_transient(resp.status()) => return Ok(resp),
+                Ok(resp) => {
+                    let asked = resp.headers().get(RETRY_AFTER)
+                        .and_then(|v| v.to_str().ok())
+                        .and_then(|v| retry_after(v, Utc::now()));
+                    match asked {
+                        Some(d) if d > Duration::from_millis(self.config.max_backoff_ms) => return Ok(resp),
+                        Some(d) => d,
+                        None => backoff_delay(&self.config, attempt, random_fraction()),
+                    }
+                },
+                Err(e) => match WeatherError::from(e) {
+                    e if retry && e.is_unreachable() => backoff_delay(&self.config, attempt, random_fraction()),
+                    e => return Err(e),
+                },
+            };
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+}
+
+/// Spaces requests out evenly to at most `per_second`.
+#[derive(Debug)]
+pub struct RateLimiter {
+    interval: Duration,
+    next: Mutex<Option<Instant>>,
+}
+
+impl RateLimiter {
+    /// No limit if `per_second` isn't positive. A rate so low its interval can't be represented waits a day between requests.
+    pub fn new(per_second: f64) -> RateLimiter {
+        const MAX_INTERVAL: Duration = Duration::from_secs(86_400);
+        let interval = match per_second > 0.0 {
+            true => Duration::try_from_secs_f64(1.0 / per_second).map_or(MAX_INTERVAL, |i| i.min(MAX_INTERVAL)),
+            false => Duration::ZERO,
+        };
+        RateLimiter { interval, next: Mutex::new(None) }
+    }
+
+    /// Waits for the next free slot.
+    pub async fn wait(&self) {
+        if self.interval.is_zero() {
+            return;
+        }
+        let slot = {
+            let mut next = self.next.lock().unwrap_or_else(|e| e.into_inner());
+            let slot = next.map_or(Instant::now(), |n| n.max(Instant::now()));
+            *next = Some(slot + self.interval);
+            slot
+        };
+        tokio::time::sleep_until(slot).await;
+    }
+}
+
+/// Statuses worth trying again: rate limiting and an overloaded or restarting service.
+fn is_transient(status: StatusCode) -> bool {
+    matches!(status.as_u16(), 429 | 500 | 502 | 503 | 504)
+}
+
+/// The wait before retry number `attempt + 1`: `backoff_ms` doubled for each earlier retry, capped at `max_backoff_ms`,
+/// less up to half of it depending on `jitter` (0 to 1) so clients that failed together don't retry together.
+pub fn backoff_delay(config: &HttpConfig, attempt: u32, jitter: f64) -> Duration {
+    let full = config.backoff_ms.saturating_mul(1 << attempt.min(32)).min(config.max_backoff_ms);
+    Duration::from_millis(full - (full as f64 * jitter.clamp(0.0, 1.0) / 2.0) as u64)
+}
+
+/// Reads a `Retry-After` header, either a number of seconds or an HTTP date.
+pub fn retry_after(value: &str, now: DateTime<Utc>) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let at = DateTime::parse_from_rfc2822(value).ok()?;
+    Some((at.with_timezone(&Utc) - now).to_std().unwrap_or(Duration::ZERO))
+}
+
+/// A number from 0 to 1 that differs from call to call, random enough for jitter.
+fn random_fraction() -> f64 {
+    RandomState::new().hash_one(Instant::now()) as f64 / u64::MAX as f64
+}
+
+#[test]
+fn test_backoff_delay() {
+    let config = HttpConfig { backoff_ms: 500, max_backoff_ms: 3_000, ..HttpConfig::default() };
+    assert_eq!(backoff_delay(&config, 0, 0.0), Duration::from_millis(500));
+    assert_eq!(backoff_delay(&config, 2, 0.0), Duration::from_millis(2_000));
+    assert_eq!(backoff_delay(&config, 2, 1.0), Duration::from_millis(1_000));
+    assert_eq!(backoff_delay(&config, 5, 0.0), Duration::from_millis(3_000));
+    assert_eq!(backoff_delay(&config, 40, 0.5), Duration::from_millis(2_250));
+}
+
+#[test]
+fn test_rate_limiter_interval() {
+    assert_eq!(RateLimiter::new(4.0).interval, Duration::from_millis(250));
+    assert_eq!(RateLimiter::new(0.0).interval, Duration::ZERO);
+    assert_eq!(RateLimiter::new(-1.0).interval, Duration::ZERO);
+    assert_eq!(RateLimiter::new(1e-300).interval, Duration::from_secs(86_400));
+    assert_eq!(RateLimiter::new(1e-6).interval, Duration::from_secs(86_400));
+}
+
+#[test]
+fn test_retry_after() {
+    let now = DateTime::parse_from_rfc3339("2024-08-14T08:00:00Z").unwrap().with_timezone(&Utc);
+    assert_eq!(retry_after("120", now), Some(Duration::from_secs(120)));
+    assert_eq!(retry_after("Wed, 14 Aug 2024 08:00:30 GMT", now), Some(Duration::from_secs(30)));
+    assert_eq!(retry_after("Wed, 14 Aug 2024 07:59:00 GMT", now), Some(Duration::ZERO));
+    assert_eq!(retry_after("soon", now), None);
+}
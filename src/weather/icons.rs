@@ -0,0 +1,126 @@
+use serde::{Serialize, Deserialize};
+
+/// The kinds of weather the bundled icons show, which every provider's condition codes map onto.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ConditionIcon {
+    Clear,
+    PartlyCloudy,
+    Cloudy,
+    Fog,
+    Drizzle,
+    Rain,
+    Sleet,
+    Snow,
+    Thunder,
+}
+
+impl ConditionIcon {
+    /// WeatherAPI's condition codes, e.g. 1009 for overcast.
+    pub fn from_weatherapi_code(code: i64) -> Option<ConditionIcon> {
+        Some(match code {
+            1000 => ConditionIcon::Clear,
+            1003 => ConditionIcon::PartlyCloudy,
+            1006 | 1009 => ConditionIcon::Cloudy,
+            1030 | 1135 | 1147 => ConditionIcon::Fog,
+            1072 | 1150 | 1153 | 1168 | 1171 => ConditionIcon::Drizzle,
+            1063 | 1180..=1201 | 1240..=1246 => ConditionIcon::Rain,
+            1069 | 1204..=1207 | 1237 | 1249 | 1252 | 1261 | 1264 => ConditionIcon::Sleet,
+            1066 | 1114 | 1117 | 1210..=1225 | 1255 | 1258 => ConditionIcon::Snow,
+            1087 | 1273..=1282 => ConditionIcon::Thunder,
+            _ => return None,
+        })
+    }
+
+    /// The WMO weather interpretation codes Open-Meteo reports.
+    pub fn from_wmo_code(code: i64) -> Option<ConditionIcon> {
+        Some(match code {
+            0 | 1 => ConditionIcon::Clear,
+            2 => ConditionIcon::PartlyCloudy,
+            3 => ConditionIcon::Cloudy,
+            45 | 48 => ConditionIcon::Fog,
+            51..=55 => ConditionIcon::Drizzle,
+            61..=65 | 80..=82 => ConditionIcon::Rain,
+            56 | 57 | 66 | 67 => ConditionIcon::Sleet,
+            71..=77 | 85 | 86 => ConditionIcon::Snow,
+            95..=99 => ConditionIcon::Thunder,
+            _ => return None,
+        })
+    }
+
+    /// The NWS icon URL's condition, e.g. "ovc" in "https://api.weather.gov/icons/land/day/ovc?size=medium".
+    /// Where two conditions are shown the first is used.
+    pub fn from_nws_icon(url: &str) -> Option<ConditionIcon> {
+        let path = url.split('?').next()?;
+        let (_, after) = path.split_once("/day/").or_else(|| path.split_once("/night/"))?;
+        let name = after.split(['/', ',']).next()?;
+        Some(match name.trim_start_matches("wind_") {
+            "skc" | "few" | "hot" | "cold" | "wind" => ConditionIcon::Clear,
+            "sct" => ConditionIcon::PartlyCloudy,
+            "bkn" | "ovc" => ConditionIcon::Cloudy,
+            "fog" | "haze" | "smoke" | "dust" => ConditionIcon::Fog,
+            "rain" | "rain_showers" | "rain_showers_hi" => ConditionIcon::Rain,
+            "rain_snow" | "rain_sleet" | "snow_sleet" | "sleet" | "fzra" | "rain_fzra" | "snow_fzra" => ConditionIcon::Sleet,
+            "snow" | "blizzard" => ConditionIcon::Snow,
+            "tsra" | "tsra_sct" | "tsra_hi" | "tornado" | "hurricane" | "tropical_storm" => ConditionIcon::Thunder,
+            _ => return None,
+        })
+    }
+}
+
+/// A bundled PNG icon, with the URI egui caches it under.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IconImage {
+    pub uri: &'static str,
+    pub bytes: &'static [u8],
+}
+
+macro_rules! icon {
+    ($name:literal) => {
+        IconImage { uri: concat!("bytes://icons/", $name, ".png"), bytes: include_bytes!(concat!("../../assets/icons/", $name, ".png")) }
+    };
+}
+
+/// The bundled icon for `icon`, with the moon instead of the sun at night. Nothing is fetched.
+pub fn icon_image(icon: ConditionIcon, is_day: bool) -> IconImage {
+    match (icon, is_day) {
+        (ConditionIcon::Clear, true) => icon!("clear-day"),
+        (ConditionIcon::Clear, false) => icon!("clear-night"),
+        (ConditionIcon::PartlyCloudy, true) => icon!("partly-cloudy-day"),
+        (ConditionIcon::PartlyCloudy, false) => icon!("partly-cloudy-night"),
+        (ConditionIcon::Cloudy, _) => icon!("cloudy"),
+        (ConditionIcon::Fog, _) => icon!("fog"),
+        (ConditionIcon::Drizzle, _) => icon!("drizzle"),
+        (ConditionIcon::Rain, _) => icon!("rain"),
+        (ConditionIcon::Sleet, _) => icon!("sleet"),
+        (ConditionIcon::Snow, _) => icon!("snow"),
+        (ConditionIcon::Thunder, _) => icon!("thunder"),
+    }
+}
+
+#[test]
+fn test_condition_codes() {
+    assert_eq!(ConditionIcon::from_weatherapi_code(1009), Some(ConditionIcon::Cloudy));
+    assert_eq!(ConditionIcon::from_weatherapi_code(1276), Some(ConditionIcon::Thunder));
+    assert_eq!(ConditionIcon::from_weatherapi_code(42), None);
+    assert_eq!(ConditionIcon::from_wmo_code(2), Some(ConditionIcon::PartlyCloudy));
+    assert_eq!(ConditionIcon::from_wmo_code(66), Some(ConditionIcon::Sleet));
+    assert_eq!(ConditionIcon::from_nws_icon("https://api.weather.gov/icons/land/day/ovc?size=medium"), Some(ConditionIcon::Cloudy));
+    assert_eq!(ConditionIcon::from_nws_icon("https://api.weather.gov/icons/land/night/tsra_sct,40/rain?size=small"), Some(ConditionIcon::Thunder));
+    assert_eq!(ConditionIcon::from_nws_icon("https://api.weather.gov/icons/land/day/wind_bkn"), Some(ConditionIcon::Cloudy));
+    assert_eq!(ConditionIcon::from_nws_icon("not an icon"), None);
+}
+
+#[test]
+fn test_icon_images() {
+    let all = [
+        ConditionIcon::Clear, ConditionIcon::PartlyCloudy, ConditionIcon::Cloudy, ConditionIcon::Fog, ConditionIcon::Drizzle,
+        ConditionIcon::Rain, ConditionIcon::Sleet, ConditionIcon::Snow, ConditionIcon::Thunder,
+    ];
+    for icon in all {
+        for is_day in [true, false] {
+            assert!(icon_image(icon, is_day).bytes.starts_with(b"\x89PNG\r\n\x1a\n"), "{:?}", icon);
+        }
+    }
+    assert_eq!(icon_image(ConditionIcon::Clear, false).uri, "bytes://icons/clear-night.png");
+    assert_eq!(icon_image(ConditionIcon::Rain, true), icon_image(ConditionIcon::Rain, false));
+}
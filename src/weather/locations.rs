@@ -0,0 +1,130 @@
+use std::sync::Arc;
+
+use chrono::DateTime;
+
+use tokio::sync::Semaphore;
+
+use crate::config::WeatherConfig;
+use crate::Scale;
+
+use super::details::format_temp;
+use super::{provider_from_config, LocationQuery, Observation, Place, WeatherError, WeatherProvider};
+
+/// One row of a multi-location lookup: the location as it was entered and what was found there.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LocationRow {
+    pub location: String,
+    pub observation: Result<Observation, String>,
+    /// How old the observation is if it came from the cache, e.g. "3 min ago".
+    pub cached: Option<String>,
+}
+
+/// Current conditions at each of `queries`, in the same order, with at most `config.concurrency` requests in flight.
+/// One location failing doesn't stop the others. Must be called from within a tokio runtime.
+pub async fn get_observations(config: &WeatherConfig, queries: &[LocationQuery]) -> Vec<Result<Observation, WeatherError>> {
+    let provider: Arc<dyn WeatherProvider> = match provider_from_config(config) {
+        Ok(p) => Arc::from(p),
+        Err(e) => return queries.iter().map(|_| Err(e.clone())).collect(),
+    };
+    let permits = Arc::new(Semaphore::new(config.concurrency.max(1)));
+    let tasks: Vec<_> = queries.iter().cloned().map(|query| {
+        let (provider, permits) = (provider.clone(), permits.clone());
+        tokio::spawn(async move {
+            // The semaphore is never closed, so acquiring only waits.
+            let _permit = permits.acquire_owned().await;
+            provider.current(&query).await
+        })
+    }).collect();
+
+    let mut results = Vec::with_capacity(tasks.len());
+    for task in tasks {
+        results.push(task.await.unwrap_or_else(|e| Err(WeatherError::Transport(e.to_string()))));
+    }
+    results
+}
+
+/// Where an observation was made, e.g. "Juneau, Alaska".
+pub fn place_name(place: &Place) -> String {
+    match place.region.is_empty() {
+        true => place.name.to_string(),
+        false => format!("{}, {}", place.name, place.region),
+    }
+}
+
+/// When an observation was made in the place's own time, or in UTC if the provider didn't say.
+pub fn observed_time(obs: &Observation) -> String {
+    match obs.local_time {
+        Some(t) => t.format("%Y-%m-%d %H:%M").to_string(),
+        None => DateTime::from_timestamp(obs.observed_epoch, 0)
+            .map(|t| t.format("%Y-%m-%d %H:%M UTC").to_string())
+            .unwrap_or_default(),
+    }
+}
+
+/// Renders one line per location with its local time, condition, temperature in each of `scales` and, for
+/// cached results, their age. Locations that failed show why in place of the readings.
+pub fn format_location_table(rows: &[LocationRow], scales: &[Scale]) -> String {
+    let cells: Vec<[String; 5]> = rows.iter().map(|row| match &row.observation {
+        Ok(obs) => [
+            place_name(&obs.place),
+            observed_time(obs),
+            obs.condition.to_string(),
+            format_temp(obs.temp_c, scales),
+            row.cached.clone().unwrap_or_default(),
+        ],
+        Err(e) => [row.location.to_string(), "-".to_string(), format!("Error: {}", e), "".to_string(), "".to_string()],
+    }).collect();
+    let header = ["Location", "Local time", "Condition", "Temperature", "Cached"].map(String::from);
+    let widths: Vec<usize> = (0..5)
+        .map(|i| cells.iter().chain([&header]).map(|c| c[i].chars().count()).max().unwrap_or(0))
+        .collect();
+
+    let line = |c: &[String; 5]| {
+        let padded: Vec<String> = c.iter().zip(&widths).map(|(cell, w)| format!("{:<w$}", cell, w = w)).collect();
+        format!("    {}", padded.join("  ").trim_end())
+    };
+    let mut out = format!("-= Weather at {} locations =-\n{}", rows.len(), line(&header));
+    for c in &cells {
+        out += &format!("\n{}", line(c));
+    }
+    out
+}
+
+#[cfg(test)]
+fn observation(name: &str, region: &str, temp_c: f32, condition: &str) -> Observation {
+    Observation {
+        place: Place { name: name.to_string(), region: region.to_string(), ..Place::default() },
+        observed_epoch: 1723654500,
+        temp_c,
+        condition: condition.to_string(),
+        ..Observation::default()
+    }
+}
+
+#[test]
+fn test_observed_time() {
+    let mut obs = observation("Juneau", "Alaska", 11.1, "Overcast");
+    assert_eq!(observed_time(&obs), "2024-08-14 16:55 UTC");
+    obs.local_time = chrono::NaiveDateTime::parse_from_str("2024-08-14 08:55", "%Y-%m-%d %H:%M").ok();
+    assert_eq!(observed_time(&obs), "2024-08-14 08:55");
+}
+
+#[test]
+fn test_format_location_table() {
+    let rows = vec![
+        LocationRow {
+            location: "99801".to_string(),
+            observation: Ok(observation("Juneau", "Alaska", 11.1, "Overcast")),
+            cached: Some("3 min ago".to_string()),
+        },
+        LocationRow { location: "00000".to_string(), observation: Err("no matching location found for 00000".to_string()), cached: None },
+        LocationRow { location: "48.85,2.35".to_string(), observation: Ok(observation("48.85,2.35", "", 20.0, "Clear sky")), cached: None },
+    ];
+    assert_eq!(
+        format_location_table(&rows, &[Scale::Celsius, Scale::Fahrenheit]),
+        "-= Weather at 3 locations =-\n\
+        \x20   Location        Local time            Condition                                    Temperature    Cached\n\
+        \x20   Juneau, Alaska  2024-08-14 16:55 UTC  Overcast                                     11.1°C / 52°F  3 min ago\n\
+        \x20   00000           -                     Error: no matching location found for 00000\n\
+        \x20   48.85,2.35      2024-08-14 16:55 UTC  Clear sky                                    20°C / 68°F");
+}
@@ -0,0 +1,401 @@
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+
+use chrono_tz::Tz;
+
+use serde::de::DeserializeOwned;
+use serde::{Serialize, Deserialize};
+
+use crate::config::WeatherConfig;
+use crate::{parse_tz, zone_time};
+
+pub mod air_quality;
+pub mod alerts;
+pub mod astronomy;
+pub mod cache;
+pub mod details;
+pub mod forecast;
+pub mod historical;
+pub mod http;
+pub mod icons;
+pub mod locations;
+pub mod nws;
+pub mod open_meteo;
+pub mod query;
+pub mod thresholds;
+pub mod watch;
+pub mod weatherapi;
+
+pub use air_quality::*;
+pub use alerts::*;
+pub use astronomy::*;
+pub use cache::*;
+pub use details::*;
+pub use forecast::*;
+pub use historical::*;
+pub use http::*;
+pub use icons::*;
+pub use locations::*;
+pub use nws::*;
+pub use open_meteo::*;
+pub use query::*;
+pub use thresholds::*;
+pub use watch::*;
+pub use weatherapi::*;
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ProviderKind {
+    #[default]
+    #[serde(rename = "weatherapi")]
+    WeatherApi,
+    #[serde(rename = "open_meteo")]
+    OpenMeteo,
+    /// The US National Weather Service, which only covers the United States.
+    #[serde(rename = "nws")]
+    Nws,
+}
+
+impl ProviderKind {
+    pub fn from_name(name: &str) -> Option<ProviderKind> {
+        match name.to_lowercase().as_str() {
+            "weatherapi" => Some(ProviderKind::WeatherApi),
+            "open_meteo" | "open-meteo" | "openmeteo" => Some(ProviderKind::OpenMeteo),
+            "nws" => Some(ProviderKind::Nws),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Place {
+    pub name: String,
+    pub region: String,
+    pub country: String,
+    pub lat: f64,
+    pub lon: f64,
+    pub tz_id: String,
+}
+
+/// Current conditions at a place, whichever provider they came from.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Observation {
+    pub place: Place,
+    /// When the provider last updated the observation, as a Unix timestamp.
+    pub observed_epoch: i64,
+    /// The same moment in the place's own time zone, when the provider gives one.
+    pub local_time: Option<NaiveDateTime>,
+    pub temp_c: f32,
+    pub condition: String,
+    /// Which bundled icon shows the condition, from the provider's condition code.
+    #[serde(default)]
+    pub icon: Option<ConditionIcon>,
+    pub is_day: bool,
+    pub feelslike_c: Option<f32>,
+    pub windchill_c: Option<f32>,
+    pub heatindex_c: Option<f32>,
+    pub dewpoint_c: Option<f32>,
+    /// Relative humidity in percent.
+    pub humidity: Option<f32>,
+    pub wind_kph: Option<f32>,
+    /// The direction the wind blows from, in degrees.
+    pub wind_degree: Option<f32>,
+    pub gust_kph: Option<f32>,
+    pub pressure_mb: Option<f32>,
+    pub precip_mm: Option<f32>,
+    /// Cloud cover in percent.
+    pub cloud: Option<f32>,
+    pub uv: Option<f32>,
+    pub vis_km: Option<f32>,
+    /// Only looked up when `air_quality` is set in the weather config, and only by providers that offer it.
+    #[serde(default)]
+    pub air_quality: Option<AirQuality>,
+}
+
+impl Observation {
+    /// When the observation was made, in the place's own time zone. `None` if the provider didn't name the zone.
+    pub fn observed_at(&self) -> Option<DateTime<Tz>> {
+        zone_time(self.observed_epoch, parse_tz(&self.place.tz_id)?)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum WeatherError {
+    /// No API key was found in the config file, the environment or on the command line.
+    MissingApiKey,
+    /// The provider rejected the API key, with the provider's message.
+    InvalidApiKey(String),
+    InvalidUrl(String),
+    LocationNotFound(String),
+    /// The provider's rate limit or quota was hit, with the provider's message.
+    QuotaExceeded(String),
+    /// The provider answered but didn't have the data asked for.
+    NoData(String),
+    /// The location query didn't validate, so nothing was sent.
+    InvalidLocation(String),
+    /// The dates asked for can't be looked up, e.g. they are in the future.
+    InvalidDate(String),
+    /// The configured provider doesn't offer this kind of data, e.g. a forecast.
+    Unsupported { provider: &'static str, feature: &'static str },
+    Timeout,
+    /// The request never got a response, e.g. the network is down.
+    Transport(String),
+    /// Working offline and there was nothing stored for the location.
+    Offline(String),
+    /// Any other error status, with the provider's own error code if it sent one.
+    Http { status: u16, code: Option<i64>, message: String },
+    /// The response wasn't the JSON expected.
+    Decode(String),
+    /// The background daemon's lookup failed, with its message.
+    Daemon(String),
+    /// The HTTP client couldn't be set up with the configured timeouts.
+    Client(String),
+}
+
+impl WeatherError {
+    /// Whether the weather service couldn't be asked at all, as opposed to answering with an error.
+    pub fn is_unreachable(&self) -> bool {
+        matches!(self, WeatherError::Timeout | WeatherError::Transport(_) | WeatherError::Offline(_))
+    }
+}
+
+impl fmt::Display for WeatherError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WeatherError::MissingApiKey => write!(
+                f,
+                "no weather API key configured: set api_key under [weather] in temperature-converter.toml, \
+                the TEMPERATURE_CONVERTER_API_KEY environment variable, or pass --api-key"),
+            WeatherError::InvalidApiKey(m) => write!(f, "the weather service rejected the API key ({}), check your api_key setting", m),
+            WeatherError::InvalidUrl(u) => write!(f, "invalid weather API base URL {}", u),
+            WeatherError::LocationNotFound(q) => write!(f, "no matching location found for {}", q),
+            WeatherError::QuotaExceeded(m) => write!(f, "the weather service quota has been used up ({}), try again later", m),
+            WeatherError::NoData(m) => write!(f, "{}", m),
+            WeatherError::InvalidLocation(m) => write!(f, "{}", m),
+            WeatherError::InvalidDate(m) => write!(f, "{}", m),
+            WeatherError::Unsupported { provider, feature } => write!(
+                f, "{} does not provide {}, choose another provider with --provider or in temperature-converter.toml", provider, feature),
+            WeatherError::Timeout => write!(f, "the weather service did not respond in time"),
+            WeatherError::Transport(m) => write!(f, "could not reach the weather service ({})", m),
+            WeatherError::Offline(q) => write!(f, "working offline and no weather is stored for {}", q),
+            WeatherError::Http { status, code: Some(c), message } => write!(f, "the weather service returned HTTP {} (error {}): {}", status, c, message),
+            WeatherError::Http { status, code: None, message } => write!(f, "the weather service returned HTTP {}: {}", status, message),
+            WeatherError::Decode(m) => write!(f, "the weather service sent a response that could not be read ({})", m),
+            WeatherError::Daemon(m) => write!(f, "{}", m),
+            WeatherError::Client(m) => write!(f, "could not set up the HTTP client ({})", m),
+        }
+    }
+}
+
+impl std::error::Error for WeatherError {}
+
+impl From<reqwest::Error> for WeatherError {
+    /// Drops the request URL so the API key never ends up in an error message or the history.
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_timeout() {
+            return WeatherError::Timeout;
+        }
+        let decode = e.is_decode();
+        let e = e.without_url();
+        // reqwest's own message is just "error sending request", the useful part is the root cause.
+        let mut root: &dyn std::error::Error = &e;
+        while let Some(cause) = root.source() {
+            root = cause;
+        }
+        let message = root.to_string();
+        if decode {
+            WeatherError::Decode(message)
+        } else {
+            WeatherError::Transport(message)
+        }
+    }
+}
+
+/// Reads a JSON response body, turning an error status into the matching `WeatherError`.
+pub async fn read_json<T: DeserializeOwned>(resp: reqwest::Response, query: &str) -> Result<T, WeatherError> {
+    let status = resp.status();
+    let body = resp.text().await?;
+    if !status.is_success() {
+        return Err(status_error(status.as_u16(), &body, query));
+    }
+    serde_json::from_str(&body).map_err(|e| WeatherError::Decode(e.to_string()))
+}
+
+/// Classifies an error response using the provider's error code where there is one,
+/// e.g. WeatherAPI's `{"error": {"code": 1006, "message": "No matching location found."}}`.
+pub fn status_error(status: u16, body: &str, query: &str) -> WeatherError {
+    let (code, message) = error_body(body);
+    match (status, code) {
+        (_, Some(1006)) => WeatherError::LocationNotFound(query.to_string()),
+        (_, Some(1002 | 2006 | 2008)) | (401, _) => WeatherError::InvalidApiKey(message),
+        (_, Some(2007)) | (429, _) => WeatherError::QuotaExceeded(message),
+        _ => WeatherError::Http { status, code, message },
+    }
+}
+
+/// Pulls the error code and message out of WeatherAPI, Open-Meteo and NWS error bodies.
+fn error_body(body: &str) -> (Option<i64>, String) {
+    let json: serde_json::Value = serde_json::from_str(body).unwrap_or_default();
+    let text = |v: &serde_json::Value| v.as_str().map(|s| s.trim_end_matches('.').to_string());
+    if let Some(error) = json.get("error").filter(|e| e.is_object()) {
+        return (error["code"].as_i64(), text(&error["message"]).unwrap_or_default());
+    }
+    let message = text(&json["reason"])
+        .or_else(|| text(&json["detail"]))
+        .or_else(|| text(&json["title"]))
+        .unwrap_or_else(|| body.trim().chars().take(200).collect());
+    (None, message)
+}
+
+pub type WeatherFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, WeatherError>> + Send + 'a>>;
+
+/// A source of weather data.
+pub trait WeatherProvider: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    fn current<'a>(&'a self, query: &'a LocationQuery) -> WeatherFuture<'a, Observation>;
+
+    /// Forecasts `days` days starting today.
+    fn forecast<'a>(&'a self, query: &'a LocationQuery, days: u32) -> WeatherFuture<'a, Forecast> {
+        let _ = (query, days);
+        Box::pin(async move { Err(WeatherError::Unsupported { provider: self.name(), feature: "forecasts" }) })
+    }
+
+    /// Watches, warnings and advisories in effect at the location now.
+    fn alerts<'a>(&'a self, query: &'a LocationQuery) -> WeatherFuture<'a, WeatherAlerts> {
+        let _ = query;
+        Box::pin(async move { Err(WeatherError::Unsupported { provider: self.name(), feature: "weather alerts" }) })
+    }
+
+    /// Sunrise, sunset, moonrise, moonset and the moon's phase at the location on `date`.
+    fn astronomy<'a>(&'a self, query: &'a LocationQuery, date: NaiveDate) -> WeatherFuture<'a, Astronomy> {
+        let _ = (query, date);
+        Box::pin(async move { Err(WeatherError::Unsupported { provider: self.name(), feature: "astronomy" }) })
+    }
+
+    /// Recorded weather for each day from `from` to `to`, both included.
+    fn history<'a>(&'a self, query: &'a LocationQuery, from: NaiveDate, to: NaiveDate) -> WeatherFuture<'a, WeatherHistory> {
+        let _ = (query, from, to);
+        Box::pin(async move { Err(WeatherError::Unsupported { provider: self.name(), feature: "historical weather" }) })
+    }
+}
+
+/// Fails only if the HTTP client can't be set up.
+pub fn provider_from_config(config: &WeatherConfig) -> Result<Box<dyn WeatherProvider>, WeatherError> {
+    if config.offline {
+        return Ok(Box::new(Offline));
+    }
+    Ok(match config.provider {
+        ProviderKind::WeatherApi => Box::new(WeatherApi::new(config)?),
+        ProviderKind::OpenMeteo => Box::new(OpenMeteo::new(config)?),
+        ProviderKind::Nws => Box::new(Nws::new(config)?),
+    })
+}
+
+/// Stands in for the configured provider when working offline, so nothing is ever sent.
+pub struct Offline;
+
+impl WeatherProvider for Offline {
+    fn name(&self) -> &'static str {
+        "offline"
+    }
+
+    fn current<'a>(&'a self, query: &'a LocationQuery) -> WeatherFuture<'a, Observation> {
+        Box::pin(async move { Err(WeatherError::Offline(query.to_string())) })
+    }
+
+    fn forecast<'a>(&'a self, query: &'a LocationQuery, _days: u32) -> WeatherFuture<'a, Forecast> {
+        Box::pin(async move { Err(WeatherError::Offline(query.to_string())) })
+    }
+
+    fn alerts<'a>(&'a self, query: &'a LocationQuery) -> WeatherFuture<'a, WeatherAlerts> {
+        Box::pin(async move { Err(WeatherError::Offline(query.to_string())) })
+    }
+
+    fn astronomy<'a>(&'a self, query: &'a LocationQuery, _date: NaiveDate) -> WeatherFuture<'a, Astronomy> {
+        Box::pin(async move { Err(WeatherError::Offline(query.to_string())) })
+    }
+
+    fn history<'a>(&'a self, query: &'a LocationQuery, _from: NaiveDate, _to: NaiveDate) -> WeatherFuture<'a, WeatherHistory> {
+        Box::pin(async move { Err(WeatherError::Offline(query.to_string())) })
+    }
+}
+
+/// Joins `path` onto a configured base URL and appends the encoded query parameters.
+pub fn endpoint(base: &str, path: &str, params: &[(&str, &str)]) -> Result<reqwest::Url, WeatherError> {
+    reqwest::Url::parse_with_params(&format!("{}/{}", base.trim_end_matches('/'), path), params)
+        .map_err(|_| WeatherError::InvalidUrl(base.to_string()))
+}
+
+pub async fn get_current_temp(zip: String) -> Result<(String, String, f32), WeatherError> {
+    get_current_temp_with(&crate::Config::load().unwrap_or_default().weather, &zip).await
+}
+
+/// Looks up any kind of location query, guessing the kind from the text.
+pub async fn get_current_temp_with(config: &WeatherConfig, query: &str) -> Result<(String, String, f32), WeatherError> {
+    let query: LocationQuery = query.parse().map_err(WeatherError::InvalidLocation)?;
+    let obs = get_observation(config, &query).await?;
+    Ok((obs.place.name, obs.place.region, obs.temp_c))
+}
+
+pub async fn get_observation(config: &WeatherConfig, query: &LocationQuery) -> Result<Observation, WeatherError> {
+    provider_from_config(config)?.current(query).await
+}
+
+/// Recorded weather at `query` for each day from `from` to `to`, both included.
+pub async fn get_historical_weather(config: &WeatherConfig, query: &LocationQuery, from: NaiveDate, to: NaiveDate) -> Result<WeatherHistory, WeatherError> {
+    check_date_range(from, to, chrono::Local::now().date_naive()).map_err(WeatherError::InvalidDate)?;
+    provider_from_config(config)?.history(query, from, to).await
+}
+
+pub async fn get_alerts(config: &WeatherConfig, query: &LocationQuery) -> Result<WeatherAlerts, WeatherError> {
+    provider_from_config(config)?.alerts(query).await
+}
+
+/// Sun and moon times at `query` on `date`, in the place's own time zone.
+pub async fn get_astronomy(config: &WeatherConfig, query: &LocationQuery, date: NaiveDate) -> Result<Astronomy, WeatherError> {
+    provider_from_config(config)?.astronomy(query, date).await
+}
+
+pub async fn get_forecast(config: &WeatherConfig, query: &LocationQuery, days: u32) -> Result<Forecast, WeatherError> {
+    provider_from_config(config)?.forecast(query, days).await
+}
+
+#[test]
+fn test_provider_from_name() {
+    assert_eq!(ProviderKind::from_name("Open-Meteo"), Some(ProviderKind::OpenMeteo));
+    assert_eq!(ProviderKind::from_name("nws"), Some(ProviderKind::Nws));
+    assert_eq!(ProviderKind::from_name("accuweather"), None);
+    assert_eq!(provider_from_config(&WeatherConfig::default()).unwrap().name(), "WeatherAPI");
+    let offline = WeatherConfig { offline: true, provider: ProviderKind::Nws, ..WeatherConfig::default() };
+    assert_eq!(provider_from_config(&offline).unwrap().name(), "offline");
+}
+
+#[test]
+fn test_endpoint() {
+    assert_eq!(
+        endpoint("https://example.com/v1/", "forecast", &[("q", "a b")]).unwrap().as_str(),
+        "https://example.com/v1/forecast?q=a+b");
+    assert!(matches!(endpoint("example", "forecast", &[]), Err(WeatherError::InvalidUrl(_))));
+}
+
+#[test]
+fn test_status_error() {
+    assert_eq!(
+        status_error(400, r#"{"error": {"code": 1006, "message": "No matching location found."}}"#, "00000"),
+        WeatherError::LocationNotFound("00000".to_string()));
+    assert_eq!(
+        status_error(403, r#"{"error": {"code": 2006, "message": "API key is invalid."}}"#, "x"),
+        WeatherError::InvalidApiKey("API key is invalid".to_string()));
+    assert_eq!(
+        status_error(403, r#"{"error": {"code": 2007, "message": "API key has exceeded calls per month quota."}}"#, "x"),
+        WeatherError::QuotaExceeded("API key has exceeded calls per month quota".to_string()));
+    assert_eq!(
+        status_error(400, r#"{"error": true, "reason": "Latitude must be in range of -90 to 90°."}"#, "x"),
+        WeatherError::Http { status: 400, code: None, message: "Latitude must be in range of -90 to 90°".to_string() });
+    assert_eq!(
+        status_error(500, "Internal Server Error", "x"),
+        WeatherError::Http { status: 500, code: None, message: "Internal Server Error".to_string() });
+}
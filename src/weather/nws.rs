@@ -0,0 +1,434 @@
+use chrono::DateTime;
+
+use reqwest::header::{ACCEPT, USER_AGENT};
+
+use serde::Deserialize;
+
+use crate::config::WeatherConfig;
+use crate::{parse_tz, zone_time};
+
+use super::{
+    days_from_hours, endpoint, geocode, parse_alert_time, read_json, split_areas, AlertSeverity, AlertUrgency, ConditionIcon, Forecast, HourlyForecast,
+    HttpClient, LocationQuery, Observation, Place, WeatherAlert, WeatherAlerts, WeatherError, WeatherFuture, WeatherProvider,
+};
+
+#[derive(Default, Debug, Clone, PartialEq, Deserialize)]
+pub struct NwsPoint {
+    pub properties: NwsPointProperties,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NwsPointProperties {
+    pub grid_id: String,
+    pub grid_x: i64,
+    pub grid_y: i64,
+    pub time_zone: String,
+    pub relative_location: NwsRelativeLocation,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Deserialize)]
+pub struct NwsRelativeLocation {
+    pub properties: NwsCity,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Deserialize)]
+pub struct NwsCity {
+    pub city: String,
+    pub state: String,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Deserialize)]
+pub struct NwsStations {
+    pub features: Vec<NwsStation>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Deserialize)]
+pub struct NwsStation {
+    pub properties: NwsStationProperties,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NwsStationProperties {
+    pub station_identifier: String,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Deserialize)]
+pub struct NwsObservation {
+    pub properties: NwsObservationProperties,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NwsObservationProperties {
+    pub timestamp: String,
+    #[serde(default)]
+    pub text_description: String,
+    /// An icon URL, which has "/night/" in it after dark.
+    #[serde(default)]
+    pub icon: Option<String>,
+    pub temperature: NwsValue,
+    #[serde(default)]
+    pub dewpoint: NwsValue,
+    #[serde(default)]
+    pub wind_chill: NwsValue,
+    #[serde(default)]
+    pub heat_index: NwsValue,
+    #[serde(default)]
+    pub relative_humidity: NwsValue,
+    #[serde(default)]
+    pub wind_speed: NwsValue,
+    #[serde(default)]
+    pub wind_direction: NwsValue,
+    #[serde(default)]
+    pub wind_gust: NwsValue,
+    #[serde(default)]
+    pub barometric_pressure: NwsValue,
+    #[serde(default)]
+    pub precipitation_last_hour: NwsValue,
+    #[serde(default)]
+    pub visibility: NwsValue,
+}
+
+/// The `alerts/active` response, a GeoJSON feature per alert.
+#[derive(Default, Debug, Clone, PartialEq, Deserialize)]
+pub struct NwsAlerts {
+    pub features: Vec<NwsAlert>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Deserialize)]
+pub struct NwsAlert {
+    pub properties: NwsAlertProperties,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct NwsAlertProperties {
+    /// Zones separated by semicolons.
+    pub area_desc: String,
+    pub effective: Option<String>,
+    /// When the alert message lapses, which can be before the event ends.
+    pub expires: Option<String>,
+    /// When the event itself is expected to end, if known.
+    pub ends: Option<String>,
+    pub severity: String,
+    pub urgency: String,
+    pub event: String,
+    pub headline: Option<String>,
+    pub description: String,
+    pub instruction: Option<String>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Deserialize)]
+pub struct NwsForecast {
+    pub properties: NwsForecastProperties,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Deserialize)]
+pub struct NwsForecastProperties {
+    pub periods: Vec<NwsPeriod>,
+}
+
+/// One hour of the hourly forecast.
+#[derive(Default, Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NwsPeriod {
+    /// With the location's UTC offset, e.g. "2024-08-20T16:00:00-04:00".
+    pub start_time: String,
+    pub temperature: f64,
+    /// "F" or "C".
+    pub temperature_unit: String,
+    #[serde(default)]
+    pub probability_of_precipitation: NwsValue,
+    #[serde(default)]
+    pub short_forecast: String,
+}
+
+/// A measurement in the unit named by `unit_code`, e.g. "wmoUnit:degC". Stations often report null values.
+#[derive(Default, Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NwsValue {
+    pub value: Option<f64>,
+    #[serde(default)]
+    pub unit_code: String,
+}
+
+impl NwsValue {
+    pub fn celsius(&self) -> Option<f32> {
+        self.convert("wmoUnit:degF", |v| (v - 32.0) * 5.0 / 9.0)
+    }
+
+    pub fn kph(&self) -> Option<f32> {
+        self.convert("wmoUnit:m_s-1", |v| v * 3.6)
+    }
+
+    pub fn mb(&self) -> Option<f32> {
+        self.convert("wmoUnit:Pa", |v| v / 100.0)
+    }
+
+    pub fn km(&self) -> Option<f32> {
+        self.convert("wmoUnit:m", |v| v / 1000.0)
+    }
+
+    pub fn mm(&self) -> Option<f32> {
+        self.convert("wmoUnit:m", |v| v * 1000.0)
+    }
+
+    pub fn value(&self) -> Option<f32> {
+        self.value.map(|v| v as f32)
+    }
+
+    /// The value as an `f32`, converted with `f` if it is in `unit`.
+    fn convert(&self, unit: &str, f: fn(f64) -> f64) -> Option<f32> {
+        self.value.map(|v| if self.unit_code == unit { f(v) } else { v } as f32)
+    }
+}
+
+/// api.weather.gov, which needs no API key but only covers the United States.
+/// Queries other than coordinates are resolved with the Open-Meteo geocoding API.
+pub struct Nws {
+    http: HttpClient,
+    url: String,
+    geocoding_url: String,
+}
+
+impl Nws {
+    pub fn new(config: &WeatherConfig) -> Result<Nws, WeatherError> {
+        Ok(Nws {
+            http: HttpClient::shared(&config.http)?,
+            url: config.nws_url.to_string(),
+            geocoding_url: config.geocoding_url.to_string(),
+        })
+    }
+
+    /// Geocodes the query and looks up the forecast office grid covering it.
+    async fn point(&self, query: &LocationQuery) -> Result<(Place, NwsPoint), WeatherError> {
+        let place = geocode(&self.http, &self.geocoding_url, query).await?;
+        // A 404 here means the point is outside the United States.
+        match self.get(&format!("points/{:.4},{:.4}", place.lat, place.lon), &[], &query.to_string()).await {
+            Err(WeatherError::Http { status: 404, .. }) => Err(WeatherError::LocationNotFound(query.to_string())),
+            point => Ok((place, point?)),
+        }
+    }
+
+    async fn get<T: serde::de::DeserializeOwned>(&self, path: &str, params: &[(&str, &str)], query: &str) -> Result<T, WeatherError> {
+        let request = self.http.get(endpoint(&self.url, path, params)?)
+            .header(USER_AGENT, "temperature-converter")
+            .header(ACCEPT, "application/geo+json");
+        let resp = self.http.send(request).await?;
+        read_json(resp, query).await
+    }
+}
+
+impl WeatherProvider for Nws {
+    fn name(&self) -> &'static str {
+        "National Weather Service"
+    }
+
+    fn current<'a>(&'a self, query: &'a LocationQuery) -> WeatherFuture<'a, Observation> {
+        Box::pin(async move {
+            let (place, point) = self.point(query).await?;
+            let query = &query.to_string();
+            let p = &point.properties;
+            let stations: NwsStations = self.get(&format!("gridpoints/{}/{},{}/stations", p.grid_id, p.grid_x, p.grid_y), &[], query).await?;
+            let Some(station) = stations.features.first() else {
+                return Err(WeatherError::NoData(format!("no observation stations near {}", query)));
+            };
+            let latest: NwsObservation = self.get(
+                &format!("stations/{}/observations/latest", station.properties.station_identifier), &[], query).await?;
+            nws_observation(place, point, latest)
+        })
+    }
+
+    fn forecast<'a>(&'a self, query: &'a LocationQuery, days: u32) -> WeatherFuture<'a, Forecast> {
+        Box::pin(async move {
+            let (place, point) = self.point(query).await?;
+            let query = &query.to_string();
+            let p = &point.properties;
+            let hourly: NwsForecast = self.get(&format!("gridpoints/{}/{},{}/forecast/hourly", p.grid_id, p.grid_x, p.grid_y), &[], query).await?;
+            let mut forecast = nws_forecast(place, point, hourly);
+            forecast.days.truncate(days as usize);
+            Ok(forecast)
+        })
+    }
+
+    fn alerts<'a>(&'a self, query: &'a LocationQuery) -> WeatherFuture<'a, WeatherAlerts> {
+        Box::pin(async move {
+            let (place, point) = self.point(query).await?;
+            let at = format!("{:.4},{:.4}", place.lat, place.lon);
+            let active: NwsAlerts = self.get("alerts/active", &[("point", &at)], &query.to_string()).await?;
+            Ok(nws_alerts(place, point, active))
+        })
+    }
+}
+
+pub fn nws_observation(place: Place, point: NwsPoint, latest: NwsObservation) -> Result<Observation, WeatherError> {
+    let obs = latest.properties;
+    let Some(temp_c) = obs.temperature.celsius() else {
+        return Err(WeatherError::NoData("the nearest station did not report a temperature".to_string()));
+    };
+    let place = nws_place(place, point);
+    let observed_epoch = DateTime::parse_from_rfc3339(&obs.timestamp).map(|t| t.timestamp()).unwrap_or_default();
+    Ok(Observation {
+        // Observations are stamped in UTC and the point only names its time zone.
+        local_time: parse_tz(&place.tz_id).and_then(|tz| zone_time(observed_epoch, tz)).map(|t| t.naive_local()),
+        place,
+        observed_epoch,
+        temp_c,
+        condition: obs.text_description,
+        icon: obs.icon.as_deref().and_then(ConditionIcon::from_nws_icon),
+        is_day: !obs.icon.is_some_and(|i| i.contains("/night/")),
+        feelslike_c: obs.wind_chill.celsius().or(obs.heat_index.celsius()),
+        windchill_c: obs.wind_chill.celsius(),
+        heatindex_c: obs.heat_index.celsius(),
+        dewpoint_c: obs.dewpoint.celsius(),
+        humidity: obs.relative_humidity.value(),
+        wind_kph: obs.wind_speed.kph(),
+        wind_degree: obs.wind_direction.value(),
+        gust_kph: obs.wind_gust.kph(),
+        pressure_mb: obs.barometric_pressure.mb(),
+        precip_mm: obs.precipitation_last_hour.mm(),
+        cloud: None,
+        uv: None,
+        vis_km: obs.visibility.km(),
+        air_quality: None,
+    })
+}
+
+/// Names the place after the town the NWS point is nearest to.
+fn nws_place(place: Place, point: NwsPoint) -> Place {
+    let city = point.properties.relative_location.properties;
+    Place {
+        name: city.city,
+        region: city.state,
+        country: "United States".to_string(),
+        tz_id: point.properties.time_zone,
+        ..place
+    }
+}
+
+pub fn nws_alerts(place: Place, point: NwsPoint, active: NwsAlerts) -> WeatherAlerts {
+    let alerts = active.features.into_iter().map(|f| {
+        let a = f.properties;
+        let time = |t: Option<String>| t.as_deref().and_then(parse_alert_time);
+        WeatherAlert {
+            headline: a.headline.unwrap_or_default(),
+            severity: AlertSeverity::from_name(&a.severity),
+            urgency: AlertUrgency::from_name(&a.urgency),
+            areas: split_areas(&a.area_desc),
+            effective: time(a.effective),
+            expires: time(a.ends).or(time(a.expires)),
+            event: a.event,
+            description: a.description,
+            instruction: a.instruction.unwrap_or_default(),
+        }
+    }).collect();
+    WeatherAlerts::new(nws_place(place, point), alerts)
+}
+
+pub fn nws_forecast(place: Place, point: NwsPoint, hourly: NwsForecast) -> Forecast {
+    let hours = hourly.properties.periods.into_iter().filter_map(|p| {
+        let start = DateTime::parse_from_rfc3339(&p.start_time).ok()?;
+        let temp_c = match p.temperature_unit.as_str() {
+            "F" => (p.temperature - 32.0) * 5.0 / 9.0,
+            _ => p.temperature,
+        };
+        Some(HourlyForecast {
+            time_epoch: start.timestamp(),
+            local_time: start.naive_local(),
+            temp_c: temp_c as f32,
+            precip_chance: p.probability_of_precipitation.value(),
+            condition: p.short_forecast,
+        })
+    }).collect();
+    Forecast {
+        place: nws_place(place, point),
+        days: days_from_hours(hours),
+    }
+}
+
+#[cfg(test)]
+const POINT: &str = r#"{"properties": {"gridId": "LWX", "gridX": 96, "gridY": 70, "timeZone": "America/New_York",
+    "relativeLocation": {"properties": {"city": "Washington", "state": "DC"}}}}"#;
+
+#[test]
+fn test_nws_observation() {
+    let latest: NwsObservation = serde_json::from_str(r#"{"properties": {"timestamp": "2024-08-20T19:52:00+00:00",
+        "textDescription": "Clear", "temperature": {"unitCode": "wmoUnit:degC", "value": 26.1}}}"#).unwrap();
+    let obs = nws_observation(Place { lat: 38.8977, lon: -77.0365, ..Place::default() }, serde_json::from_str(POINT).unwrap(), latest).unwrap();
+    assert_eq!(obs.place.name, "Washington");
+    assert_eq!(obs.place.region, "DC");
+    assert_eq!(obs.place.lat, 38.8977);
+    assert_eq!(obs.temp_c, 26.1);
+    assert_eq!(obs.condition, "Clear");
+    assert_eq!(obs.observed_epoch, 1724183520);
+    assert!(obs.is_day);
+    assert_eq!(obs.humidity, None);
+}
+
+#[test]
+fn test_nws_units() {
+    let latest: NwsObservation = serde_json::from_str(r#"{"properties": {"timestamp": "2024-08-20T04:52:00+00:00",
+        "icon": "https://api.weather.gov/icons/land/night/few?size=medium",
+        "temperature": {"unitCode": "wmoUnit:degF", "value": 50.0},
+        "windChill": {"unitCode": "wmoUnit:degC", "value": 8.5},
+        "heatIndex": {"unitCode": "wmoUnit:degC", "value": null},
+        "windSpeed": {"unitCode": "wmoUnit:m_s-1", "value": 5.0},
+        "barometricPressure": {"unitCode": "wmoUnit:Pa", "value": 101320},
+        "visibility": {"unitCode": "wmoUnit:m", "value": 16090},
+        "relativeHumidity": {"unitCode": "wmoUnit:percent", "value": 64.5}}}"#).unwrap();
+    let obs = nws_observation(Place::default(), serde_json::from_str(POINT).unwrap(), latest).unwrap();
+    assert_eq!(obs.temp_c, 10.0);
+    assert!(!obs.is_day);
+    assert_eq!(obs.feelslike_c, Some(8.5));
+    assert_eq!(obs.heatindex_c, None);
+    assert_eq!(obs.wind_kph, Some(18.0));
+    assert_eq!(obs.pressure_mb, Some(1013.2));
+    assert_eq!(obs.vis_km, Some(16.09));
+    assert_eq!(obs.humidity, Some(64.5));
+}
+
+#[test]
+fn test_nws_missing_temperature() {
+    let latest: NwsObservation = serde_json::from_str(r#"{"properties": {"timestamp": "2024-08-20T19:52:00+00:00",
+        "textDescription": "", "temperature": {"unitCode": "wmoUnit:degC", "value": null}}}"#).unwrap();
+    assert!(matches!(nws_observation(Place::default(), serde_json::from_str(POINT).unwrap(), latest), Err(WeatherError::NoData(_))));
+}
+
+#[test]
+fn test_nws_forecast() {
+    let hourly: NwsForecast = serde_json::from_str(r#"{"properties": {"periods": [
+        {"startTime": "2024-08-20T22:00:00-04:00", "temperature": 77, "temperatureUnit": "F",
+            "probabilityOfPrecipitation": {"unitCode": "wmoUnit:percent", "value": 10}, "shortForecast": "Mostly Clear"},
+        {"startTime": "2024-08-20T23:00:00-04:00", "temperature": 75, "temperatureUnit": "F",
+            "probabilityOfPrecipitation": {"unitCode": "wmoUnit:percent", "value": null}, "shortForecast": "Mostly Clear"},
+        {"startTime": "2024-08-21T00:00:00-04:00", "temperature": 24, "temperatureUnit": "C", "shortForecast": "Clear"}
+    ]}}"#).unwrap();
+    let forecast = nws_forecast(Place::default(), serde_json::from_str(POINT).unwrap(), hourly);
+    assert_eq!(forecast.place.name, "Washington");
+    assert_eq!(forecast.days.len(), 2);
+    assert_eq!(forecast.days[0].max_c, 25.0);
+    assert_eq!(forecast.days[0].precip_chance, Some(10.0));
+    assert_eq!(forecast.days[0].hours[1].local_time.to_string(), "2024-08-20 23:00:00");
+    assert_eq!(forecast.days[1].min_c, 24.0);
+}
+
+#[test]
+fn test_nws_alerts() {
+    let active: NwsAlerts = serde_json::from_str(r#"{"features": [
+        {"properties": {"areaDesc": "District of Columbia; Arlington", "effective": "2024-08-20T11:00:00-04:00",
+            "expires": "2024-08-20T14:00:00-04:00", "ends": "2024-08-20T20:00:00-04:00", "severity": "Moderate",
+            "urgency": "Expected", "event": "Heat Advisory", "headline": "Heat Advisory issued August 20", "description": "Heat index values up to 108."}},
+        {"properties": {"areaDesc": "District of Columbia", "severity": "Severe", "urgency": "Immediate",
+            "event": "Severe Thunderstorm Warning", "headline": null, "description": ""}}
+    ]}"#).unwrap();
+    let alerts = nws_alerts(Place::default(), serde_json::from_str(POINT).unwrap(), active);
+    assert_eq!(alerts.place.name, "Washington");
+    assert_eq!(alerts.alerts.iter().map(|a| a.event.as_str()).collect::<Vec<_>>(), vec!["Severe Thunderstorm Warning", "Heat Advisory"]);
+    let heat = &alerts.alerts[1];
+    assert_eq!(heat.areas, vec!["District of Columbia", "Arlington"]);
+    assert_eq!(heat.expires, parse_alert_time("2024-08-20T20:00:00-04:00"));
+    assert_eq!((heat.severity, heat.urgency), (AlertSeverity::Moderate, AlertUrgency::Expected));
+    assert_eq!(alerts.alerts[0].effective, None);
+}
@@ -0,0 +1,465 @@
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+
+use serde::Deserialize;
+
+use crate::config::WeatherConfig;
+
+use super::{
+    days_from_hours, endpoint, historical_days, moon_phase, Astronomy, ConditionIcon, Forecast, HistoricalHour, HourlyForecast, HttpClient, Observation, Place,
+    LocationQuery, WeatherError, WeatherFuture, WeatherHistory, WeatherProvider,
+};
+
+#[derive(Default, Debug, Clone, PartialEq, Deserialize)]
+pub struct GeocodingResponse {
+    #[serde(default)]
+    pub results: Vec<GeocodingResult>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Deserialize)]
+pub struct GeocodingResult {
+    pub name: String,
+    pub latitude: f64,
+    pub longitude: f64,
+    #[serde(default)]
+    pub country: Option<String>,
+    #[serde(default)]
+    pub country_code: Option<String>,
+    #[serde(default)]
+    pub admin1: Option<String>,
+    #[serde(default)]
+    pub timezone: Option<String>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Deserialize)]
+pub struct OpenMeteoResponse {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub timezone: String,
+    #[serde(default)]
+    pub utc_offset_seconds: i64,
+    pub current: OpenMeteoCurrent,
+}
+
+/// Requested with `timeformat=unixtime`, so `time` is a Unix timestamp.
+#[derive(Default, Debug, Clone, PartialEq, Deserialize)]
+pub struct OpenMeteoCurrent {
+    pub time: i64,
+    pub temperature_2m: f32,
+    pub weather_code: i64,
+    pub is_day: i64,
+    #[serde(default)]
+    pub apparent_temperature: Option<f32>,
+    #[serde(default)]
+    pub dew_point_2m: Option<f32>,
+    #[serde(default)]
+    pub relative_humidity_2m: Option<f32>,
+    #[serde(default)]
+    pub wind_speed_10m: Option<f32>,
+    #[serde(default)]
+    pub wind_direction_10m: Option<f32>,
+    #[serde(default)]
+    pub wind_gusts_10m: Option<f32>,
+    #[serde(default)]
+    pub pressure_msl: Option<f32>,
+    #[serde(default)]
+    pub precipitation: Option<f32>,
+    #[serde(default)]
+    pub cloud_cover: Option<f32>,
+    #[serde(default)]
+    pub uv_index: Option<f32>,
+    /// In metres.
+    #[serde(default)]
+    pub visibility: Option<f32>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Deserialize)]
+pub struct OpenMeteoForecast {
+    pub timezone: String,
+    pub utc_offset_seconds: i64,
+    pub hourly: OpenMeteoHourly,
+}
+
+/// Parallel arrays, one entry per hour.
+#[derive(Default, Debug, Clone, PartialEq, Deserialize)]
+pub struct OpenMeteoHourly {
+    pub time: Vec<i64>,
+    pub temperature_2m: Vec<f32>,
+    #[serde(default)]
+    pub precipitation_probability: Vec<Option<f32>>,
+    pub weather_code: Vec<i64>,
+}
+
+/// A forecast response asked for `daily=sunrise,sunset` only.
+#[derive(Default, Debug, Clone, PartialEq, Deserialize)]
+pub struct OpenMeteoSun {
+    pub timezone: String,
+    pub daily: OpenMeteoSunDaily,
+}
+
+/// Parallel arrays, one entry per day. Times are local ISO 8601, e.g. "2024-08-14T05:08".
+#[derive(Default, Debug, Clone, PartialEq, Deserialize)]
+pub struct OpenMeteoSunDaily {
+    pub time: Vec<String>,
+    pub sunrise: Vec<String>,
+    pub sunset: Vec<String>,
+}
+
+/// A response from the historical weather (archive) API.
+#[derive(Default, Debug, Clone, PartialEq, Deserialize)]
+pub struct OpenMeteoArchive {
+    pub timezone: String,
+    pub utc_offset_seconds: i64,
+    pub hourly: OpenMeteoArchiveHourly,
+}
+
+/// Parallel arrays, one entry per hour. Hours not yet in the archive are null.
+#[derive(Default, Debug, Clone, PartialEq, Deserialize)]
+pub struct OpenMeteoArchiveHourly {
+    pub time: Vec<i64>,
+    pub temperature_2m: Vec<Option<f32>>,
+    #[serde(default)]
+    pub precipitation: Vec<Option<f32>>,
+    #[serde(default)]
+    pub relative_humidity_2m: Vec<Option<f32>>,
+    #[serde(default)]
+    pub wind_speed_10m: Vec<Option<f32>>,
+    #[serde(default)]
+    pub weather_code: Vec<Option<i64>>,
+}
+
+const CURRENT_FIELDS: &str = "temperature_2m,weather_code,is_day,apparent_temperature,dew_point_2m,relative_humidity_2m,\
+    wind_speed_10m,wind_direction_10m,wind_gusts_10m,pressure_msl,precipitation,cloud_cover,uv_index,visibility";
+
+/// open-meteo.com, which needs no API key. Locations are resolved with its geocoding API.
+pub struct OpenMeteo {
+    http: HttpClient,
+    url: String,
+    archive_url: String,
+    geocoding_url: String,
+}
+
+impl OpenMeteo {
+    pub fn new(config: &WeatherConfig) -> Result<OpenMeteo, WeatherError> {
+        Ok(OpenMeteo {
+            http: HttpClient::shared(&config.http)?,
+            url: config.open_meteo_url.to_string(),
+            archive_url: config.archive_url.to_string(),
+            geocoding_url: config.geocoding_url.to_string(),
+        })
+    }
+}
+
+impl WeatherProvider for OpenMeteo {
+    fn name(&self) -> &'static str {
+        "Open-Meteo"
+    }
+
+    fn current<'a>(&'a self, query: &'a LocationQuery) -> WeatherFuture<'a, Observation> {
+        Box::pin(async move {
+            let place = geocode(&self.http, &self.geocoding_url, query).await?;
+            let (lat, lon) = (place.lat.to_string(), place.lon.to_string());
+            let url = endpoint(&self.url, "forecast", &[
+                ("latitude", &lat),
+                ("longitude", &lon),
+                ("current", CURRENT_FIELDS),
+                ("timezone", "auto"),
+                ("timeformat", "unixtime"),
+            ])?;
+            let resp: OpenMeteoResponse = self.http.get_json(url, &query.to_string()).await?;
+            Ok(open_meteo_observation(place, resp))
+        })
+    }
+
+    fn forecast<'a>(&'a self, query: &'a LocationQuery, days: u32) -> WeatherFuture<'a, Forecast> {
+        Box::pin(async move {
+            let place = geocode(&self.http, &self.geocoding_url, query).await?;
+            let (lat, lon) = (place.lat.to_string(), place.lon.to_string());
+            let url = endpoint(&self.url, "forecast", &[
+                ("latitude", &lat),
+                ("longitude", &lon),
+                ("hourly", "temperature_2m,precipitation_probability,weather_code"),
+                ("forecast_days", &days.to_string()),
+                ("timezone", "auto"),
+                ("timeformat", "unixtime"),
+            ])?;
+            let resp: OpenMeteoForecast = self.http.get_json(url, &query.to_string()).await?;
+            Ok(open_meteo_forecast(place, resp))
+        })
+    }
+
+    fn astronomy<'a>(&'a self, query: &'a LocationQuery, date: NaiveDate) -> WeatherFuture<'a, Astronomy> {
+        Box::pin(async move {
+            let place = geocode(&self.http, &self.geocoding_url, query).await?;
+            let (lat, lon, day) = (place.lat.to_string(), place.lon.to_string(), date.to_string());
+            let url = endpoint(&self.url, "forecast", &[
+                ("latitude", &lat),
+                ("longitude", &lon),
+                ("daily", "sunrise,sunset"),
+                ("start_date", &day),
+                ("end_date", &day),
+                ("timezone", "auto"),
+            ])?;
+            let resp: OpenMeteoSun = self.http.get_json(url, &query.to_string()).await?;
+            Ok(open_meteo_astronomy(place, date, resp))
+        })
+    }
+
+    fn history<'a>(&'a self, query: &'a LocationQuery, from: NaiveDate, to: NaiveDate) -> WeatherFuture<'a, WeatherHistory> {
+        Box::pin(async move {
+            let place = geocode(&self.http, &self.geocoding_url, query).await?;
+            let (lat, lon) = (place.lat.to_string(), place.lon.to_string());
+            let url = endpoint(&self.archive_url, "archive", &[
+                ("latitude", &lat),
+                ("longitude", &lon),
+                ("start_date", &from.to_string()),
+                ("end_date", &to.to_string()),
+                ("hourly", "temperature_2m,precipitation,relative_humidity_2m,wind_speed_10m,weather_code"),
+                ("timezone", "auto"),
+                ("timeformat", "unixtime"),
+            ])?;
+            let resp: OpenMeteoArchive = self.http.get_json(url, &query.to_string()).await?;
+            open_meteo_history(place, resp, &query.to_string())
+        })
+    }
+}
+
+pub fn open_meteo_observation(place: Place, resp: OpenMeteoResponse) -> Observation {
+    let c = resp.current;
+    Observation {
+        place: Place { tz_id: resp.timezone, ..place },
+        observed_epoch: c.time,
+        local_time: DateTime::from_timestamp(c.time + resp.utc_offset_seconds, 0).map(|t| t.naive_utc()),
+        temp_c: c.temperature_2m,
+        condition: wmo_condition(c.weather_code).to_string(),
+        icon: ConditionIcon::from_wmo_code(c.weather_code),
+        is_day: c.is_day == 1,
+        feelslike_c: c.apparent_temperature,
+        windchill_c: None,
+        heatindex_c: None,
+        dewpoint_c: c.dew_point_2m,
+        humidity: c.relative_humidity_2m,
+        wind_kph: c.wind_speed_10m,
+        wind_degree: c.wind_direction_10m,
+        gust_kph: c.wind_gusts_10m,
+        pressure_mb: c.pressure_msl,
+        precip_mm: c.precipitation,
+        cloud: c.cloud_cover,
+        uv: c.uv_index,
+        vis_km: c.visibility.map(|v| v / 1000.0),
+        // Open-Meteo's air quality is a separate API with neither index, so it isn't looked up.
+        air_quality: None,
+    }
+}
+
+pub fn open_meteo_forecast(place: Place, resp: OpenMeteoForecast) -> Forecast {
+    let h = resp.hourly;
+    let hours = h.time.iter().zip(&h.temperature_2m).zip(&h.weather_code).enumerate()
+        .map(|(i, ((&time, &temp_c), &code))| HourlyForecast {
+            time_epoch: time,
+            local_time: DateTime::from_timestamp(time + resp.utc_offset_seconds, 0).unwrap_or_default().naive_utc(),
+            temp_c,
+            precip_chance: h.precipitation_probability.get(i).copied().flatten(),
+            condition: wmo_condition(code).to_string(),
+        })
+        .collect();
+    Forecast { place: Place { tz_id: resp.timezone, ..place }, days: days_from_hours(hours) }
+}
+
+/// Open-Meteo has no moon data, so moonrise and moonset are left out and the phase is worked out here.
+pub fn open_meteo_astronomy(place: Place, date: NaiveDate, resp: OpenMeteoSun) -> Astronomy {
+    let d = resp.daily;
+    let time = |times: &Vec<String>| times.first()
+        .and_then(|t| NaiveDateTime::parse_from_str(t, "%Y-%m-%dT%H:%M").ok())
+        .map(|t| t.time());
+    let (phase, illumination) = moon_phase(date);
+    Astronomy {
+        place: Place { tz_id: resp.timezone, ..place },
+        date,
+        sunrise: time(&d.sunrise),
+        sunset: time(&d.sunset),
+        moon_phase: phase.to_string(),
+        moon_illumination: Some(illumination),
+        ..Astronomy::default()
+    }
+}
+
+/// Skips hours the archive has no temperature for yet, which is the last few days.
+pub fn open_meteo_history(place: Place, resp: OpenMeteoArchive, query: &str) -> Result<WeatherHistory, WeatherError> {
+    let h = resp.hourly;
+    let at = |v: &Vec<Option<f32>>, i: usize| v.get(i).copied().flatten();
+    let hours: Vec<HistoricalHour> = h.time.iter().enumerate()
+        .filter_map(|(i, &time)| Some(HistoricalHour {
+            time_epoch: time,
+            local_time: DateTime::from_timestamp(time + resp.utc_offset_seconds, 0)?.naive_utc(),
+            temp_c: at(&h.temperature_2m, i)?,
+            precip_mm: at(&h.precipitation, i),
+            humidity: at(&h.relative_humidity_2m, i),
+            wind_kph: at(&h.wind_speed_10m, i),
+            condition: h.weather_code.get(i).copied().flatten().map(wmo_condition).unwrap_or_default().to_string(),
+        }))
+        .collect();
+    if hours.is_empty() {
+        return Err(WeatherError::NoData(format!("no recorded weather for {} on those dates yet", query)));
+    }
+    Ok(WeatherHistory { place: Place { tz_id: resp.timezone, ..place }, days: historical_days(hours) })
+}
+
+/// Reads a "lat,lon" query, e.g. "38.8977,-77.0365".
+pub fn parse_coordinates(query: &str) -> Option<(f64, f64)> {
+    let (lat, lon) = query.split_once(',')?;
+    let (lat, lon): (f64, f64) = (lat.trim().parse().ok()?, lon.trim().parse().ok()?);
+    ((-90.0..=90.0).contains(&lat) && (-180.0..=180.0).contains(&lon)).then_some((lat, lon))
+}
+
+/// Resolves a place name or postal code to coordinates. Coordinates are passed straight through.
+/// For "City, Region" queries the first result in that region or country is used.
+pub async fn geocode(http: &HttpClient, geocoding_url: &str, query: &LocationQuery) -> Result<Place, WeatherError> {
+    let unsupported = |feature| WeatherError::Unsupported { provider: "Open-Meteo geocoding", feature };
+    let (name, country, within) = match query {
+        LocationQuery::Coordinates { lat, lon } => {
+            return Ok(Place { name: query.to_string(), lat: *lat, lon: *lon, ..Place::default() });
+        },
+        LocationQuery::PostalCode { code, country } => (code.as_str(), country.as_deref(), None),
+        LocationQuery::City(name) => match name.split_once(',') {
+            Some((name, within)) => (name.trim(), None, Some(within.trim().to_lowercase())),
+            None => (name.as_str(), None, None),
+        },
+        LocationQuery::Iata(_) => return Err(unsupported("airport codes")),
+        LocationQuery::AutoIp => return Err(unsupported("IP address lookups")),
+    };
+    let mut params = vec![("name", name), ("count", if within.is_some() { "10" } else { "1" }), ("format", "json")];
+    if let Some(country) = country {
+        params.push(("countryCode", country));
+    }
+    let resp: GeocodingResponse = http.get_json(endpoint(geocoding_url, "search", &params)?, &query.to_string()).await?;
+    let matches = |r: &GeocodingResult| within.as_ref().is_none_or(|w| {
+        [&r.admin1, &r.country, &r.country_code].iter().any(|f| f.as_deref().is_some_and(|f| f.to_lowercase() == *w))
+    });
+    let result = match resp.results.iter().position(matches) {
+        Some(i) => resp.results.into_iter().nth(i),
+        None => resp.results.into_iter().next(),
+    };
+    match result {
+        Some(r) => Ok(Place {
+            name: r.name,
+            region: r.admin1.unwrap_or_default(),
+            country: r.country.unwrap_or_default(),
+            lat: r.latitude,
+            lon: r.longitude,
+            tz_id: r.timezone.unwrap_or_default(),
+        }),
+        None => Err(WeatherError::LocationNotFound(query.to_string())),
+    }
+}
+
+/// Describes a WMO weather interpretation code, as used by Open-Meteo.
+pub fn wmo_condition(code: i64) -> &'static str {
+    match code {
+        0 => "Clear sky",
+        1 => "Mainly clear",
+        2 => "Partly cloudy",
+        3 => "Overcast",
+        45 | 48 => "Fog",
+        51 | 53 | 55 => "Drizzle",
+        56 | 57 => "Freezing drizzle",
+        61 | 63 | 65 => "Rain",
+        66 | 67 => "Freezing rain",
+        71 | 73 | 75 => "Snow",
+        77 => "Snow grains",
+        80..=82 => "Rain showers",
+        85 | 86 => "Snow showers",
+        95 => "Thunderstorm",
+        96 | 99 => "Thunderstorm with hail",
+        _ => "Unknown",
+    }
+}
+
+#[test]
+fn test_parse_coordinates() {
+    assert_eq!(parse_coordinates("38.8977,-77.0365"), Some((38.8977, -77.0365)));
+    assert_eq!(parse_coordinates(" 10 , 20 "), Some((10.0, 20.0)));
+    assert_eq!(parse_coordinates("91,0"), None);
+    assert_eq!(parse_coordinates("Paris, France"), None);
+    assert_eq!(parse_coordinates("20500"), None);
+}
+
+#[test]
+fn test_open_meteo_observation() {
+    let resp: OpenMeteoResponse = serde_json::from_str(r#"{
+        "latitude": 38.89, "longitude": -77.03, "timezone": "America/New_York", "utc_offset_seconds": -14400,
+        "current": {"time": 1724180400, "interval": 900, "temperature_2m": 24.3, "weather_code": 2, "is_day": 1,
+            "apparent_temperature": 25.9, "wind_speed_10m": 7.2, "wind_direction_10m": 200, "visibility": 24140.0}
+    }"#).unwrap();
+    let place = Place { name: "Washington".to_string(), region: "District of Columbia".to_string(), ..Place::default() };
+    let obs = open_meteo_observation(place, resp);
+    assert_eq!(obs.place.name, "Washington");
+    assert_eq!(obs.place.tz_id, "America/New_York");
+    assert_eq!(obs.temp_c, 24.3);
+    assert_eq!(obs.condition, "Partly cloudy");
+    assert_eq!(obs.icon, Some(ConditionIcon::PartlyCloudy));
+    assert_eq!(obs.observed_epoch, 1724180400);
+    assert_eq!(obs.local_time.unwrap().to_string(), "2024-08-20 15:00:00");
+    assert_eq!(obs.feelslike_c, Some(25.9));
+    assert_eq!(obs.wind_degree, Some(200.0));
+    assert_eq!(obs.vis_km, Some(24.14));
+    assert_eq!(obs.humidity, None);
+}
+
+#[test]
+fn test_open_meteo_forecast() {
+    let resp: OpenMeteoForecast = serde_json::from_str(r#"{
+        "timezone": "America/Juneau", "utc_offset_seconds": -28800,
+        "hourly": {"time": [1723705200, 1723708800, 1723712400], "temperature_2m": [12.0, 10.0, 9.0],
+            "precipitation_probability": [20, null, 60], "weather_code": [3, 3, 61]}
+    }"#).unwrap();
+    let forecast = open_meteo_forecast(Place::default(), resp);
+    assert_eq!(forecast.place.tz_id, "America/Juneau");
+    assert_eq!(forecast.days.len(), 2);
+    assert_eq!(forecast.days[0].date.to_string(), "2024-08-14");
+    assert_eq!(forecast.days[0].hours[0].local_time.to_string(), "2024-08-14 23:00:00");
+    assert_eq!((forecast.days[0].max_c, forecast.days[0].precip_chance), (12.0, Some(20.0)));
+    assert_eq!(forecast.days[1].condition, "Overcast");
+    assert_eq!(forecast.days[1].precip_chance, Some(60.0));
+}
+
+#[test]
+fn test_open_meteo_history() {
+    let resp: OpenMeteoArchive = serde_json::from_str(r#"{
+        "timezone": "America/Juneau", "utc_offset_seconds": -28800,
+        "hourly": {"time": [1722528000, 1722549600, 1722614400], "temperature_2m": [10.0, 16.0, null],
+            "precipitation": [0.5, 1.5, null], "relative_humidity_2m": [90, 70, null], "wind_speed_10m": [5.0, 8.0, null],
+            "weather_code": [61, 61, null]}
+    }"#).unwrap();
+    let history = open_meteo_history(Place::default(), resp, "Juneau").unwrap();
+    assert_eq!(history.days.len(), 1);
+    assert_eq!(history.days[0].date.to_string(), "2024-08-01");
+    assert_eq!(history.days[0].precip_mm, Some(2.0));
+    assert_eq!(history.days[0].condition, "Rain");
+    assert_eq!(history.days[0].hours[1].humidity, Some(70.0));
+
+    let empty: OpenMeteoArchive = serde_json::from_str(r#"{"timezone": "GMT", "utc_offset_seconds": 0,
+        "hourly": {"time": [1722528000], "temperature_2m": [null]}}"#).unwrap();
+    assert!(matches!(open_meteo_history(Place::default(), empty, "Juneau"), Err(WeatherError::NoData(_))));
+}
+
+#[test]
+fn test_open_meteo_astronomy() {
+    let resp: OpenMeteoSun = serde_json::from_str(r#"{"timezone": "America/Juneau", "utc_offset_seconds": -28800,
+        "daily": {"time": ["2024-08-14"], "sunrise": ["2024-08-14T05:08"], "sunset": ["2024-08-14T21:23"]}}"#).unwrap();
+    let date = NaiveDate::from_ymd_opt(2024, 8, 14).unwrap();
+    let astro = open_meteo_astronomy(Place::default(), date, resp);
+    assert_eq!(astro.place.tz_id, "America/Juneau");
+    assert_eq!(astro.sunrise.unwrap().to_string(), "05:08:00");
+    assert_eq!(astro.sunset.unwrap().to_string(), "21:23:00");
+    assert_eq!((astro.moonrise, astro.moonset), (None, None));
+    assert_eq!(astro.moon_phase, "Waxing Gibbous");
+}
+
+#[test]
+fn test_geocoding_response() {
+    let resp: GeocodingResponse = serde_json::from_str(r#"{"generationtime_ms": 0.5}"#).unwrap();
+    assert!(resp.results.is_empty());
+    let resp: GeocodingResponse = serde_json::from_str(r#"{"results": [{"id": 1, "name": "Juneau", "latitude": 58.3,
+        "longitude": -134.42, "country": "United States", "admin1": "Alaska", "timezone": "America/Juneau"}]}"#).unwrap();
+    assert_eq!(resp.results[0].admin1.as_deref(), Some("Alaska"));
+}
@@ -0,0 +1,209 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Serialize, Deserialize};
+
+use super::parse_coordinates;
+
+/// What a location query names, as picked with a CLI option or the GUI's input mode.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryKind {
+    /// Work out the kind from the input itself.
+    #[default]
+    Auto,
+    PostalCode,
+    City,
+    Coordinates,
+    /// A three letter IATA airport code, e.g. "JFK".
+    Iata,
+    /// Wherever the request comes from, as located by the provider.
+    AutoIp,
+}
+
+impl QueryKind {
+    pub const ALL: [QueryKind; 6] =
+        [QueryKind::Auto, QueryKind::PostalCode, QueryKind::City, QueryKind::Coordinates, QueryKind::Iata, QueryKind::AutoIp];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            QueryKind::Auto => "Any",
+            QueryKind::PostalCode => "Postal code",
+            QueryKind::City => "City",
+            QueryKind::Coordinates => "Latitude, longitude",
+            QueryKind::Iata => "Airport (IATA)",
+            QueryKind::AutoIp => "My location (IP)",
+        }
+    }
+}
+
+/// A validated location to look weather up for.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum LocationQuery {
+    /// A postal code, with the ISO 3166 two letter country code if known, e.g. "75001" in "FR".
+    PostalCode { code: String, country: Option<String> },
+    /// A place name, optionally followed by its region or country, e.g. "Paris, France".
+    City(String),
+    Coordinates { lat: f64, lon: f64 },
+    Iata(String),
+    AutoIp,
+}
+
+impl LocationQuery {
+    /// Validates `input` as a query of the given kind. `country` only applies to postal codes, including detected ones.
+    pub fn build(kind: QueryKind, input: &str, country: Option<&str>) -> Result<LocationQuery, String> {
+        let input = input.trim();
+        match kind {
+            QueryKind::Auto => match input.parse()? {
+                LocationQuery::PostalCode { code, .. } if country.is_some() => LocationQuery::postal_code(&code, country),
+                query => Ok(query),
+            },
+            QueryKind::PostalCode => LocationQuery::postal_code(input, country),
+            QueryKind::City => LocationQuery::city(input),
+            QueryKind::Coordinates => parse_coordinates(input)
+                .map(|(lat, lon)| LocationQuery::Coordinates { lat, lon })
+                .ok_or(format!("{} is not a latitude and longitude (ex: 38.8977,-77.0365)", input)),
+            QueryKind::Iata => LocationQuery::iata(input),
+            QueryKind::AutoIp => Ok(LocationQuery::AutoIp),
+        }
+    }
+
+    pub fn postal_code(code: &str, country: Option<&str>) -> Result<LocationQuery, String> {
+        let code = code.trim().to_uppercase();
+        let country = match country.map(str::trim).filter(|c| !c.is_empty()) {
+            Some(c) if c.len() == 2 && c.chars().all(|c| c.is_ascii_alphabetic()) => Some(c.to_uppercase()),
+            Some(c) => return Err(format!("{} is not a two letter country code (ex: US, GB, FR)", c)),
+            None => None,
+        };
+        let valid = match country.as_deref() {
+            Some("US") => is_us_zip(&code),
+            Some("CA") => is_canadian_postal_code(&code),
+            _ => !code.is_empty()
+                && code.len() <= 10
+                && code.chars().any(|c| c.is_ascii_digit())
+                && code.chars().all(|c| c.is_ascii_alphanumeric() || c == ' ' || c == '-'),
+        };
+        match valid {
+            true => Ok(LocationQuery::PostalCode { code, country }),
+            false => Err(format!("{} is not a valid postal code{}", code, country.map(|c| format!(" for {}", c)).unwrap_or_default())),
+        }
+    }
+
+    pub fn city(name: &str) -> Result<LocationQuery, String> {
+        let name = name.trim();
+        if name.is_empty() || !name.chars().any(char::is_alphabetic) {
+            return Err(format!("{:?} is not a place name", name));
+        }
+        Ok(LocationQuery::City(name.to_string()))
+    }
+
+    pub fn iata(code: &str) -> Result<LocationQuery, String> {
+        let code = code.trim();
+        match code.len() == 3 && code.chars().all(|c| c.is_ascii_alphabetic()) {
+            true => Ok(LocationQuery::Iata(code.to_uppercase())),
+            false => Err(format!("{} is not a three letter airport code (ex: JFK)", code)),
+        }
+    }
+
+    /// The `q=` parameter WeatherAPI expects for this query.
+    pub fn weatherapi_q(&self) -> String {
+        match self {
+            LocationQuery::PostalCode { code, country: Some(country) } => format!("{},{}", code, country),
+            LocationQuery::PostalCode { code, country: None } => code.to_string(),
+            LocationQuery::City(name) => name.to_string(),
+            LocationQuery::Coordinates { lat, lon } => format!("{},{}", lat, lon),
+            LocationQuery::Iata(code) => format!("iata:{}", code),
+            LocationQuery::AutoIp => "auto:ip".to_string(),
+        }
+    }
+}
+
+/// Guesses the kind of query: "auto:ip", "iata:JFK", coordinates, anything with a digit as a postal code, otherwise a city.
+impl FromStr for LocationQuery {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<LocationQuery, String> {
+        let input = input.trim();
+        let lower = input.to_lowercase();
+        if input.is_empty() {
+            Err("enter a location (ex: 20500, Paris, 38.8977,-77.0365 or iata:JFK)".to_string())
+        } else if lower == "auto:ip" || lower == "auto" {
+            Ok(LocationQuery::AutoIp)
+        } else if let Some(code) = lower.strip_prefix("iata:") {
+            LocationQuery::iata(code)
+        } else if let Some((lat, lon)) = parse_coordinates(input) {
+            Ok(LocationQuery::Coordinates { lat, lon })
+        } else if input.chars().any(|c| c.is_ascii_digit()) {
+            LocationQuery::postal_code(input, None)
+        } else {
+            LocationQuery::city(input)
+        }
+    }
+}
+
+impl fmt::Display for LocationQuery {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LocationQuery::PostalCode { code, country: Some(country) } => write!(f, "{} ({})", code, country),
+            LocationQuery::Iata(code) => write!(f, "iata:{}", code),
+            _ => write!(f, "{}", self.weatherapi_q()),
+        }
+    }
+}
+
+fn is_us_zip(code: &str) -> bool {
+    let (zip, plus4) = code.split_once('-').unwrap_or((code, "0000"));
+    zip.len() == 5 && plus4.len() == 4 && zip.chars().chain(plus4.chars()).all(|c| c.is_ascii_digit())
+}
+
+/// "A1A 1A1", with or without the space.
+fn is_canadian_postal_code(code: &str) -> bool {
+    let code: Vec<char> = code.chars().filter(|c| *c != ' ').collect();
+    code.len() == 6 && code.iter().enumerate().all(|(i, c)| match i % 2 {
+        0 => c.is_ascii_alphabetic(),
+        _ => c.is_ascii_digit(),
+    })
+}
+
+#[test]
+fn test_parse_query() {
+    assert_eq!("20500".parse(), Ok(LocationQuery::PostalCode { code: "20500".to_string(), country: None }));
+    assert_eq!("sw1a 1aa".parse(), Ok(LocationQuery::PostalCode { code: "SW1A 1AA".to_string(), country: None }));
+    assert_eq!("Paris, France".parse(), Ok(LocationQuery::City("Paris, France".to_string())));
+    assert_eq!("38.8977,-77.0365".parse(), Ok(LocationQuery::Coordinates { lat: 38.8977, lon: -77.0365 }));
+    assert_eq!("iata:jfk".parse(), Ok(LocationQuery::Iata("JFK".to_string())));
+    assert_eq!("auto:ip".parse(), Ok(LocationQuery::AutoIp));
+    assert!("".parse::<LocationQuery>().is_err());
+    assert!("iata:JFKX".parse::<LocationQuery>().is_err());
+}
+
+#[test]
+fn test_build_query() {
+    assert_eq!(
+        LocationQuery::build(QueryKind::PostalCode, "75001", Some("fr")),
+        Ok(LocationQuery::PostalCode { code: "75001".to_string(), country: Some("FR".to_string()) }));
+    assert!(LocationQuery::build(QueryKind::PostalCode, "2050", Some("US")).is_err());
+    assert!(LocationQuery::build(QueryKind::PostalCode, "20500-0003", Some("US")).is_ok());
+    assert!(LocationQuery::build(QueryKind::PostalCode, "K1A 0B1", Some("CA")).is_ok());
+    assert!(LocationQuery::build(QueryKind::PostalCode, "K1A", Some("CA")).is_err());
+    assert!(LocationQuery::build(QueryKind::PostalCode, "20500", Some("USA")).is_err());
+    assert!(LocationQuery::build(QueryKind::PostalCode, "Juneau, AK", None).is_err());
+    assert!(LocationQuery::build(QueryKind::City, "12345", None).is_err());
+    assert!(LocationQuery::build(QueryKind::Coordinates, "91,0", None).is_err());
+    assert_eq!(LocationQuery::build(QueryKind::AutoIp, "", None), Ok(LocationQuery::AutoIp));
+    assert_eq!(
+        LocationQuery::build(QueryKind::Auto, "sw1a 1aa", Some("GB")),
+        Ok(LocationQuery::PostalCode { code: "SW1A 1AA".to_string(), country: Some("GB".to_string()) }));
+    assert_eq!(LocationQuery::build(QueryKind::Auto, "Paris", Some("FR")), Ok(LocationQuery::City("Paris".to_string())));
+}
+
+#[test]
+fn test_weatherapi_q() {
+    let q = |s: &str| s.parse::<LocationQuery>().unwrap().weatherapi_q();
+    assert_eq!(q("20500"), "20500");
+    assert_eq!(q("Juneau, AK"), "Juneau, AK");
+    assert_eq!(q("iata:LHR"), "iata:LHR");
+    assert_eq!(q("auto"), "auto:ip");
+    assert_eq!(LocationQuery::postal_code("75001", Some("FR")).unwrap().weatherapi_q(), "75001,FR");
+    assert_eq!(LocationQuery::postal_code("75001", Some("FR")).unwrap().to_string(), "75001 (FR)");
+}
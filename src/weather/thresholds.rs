@@ -0,0 +1,401 @@
+use std::collections::BTreeMap;
+use std::{fmt, fs, io};
+
+use serde::{Serialize, Deserialize};
+
+use crate::history::write_atomic;
+use crate::{from_celsius, Config, Scale};
+
+use super::details::round;
+use super::{cache_key, place_name, CachedObservation, HttpClient, LocationQuery, Observation, ObservationSource, QueryKind, WeatherError};
+
+/// Which side of its threshold a rule watches for.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Comparison {
+    #[default]
+    Above,
+    Below,
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Comparison::Above => write!(f, "above"),
+            Comparison::Below => write!(f, "below"),
+        }
+    }
+}
+
+/// A temperature in any scale, written in the config as e.g. "30C", "86 F" or "-5°C".
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Threshold {
+    pub value: f32,
+    pub scale: Scale,
+}
+
+impl Default for Threshold {
+    fn default() -> Self {
+        Threshold { value: 0.0, scale: Scale::Celsius }
+    }
+}
+
+impl TryFrom<String> for Threshold {
+    type Error = String;
+
+    fn try_from(input: String) -> Result<Threshold, String> {
+        let invalid = || format!("invalid threshold {} (ex: 30C, 86F, 300K)", input);
+        let split = input.find(|c: char| c.is_alphabetic() || c == '°').ok_or_else(invalid)?;
+        let (number, scale) = input.split_at(split);
+        let value = number.trim().parse().map_err(|_| invalid())?;
+        let scale = Scale::from_name(scale.trim_start_matches('°')).ok_or_else(invalid)?;
+        Ok(Threshold { value, scale })
+    }
+}
+
+impl From<Threshold> for String {
+    fn from(threshold: Threshold) -> String {
+        threshold.to_string()
+    }
+}
+
+impl fmt::Display for Threshold {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", self.value, self.scale.symbol())
+    }
+}
+
+/// Where a triggered rule is reported, under `notify` in a rule, e.g. `{ type = "webhook", url = "..." }`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Notifier {
+    /// Prints the message on standard output.
+    Stdout,
+    /// Shows a desktop notification with `notify-send`, or the system's own on macOS and Windows.
+    Desktop,
+    /// POSTs the rule, place and temperature as JSON to `url`.
+    Webhook { url: String },
+    /// Runs `command` with `args`. The message, rule, place and temperature are passed in
+    /// `TEMPERATURE_CONVERTER_*` environment variables.
+    Command { command: String, #[serde(default)] args: Vec<String> },
+}
+
+/// A temperature to be told about at a location, under `[[thresholds.rules]]`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ThresholdRule {
+    /// Names the rule in messages. Defaults to the location, comparison and threshold.
+    pub name: String,
+    /// A location as entered on the command line, e.g. "99801" or "Juneau". It also matches lookups of
+    /// other queries that resolve to a place of that name.
+    pub location: String,
+    pub comparison: Comparison,
+    pub threshold: Threshold,
+    /// How far back past the threshold, in the threshold's scale, the temperature must go before the rule can trigger again.
+    pub hysteresis: f32,
+    /// The least time between two notifications from the rule.
+    pub cooldown_minutes: u64,
+    pub notify: Vec<Notifier>,
+}
+
+impl Default for ThresholdRule {
+    fn default() -> Self {
+        ThresholdRule {
+            name: "".to_string(),
+            location: "".to_string(),
+            comparison: Comparison::Above,
+            threshold: Threshold::default(),
+            hysteresis: 0.0,
+            cooldown_minutes: 60,
+            notify: vec![Notifier::Stdout],
+        }
+    }
+}
+
+impl ThresholdRule {
+    /// The rule's name, or e.g. "99801 above 25°C" for an unnamed rule.
+    pub fn label(&self) -> String {
+        match self.name.trim() {
+            "" => format!("{} {} {}", self.location.trim(), self.comparison, self.threshold),
+            name => name.to_string(),
+        }
+    }
+
+    /// What the rule's state is kept under, so rules sharing a name don't share hysteresis and cooldowns.
+    pub fn state_key(&self) -> String {
+        format!("{}|{}|{}|{}", self.label(), self.location.trim(), self.comparison, self.threshold)
+    }
+
+    /// Whether the rule is about the place `obs` was looked up for with `query`.
+    pub fn applies_to(&self, query: &LocationQuery, obs: &Observation) -> bool {
+        let location = self.location.trim().to_lowercase();
+        let by_query = LocationQuery::build(QueryKind::Auto, &location, None)
+            .is_ok_and(|q| cache_key(Default::default(), &q) == cache_key(Default::default(), query));
+        by_query || location == obs.place.name.to_lowercase() || location == place_name(&obs.place).to_lowercase()
+    }
+
+    /// Takes a temperature measured at `now` (a Unix timestamp) and returns whether to notify. The rule triggers when
+    /// the temperature crosses the threshold, then waits for it to come back past the threshold by `hysteresis`
+    /// before it can trigger again. A crossing within `cooldown_minutes` of the last notification is held back, and
+    /// reported by the first evaluation after the cooldown if the temperature is still past the threshold.
+    pub fn evaluate(&self, state: &mut RuleState, temp_c: f32, now: i64) -> bool {
+        let temp = from_celsius(temp_c, self.threshold.scale);
+        let (crossed, cleared) = match self.comparison {
+            Comparison::Above => (temp > self.threshold.value, temp <= self.threshold.value - self.hysteresis),
+            Comparison::Below => (temp < self.threshold.value, temp >= self.threshold.value + self.hysteresis),
+        };
+        if state.triggered {
+            state.triggered = !cleared;
+            return false;
+        }
+        if !crossed {
+            return false;
+        }
+        if state.last_notified.is_some_and(|at| now - at < self.cooldown_minutes as i64 * 60) {
+            return false;
+        }
+        state.triggered = true;
+        state.last_notified = Some(now);
+        true
+    }
+
+    /// The notification text, e.g. "Warm: Juneau, Alaska is 11.1°C, above 10°C".
+    pub fn message(&self, obs: &Observation) -> String {
+        let temp = round(from_celsius(obs.temp_c, self.threshold.scale));
+        format!("{}: {} is {}{}, {} {}", self.label(), place_name(&obs.place), temp, self.threshold.scale.symbol(), self.comparison, self.threshold)
+    }
+}
+
+/// Whether a rule has triggered and not yet cleared, and when it last notified.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RuleState {
+    pub triggered: bool,
+    pub last_notified: Option<i64>,
+}
+
+/// The state of every rule, kept on disk between runs so hysteresis and cooldowns hold across lookups.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ThresholdState {
+    pub rules: BTreeMap<String, RuleState>,
+}
+
+impl ThresholdState {
+    /// Reads the state file, starting with every rule clear if it is missing or can't be read.
+    pub fn load(path: &str) -> ThresholdState {
+        fs::read_to_string(path).ok().and_then(|t| serde_json::from_str(&t).ok()).unwrap_or_default()
+    }
+
+    /// Writes the state file through a temporary file, as the CLI, GUI and daemon may all save it at once.
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        write_atomic(path, serde_json::to_string(self)?.as_bytes())
+    }
+}
+
+/// A rule that triggered, with any notifiers that failed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ThresholdEvent {
+    pub rule: String,
+    pub message: String,
+    pub failures: Vec<String>,
+}
+
+/// Evaluates every configured rule against the observations just fetched, running the notifiers of those that
+/// trigger. Observations from the cache or the use history were already seen when they were fetched, so they are skipped.
+pub async fn check_thresholds(config: &Config, found: &[(&LocationQuery, &CachedObservation)]) -> Vec<ThresholdEvent> {
+    let rules = &config.thresholds.rules;
+    let live: Vec<_> = found.iter().filter(|(_, c)| c.source == ObservationSource::Live).collect();
+    if rules.is_empty() || live.is_empty() {
+        return Vec::new();
+    }
+    let path = config.thresholds.state_file();
+    let mut state = ThresholdState::load(&path);
+    let mut triggered = Vec::new();
+    for rule in rules {
+        for (_, cached) in live.iter().filter(|(q, c)| rule.applies_to(q, &c.observation)) {
+            let rule_state = state.rules.entry(rule.state_key()).or_default();
+            if rule.evaluate(rule_state, cached.observation.temp_c, cached.fetched_epoch) {
+                triggered.push((rule, &cached.observation));
+            }
+        }
+    }
+    // State that can't be written only means a rule may notify again next time.
+    let _ = state.save(&path);
+
+    let client = HttpClient::shared(&config.weather.http);
+    let mut events = Vec::new();
+    for (rule, obs) in triggered {
+        let message = rule.message(obs);
+        let mut failures = Vec::new();
+        for notifier in &rule.notify {
+            if let Err(e) = notify(notifier, rule, obs, &message, client.as_ref()).await {
+                failures.push(e);
+            }
+        }
+        events.push(ThresholdEvent { rule: rule.label(), message, failures });
+    }
+    events
+}
+
+/// Sends one notification, describing what went wrong if it couldn't be sent. `client` is only needed for webhooks.
+pub async fn notify(notifier: &Notifier, rule: &ThresholdRule, obs: &Observation, message: &str, client: Result<&HttpClient, &WeatherError>) -> Result<(), String> {
+    let temp = round(from_celsius(obs.temp_c, rule.threshold.scale));
+    match notifier {
+        Notifier::Stdout => {
+            println!("{}", message);
+            Ok(())
+        },
+        Notifier::Desktop => {
+            let title = "Temperature threshold";
+            let mut command = if cfg!(target_os = "macos") {
+                let mut c = tokio::process::Command::new("osascript");
+                c.args(["-e", &format!("display notification {:?} with title {:?}", message, title)]);
+                c
+            } else if cfg!(windows) {
+                let mut c = tokio::process::Command::new("msg");
+                c.args(["*", message]);
+                c
+            } else {
+                let mut c = tokio::process::Command::new("notify-send");
+                c.args([title, message]);
+                c
+            };
+            run(&mut command, "desktop notification").await
+        },
+        Notifier::Webhook { url } => {
+            let url = reqwest::Url::parse(url).map_err(|e| format!("webhook {}: {}", url, e))?;
+            let body = serde_json::json!({
+                "rule": rule.label(),
+                "location": place_name(&obs.place),
+                "comparison": rule.comparison,
+                "threshold": rule.threshold,
+                "temperature": temp,
+                "temp_c": obs.temp_c,
+                "observed_epoch": obs.observed_epoch,
+                "message": message,
+            });
+            let client = client.map_err(|e| format!("webhook {}: {}", url, e))?;
+            let resp = client.send(client.post(url.clone()).json(&body)).await.map_err(|e| format!("webhook {}: {}", url, e))?;
+            match resp.status() {
+                s if s.is_success() => Ok(()),
+                s => Err(format!("webhook {}: HTTP {}", url, s.as_u16())),
+            }
+        },
+        Notifier::Command { command, args } => {
+            let mut c = tokio::process::Command::new(command);
+            c.args(args)
+                .env("TEMPERATURE_CONVERTER_MESSAGE", message)
+                .env("TEMPERATURE_CONVERTER_RULE", rule.label())
+                .env("TEMPERATURE_CONVERTER_LOCATION", place_name(&obs.place))
+                .env("TEMPERATURE_CONVERTER_TEMPERATURE", format!("{}{}", temp, rule.threshold.scale.symbol()));
+            run(&mut c, command).await
+        },
+    }
+}
+
+/// Runs a notifier's command to completion, failing if it can't be started or exits unsuccessfully.
+async fn run(command: &mut tokio::process::Command, name: &str) -> Result<(), String> {
+    let output = command.stdout(std::process::Stdio::null()).output().await.map_err(|e| format!("{}: {}", name, e))?;
+    match output.status.success() {
+        true => Ok(()),
+        false => Err(format!("{}: {} {}", name, output.status, String::from_utf8_lossy(&output.stderr).trim())),
+    }
+}
+
+#[cfg(test)]
+fn juneau(temp_c: f32) -> Observation {
+    use super::Place;
+    let place = Place { name: "Juneau".to_string(), region: "Alaska".to_string(), ..Place::default() };
+    Observation { place, temp_c, ..Observation::default() }
+}
+
+#[test]
+fn test_threshold() {
+    assert_eq!(Threshold::try_from("30C".to_string()), Ok(Threshold { value: 30.0, scale: Scale::Celsius }));
+    assert_eq!(Threshold::try_from("86 F".to_string()), Ok(Threshold { value: 86.0, scale: Scale::Fahrenheit }));
+    assert_eq!(Threshold::try_from("-5°C".to_string()), Ok(Threshold { value: -5.0, scale: Scale::Celsius }));
+    assert_eq!(Threshold::try_from("300kelvin".to_string()), Ok(Threshold { value: 300.0, scale: Scale::Kelvin }));
+    assert!(Threshold::try_from("30".to_string()).is_err());
+    assert!(Threshold::try_from("warm".to_string()).is_err());
+    assert_eq!(String::from(Threshold { value: 86.0, scale: Scale::Fahrenheit }), "86°F");
+}
+
+#[test]
+fn test_rule_hysteresis() {
+    let rule = ThresholdRule {
+        location: "99801".to_string(),
+        threshold: Threshold { value: 10.0, scale: Scale::Celsius },
+        hysteresis: 1.0,
+        cooldown_minutes: 0,
+        ..ThresholdRule::default()
+    };
+    let mut state = RuleState::default();
+    let temps = [9.0, 10.5, 11.0, 9.5, 10.5, 8.9, 10.1];
+    let fired: Vec<bool> = temps.iter().map(|&t| rule.evaluate(&mut state, t, 0)).collect();
+    // Dipping to 9.5 isn't far enough back to trigger again, 8.9 is.
+    assert_eq!(fired, vec![false, true, false, false, false, false, true]);
+    assert_eq!(rule.label(), "99801 above 10°C");
+    assert_eq!(rule.message(&juneau(11.06)), "99801 above 10°C: Juneau, Alaska is 11.1°C, above 10°C");
+
+    let below = ThresholdRule {
+        name: "Freezing".to_string(),
+        comparison: Comparison::Below,
+        threshold: Threshold { value: 32.0, scale: Scale::Fahrenheit },
+        ..ThresholdRule::default()
+    };
+    let mut state = RuleState::default();
+    assert!(below.evaluate(&mut state, -1.0, 0));
+    assert_eq!(below.message(&juneau(-1.0)), "Freezing: Juneau, Alaska is 30.2°F, below 32°F");
+}
+
+#[test]
+fn test_rule_cooldown() {
+    let rule = ThresholdRule { threshold: Threshold { value: 10.0, scale: Scale::Celsius }, cooldown_minutes: 60, ..ThresholdRule::default() };
+    let mut state = RuleState::default();
+    assert!(rule.evaluate(&mut state, 11.0, 0));
+    assert!(!rule.evaluate(&mut state, 9.0, 600));
+    // Crossed again within the hour, so it is held back until the hour is up and reported then if still above.
+    assert!(!rule.evaluate(&mut state, 11.0, 1200));
+    assert!(!state.triggered);
+    assert!(rule.evaluate(&mut state, 11.0, 3600));
+    assert_eq!(state.last_notified, Some(3600));
+    assert!(!rule.evaluate(&mut state, 11.0, 3700));
+    // A breach that ends within the cooldown is never reported.
+    assert!(!rule.evaluate(&mut state, 9.0, 3800));
+    assert!(!rule.evaluate(&mut state, 11.0, 4000));
+    assert!(!rule.evaluate(&mut state, 9.0, 4200));
+    assert!(!rule.evaluate(&mut state, 9.0, 7200));
+    assert!(rule.evaluate(&mut state, 11.0, 7300));
+}
+
+#[test]
+fn test_rule_state_key() {
+    let rule = |name: &str, location: &str, threshold| ThresholdRule {
+        name: name.to_string(),
+        location: location.to_string(),
+        threshold: Threshold { value: threshold, scale: Scale::Celsius },
+        ..ThresholdRule::default()
+    };
+    assert_eq!(rule("Warm", "99801", 10.0).state_key(), "Warm|99801|above|10°C");
+    assert_ne!(rule("Warm", "99801", 10.0).state_key(), rule("Warm", "99801", 15.0).state_key());
+    assert_ne!(rule("Warm", "99801", 10.0).state_key(), rule("Warm", "20500", 10.0).state_key());
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("rules.json").to_string_lossy().to_string();
+    let mut state = ThresholdState::default();
+    state.rules.insert(rule("Warm", "99801", 10.0).state_key(), RuleState { triggered: true, last_notified: Some(60) });
+    state.save(&path).unwrap();
+    assert_eq!(ThresholdState::load(&path), state);
+    assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+}
+
+#[test]
+fn test_rule_applies_to() {
+    let rule = |location: &str| ThresholdRule { location: location.to_string(), ..ThresholdRule::default() };
+    let zip = LocationQuery::postal_code("99801", None).unwrap();
+    assert!(rule("99801").applies_to(&zip, &juneau(0.0)));
+    assert!(rule("juneau").applies_to(&zip, &juneau(0.0)));
+    assert!(rule("Juneau, Alaska").applies_to(&zip, &juneau(0.0)));
+    assert!(rule("Juneau").applies_to(&LocationQuery::City("JUNEAU".to_string()), &Observation::default()));
+    assert!(!rule("20500").applies_to(&zip, &juneau(0.0)));
+}
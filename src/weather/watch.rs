@@ -0,0 +1,122 @@
+use std::time::Duration;
+
+use crate::{from_celsius, Scale};
+
+use super::details::round;
+use super::Observation;
+
+/// Reads a poll interval such as "30s", "5m", "1h" or "500ms". A bare number is seconds.
+pub fn parse_interval(input: &str) -> Result<Duration, String> {
+    let input = input.trim();
+    let split = input.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(input.len());
+    let (number, unit) = input.split_at(split);
+    let invalid = || format!("invalid interval {} (use e.g. 30s, 5m or 1h)", input);
+    let value: f64 = number.parse().map_err(|_| invalid())?;
+    let secs = match unit.trim() {
+        "ms" => value / 1000.0,
+        "" | "s" | "sec" | "secs" => value,
+        "m" | "min" | "mins" => value * 60.0,
+        "h" | "hr" | "hrs" => value * 3600.0,
+        _ => return Err(invalid()),
+    };
+    if secs <= 0.0 {
+        return Err(format!("invalid interval {}, it must be more than 0", input));
+    }
+    Duration::try_from_secs_f64(secs).map_err(|_| invalid())
+}
+
+/// What to do with one poll of a watched location.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WatchUpdate {
+    /// The line to print, if the poll is worth showing.
+    pub line: Option<String>,
+    /// Whether to record the poll in the use history.
+    pub log: bool,
+}
+
+/// Follows the polls of one location, deciding which to print and which to record.
+#[derive(Debug, Clone)]
+pub struct Watcher {
+    scale: Scale,
+    every_tick: bool,
+    log_interval_secs: i64,
+    last: Option<Observation>,
+    last_logged: Option<i64>,
+}
+
+impl Watcher {
+    /// Temperatures are shown in `scale`. Polls are recorded at most every `log_interval_secs`, the first one always.
+    pub fn new(scale: Scale, every_tick: bool, log_interval_secs: u64) -> Watcher {
+        Watcher { scale, every_tick, log_interval_secs: log_interval_secs as i64, last: None, last_logged: None }
+    }
+
+    /// Takes the observation polled at `now` (a Unix timestamp). It is printed if the temperature or condition changed
+    /// since the last poll, or always with `every_tick`, along with the trend since the last poll.
+    pub fn sample(&mut self, obs: &Observation, now: i64) -> WatchUpdate {
+        let changed = self.last.as_ref()
+            .is_none_or(|last| round(last.temp_c) != round(obs.temp_c) || last.condition != obs.condition);
+        let line = (changed || self.every_tick).then(|| {
+            let temp = round(from_celsius(obs.temp_c, self.scale));
+            let trend = self.last.as_ref().map(|last| format!(" ({})", trend(last, obs, self.scale))).unwrap_or_default();
+            format!("{}, {}: {}{}, {}{}", obs.place.name, obs.place.region, temp, self.scale.symbol(), obs.condition, trend)
+        });
+        let log = self.last_logged.is_none_or(|at| now - at >= self.log_interval_secs);
+        if log {
+            self.last_logged = Some(now);
+        }
+        self.last = Some(obs.clone());
+        WatchUpdate { line, log }
+    }
+}
+
+/// How the temperature and condition moved between two polls, e.g. "+0.5°C since last sample, was Overcast".
+pub fn trend(last: &Observation, next: &Observation, scale: Scale) -> String {
+    let change = round(from_celsius(next.temp_c, scale) - from_celsius(last.temp_c, scale));
+    let temp = match change {
+        c if c > 0.0 => format!("+{}{} since last sample", c, scale.symbol()),
+        c if c < 0.0 => format!("{}{} since last sample", c, scale.symbol()),
+        _ => "steady since last sample".to_string(),
+    };
+    if last.condition == next.condition {
+        temp
+    } else {
+        format!("{}, was {}", temp, last.condition)
+    }
+}
+
+#[test]
+fn test_parse_interval() {
+    assert_eq!(parse_interval("30s"), Ok(Duration::from_secs(30)));
+    assert_eq!(parse_interval("5m"), Ok(Duration::from_secs(300)));
+    assert_eq!(parse_interval("1.5h"), Ok(Duration::from_secs(5400)));
+    assert_eq!(parse_interval("250ms"), Ok(Duration::from_millis(250)));
+    assert_eq!(parse_interval("90"), Ok(Duration::from_secs(90)));
+    assert!(parse_interval("0s").is_err());
+    assert!(parse_interval("soon").is_err());
+    assert!(parse_interval("5 days").is_err());
+    assert_eq!(parse_interval("99999999999999999999999"), Err("invalid interval 99999999999999999999999 (use e.g. 30s, 5m or 1h)".to_string()));
+    assert!(parse_interval("99999999999999999h").is_err());
+}
+
+#[test]
+fn test_watcher() {
+    use super::Place;
+    let place = Place { name: "Juneau".to_string(), region: "Alaska".to_string(), ..Place::default() };
+    let obs = |temp_c: f32, condition: &str| Observation {
+        place: place.clone(), temp_c, condition: condition.to_string(), ..Observation::default()
+    };
+    let mut watcher = Watcher::new(Scale::Celsius, false, 600);
+    assert_eq!(watcher.sample(&obs(11.1, "Overcast"), 0), WatchUpdate { line: Some("Juneau, Alaska: 11.1°C, Overcast".to_string()), log: true });
+    assert_eq!(watcher.sample(&obs(11.1, "Overcast"), 60), WatchUpdate { line: None, log: false });
+    assert_eq!(
+        watcher.sample(&obs(10.6, "Light rain"), 120).line.unwrap(),
+        "Juneau, Alaska: 10.6°C, Light rain (-0.5°C since last sample, was Overcast)");
+    assert!(watcher.sample(&obs(10.6, "Light rain"), 600).log);
+
+    let mut every = Watcher::new(Scale::Fahrenheit, true, 0);
+    every.sample(&obs(10.0, "Overcast"), 0);
+    assert_eq!(
+        every.sample(&obs(10.0, "Overcast"), 1),
+        WatchUpdate { line: Some("Juneau, Alaska: 50°F, Overcast (steady since last sample)".to_string()), log: true });
+    assert_eq!(every.sample(&obs(11.0, "Overcast"), 2).line.unwrap(), "Juneau, Alaska: 51.8°F, Overcast (+1.8°F since last sample)");
+}
@@ -0,0 +1,563 @@
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+
+use chrono_tz::Tz;
+
+use serde::{Serialize, Deserialize};
+
+use crate::config::WeatherConfig;
+use crate::{parse_tz, zone_time};
+
+use super::{
+    endpoint, parse_alert_time, parse_clock_time, split_areas, AirQuality, AlertSeverity, Astronomy, ConditionIcon, AlertUrgency, DailyForecast, HttpClient, LocationQuery, Forecast,
+    HistoricalDay, HistoricalHour, HourlyForecast, Observation, Place, WeatherAlert, WeatherAlerts, WeatherError, WeatherFuture,
+    WeatherHistory, WeatherProvider,
+};
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Todo {
+    pub location: Location,
+    pub current: Current,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Location {
+    pub name: String,
+    pub region: String,
+    pub country: String,
+    pub lat: f64,
+    pub lon: f64,
+    pub tz_id: String,
+    pub localtime_epoch: i64,
+    pub localtime: String,
+}
+
+impl Location {
+    /// The time at the location when the response was sent, from `localtime_epoch` and `tz_id`.
+    /// `localtime` itself is the same moment without its zone, e.g. "2024-08-14 8:56".
+    pub fn local_datetime(&self) -> Option<DateTime<Tz>> {
+        zone_time(self.localtime_epoch, parse_tz(&self.tz_id)?)
+    }
+
+    /// `localtime` read as is, for when `tz_id` isn't a zone chrono-tz knows.
+    fn naive_localtime(&self) -> Option<NaiveDateTime> {
+        NaiveDateTime::parse_from_str(&self.localtime, "%Y-%m-%d %H:%M").ok()
+    }
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Current {
+    pub last_updated_epoch: i64,
+    pub last_updated: String,
+    pub temp_c: f32,
+    pub temp_f: f32,
+    pub is_day: i64,
+    pub condition: Condition,
+    pub wind_mph: f64,
+    pub wind_kph: f64,
+    pub wind_degree: i64,
+    pub wind_dir: String,
+    pub pressure_mb: f64,
+    pub pressure_in: f64,
+    pub precip_mm: f64,
+    pub precip_in: f64,
+    pub humidity: i64,
+    pub cloud: i64,
+    pub feelslike_c: f64,
+    pub feelslike_f: f64,
+    pub windchill_c: f64,
+    pub windchill_f: f64,
+    pub heatindex_c: f64,
+    pub heatindex_f: f64,
+    pub dewpoint_c: f64,
+    pub dewpoint_f: f64,
+    pub vis_km: f64,
+    pub vis_miles: f64,
+    pub uv: f64,
+    pub gust_mph: f64,
+    pub gust_kph: f64,
+    /// Only sent when asked for with `aqi=yes`.
+    #[serde(default)]
+    pub air_quality: Option<AirQualityJson>,
+}
+
+/// Concentrations are in µg/m³. Any of them can be missing for a place without nearby measurements.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AirQualityJson {
+    pub co: Option<f64>,
+    pub no2: Option<f64>,
+    pub o3: Option<f64>,
+    pub so2: Option<f64>,
+    pub pm2_5: Option<f64>,
+    pub pm10: Option<f64>,
+    #[serde(rename = "us-epa-index")]
+    pub us_epa_index: Option<u8>,
+    #[serde(rename = "gb-defra-index")]
+    pub gb_defra_index: Option<u8>,
+}
+
+impl Current {
+    /// When the observation was last updated, from `last_updated_epoch` in the location's zone.
+    pub fn updated_at(&self, location: &Location) -> Option<DateTime<Tz>> {
+        zone_time(self.last_updated_epoch, parse_tz(&location.tz_id)?)
+    }
+}
+
+impl From<AirQualityJson> for AirQuality {
+    fn from(a: AirQualityJson) -> Self {
+        AirQuality {
+            pm2_5: a.pm2_5.map(|v| v as f32),
+            pm10: a.pm10.map(|v| v as f32),
+            o3: a.o3.map(|v| v as f32),
+            no2: a.no2.map(|v| v as f32),
+            us_epa_index: a.us_epa_index,
+            gb_defra_index: a.gb_defra_index,
+        }
+    }
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Condition {
+    pub text: String,
+    pub icon: String,
+    pub code: i64,
+}
+
+/// The `alerts.json` response.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AlertsJson {
+    pub location: Location,
+    pub alerts: AlertList,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AlertList {
+    pub alert: Vec<AlertJson>,
+}
+
+/// Times are ISO 8601 with the location's offset, e.g. "2024-08-14T11:00:00-08:00".
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AlertJson {
+    pub headline: String,
+    pub msgtype: String,
+    pub severity: String,
+    pub urgency: String,
+    /// Areas separated by semicolons.
+    pub areas: String,
+    pub category: String,
+    pub certainty: String,
+    pub event: String,
+    pub note: String,
+    pub effective: String,
+    pub expires: String,
+    pub desc: String,
+    pub instruction: String,
+}
+
+impl From<AlertsJson> for WeatherAlerts {
+    fn from(a: AlertsJson) -> Self {
+        let alerts = a.alerts.alert.into_iter().map(|a| WeatherAlert {
+            severity: AlertSeverity::from_name(&a.severity),
+            urgency: AlertUrgency::from_name(&a.urgency),
+            areas: split_areas(&a.areas),
+            effective: parse_alert_time(&a.effective),
+            expires: parse_alert_time(&a.expires),
+            event: a.event,
+            headline: a.headline,
+            description: a.desc,
+            instruction: a.instruction,
+        }).collect();
+        WeatherAlerts::new(a.location.into(), alerts)
+    }
+}
+
+/// The `astronomy.json` response.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AstronomyJson {
+    pub location: Location,
+    pub astronomy: AstronomyData,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AstronomyData {
+    pub astro: Astro,
+}
+
+/// Times are local 12-hour times, e.g. "05:08 AM", or "No moonrise" when there isn't one that day.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Astro {
+    pub sunrise: String,
+    pub sunset: String,
+    pub moonrise: String,
+    pub moonset: String,
+    pub moon_phase: String,
+    pub moon_illumination: f32,
+    pub is_moon_up: i64,
+    pub is_sun_up: i64,
+}
+
+/// Takes `date` along since the response doesn't repeat it.
+pub fn weatherapi_astronomy(resp: AstronomyJson, date: NaiveDate) -> Astronomy {
+    let a = resp.astronomy.astro;
+    // Whether the sun and moon are up is as of the request, so it only means something for today.
+    let now = resp.location.local_datetime().map(|t| t.naive_local()).or_else(|| resp.location.naive_localtime());
+    let today = now.is_some_and(|now| now.date() == date);
+    Astronomy {
+        place: resp.location.into(),
+        date,
+        sunrise: parse_clock_time(&a.sunrise),
+        sunset: parse_clock_time(&a.sunset),
+        moonrise: parse_clock_time(&a.moonrise),
+        moonset: parse_clock_time(&a.moonset),
+        moon_phase: a.moon_phase,
+        moon_illumination: Some(a.moon_illumination),
+        is_sun_up: today.then_some(a.is_sun_up == 1),
+        is_moon_up: today.then_some(a.is_moon_up == 1),
+    }
+}
+
+/// The `forecast.json` response.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ForecastJson {
+    pub location: Location,
+    pub forecast: ForecastData,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ForecastData {
+    pub forecastday: Vec<ForecastDay>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ForecastDay {
+    pub date: String,
+    pub date_epoch: i64,
+    pub day: Day,
+    pub hour: Vec<Hour>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Day {
+    pub maxtemp_c: f32,
+    pub mintemp_c: f32,
+    pub avgtemp_c: f32,
+    pub daily_chance_of_rain: i64,
+    pub daily_chance_of_snow: i64,
+    pub condition: Condition,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Hour {
+    pub time_epoch: i64,
+    /// Local time at the location, e.g. "2024-08-14 13:00".
+    pub time: String,
+    pub temp_c: f32,
+    pub chance_of_rain: i64,
+    pub chance_of_snow: i64,
+    pub condition: Condition,
+}
+
+/// The `history.json` response, which has the same layout as a forecast.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryJson {
+    pub location: Location,
+    pub forecast: HistoryData,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryData {
+    pub forecastday: Vec<HistoryDay>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryDay {
+    pub date: String,
+    pub date_epoch: i64,
+    pub day: HistoryDaySummary,
+    pub hour: Vec<HistoryHour>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryDaySummary {
+    pub maxtemp_c: f32,
+    pub mintemp_c: f32,
+    pub avgtemp_c: f32,
+    pub totalprecip_mm: f32,
+    pub condition: Condition,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryHour {
+    pub time_epoch: i64,
+    /// Local time at the location, e.g. "2024-08-01 13:00".
+    pub time: String,
+    pub temp_c: f32,
+    pub precip_mm: f32,
+    pub humidity: i64,
+    pub wind_kph: f32,
+    pub condition: Condition,
+}
+
+impl TryFrom<HistoryJson> for WeatherHistory {
+    type Error = WeatherError;
+
+    /// Fails on a day or hour whose date or time can't be read, rather than placing it at the epoch.
+    fn try_from(h: HistoryJson) -> Result<WeatherHistory, WeatherError> {
+        let days = h.forecast.forecastday.into_iter().map(|d| Ok(HistoricalDay {
+            date: parse_date(&d.date)?,
+            min_c: d.day.mintemp_c,
+            max_c: d.day.maxtemp_c,
+            avg_c: d.day.avgtemp_c,
+            precip_mm: Some(d.day.totalprecip_mm),
+            condition: d.day.condition.text,
+            hours: d.hour.into_iter().map(|h| Ok(HistoricalHour {
+                time_epoch: h.time_epoch,
+                local_time: parse_local_time(&h.time)?,
+                temp_c: h.temp_c,
+                precip_mm: Some(h.precip_mm),
+                humidity: Some(h.humidity as f32),
+                wind_kph: Some(h.wind_kph),
+                condition: h.condition.text,
+            })).collect::<Result<_, WeatherError>>()?,
+        })).collect::<Result<_, WeatherError>>()?;
+        Ok(WeatherHistory { place: h.location.into(), days })
+    }
+}
+
+impl From<Location> for Place {
+    fn from(l: Location) -> Self {
+        Place { name: l.name, region: l.region, country: l.country, lat: l.lat, lon: l.lon, tz_id: l.tz_id }
+    }
+}
+
+impl TryFrom<ForecastJson> for Forecast {
+    type Error = WeatherError;
+
+    /// Fails on a day or hour whose date or time can't be read, rather than placing it at the epoch.
+    fn try_from(f: ForecastJson) -> Result<Forecast, WeatherError> {
+        let days = f.forecast.forecastday.into_iter().map(|d| Ok(DailyForecast {
+            date: parse_date(&d.date)?,
+            min_c: d.day.mintemp_c,
+            max_c: d.day.maxtemp_c,
+            avg_c: d.day.avgtemp_c,
+            precip_chance: Some(d.day.daily_chance_of_rain.max(d.day.daily_chance_of_snow) as f32),
+            condition: d.day.condition.text,
+            hours: d.hour.into_iter().map(|h| Ok(HourlyForecast {
+                time_epoch: h.time_epoch,
+                local_time: parse_local_time(&h.time)?,
+                temp_c: h.temp_c,
+                precip_chance: Some(h.chance_of_rain.max(h.chance_of_snow) as f32),
+                condition: h.condition.text,
+            })).collect::<Result<_, WeatherError>>()?,
+        })).collect::<Result<_, WeatherError>>()?;
+        Ok(Forecast { place: f.location.into(), days })
+    }
+}
+
+/// A date as WeatherAPI writes it, e.g. "2024-08-14".
+fn parse_date(date: &str) -> Result<NaiveDate, WeatherError> {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|e| WeatherError::Decode(format!("invalid date {:?}: {}", date, e)))
+}
+
+/// A local time as WeatherAPI writes it, e.g. "2024-08-14 13:00".
+fn parse_local_time(time: &str) -> Result<NaiveDateTime, WeatherError> {
+    NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M").map_err(|e| WeatherError::Decode(format!("invalid time {:?}: {}", time, e)))
+}
+
+impl From<Todo> for Observation {
+    fn from(t: Todo) -> Self {
+        let local_time = t.current.updated_at(&t.location).map(|u| u.naive_local())
+            .or_else(|| NaiveDateTime::parse_from_str(&t.current.last_updated, "%Y-%m-%d %H:%M").ok());
+        Observation {
+            place: t.location.into(),
+            observed_epoch: t.current.last_updated_epoch,
+            local_time,
+            temp_c: t.current.temp_c,
+            icon: ConditionIcon::from_weatherapi_code(t.current.condition.code),
+            condition: t.current.condition.text,
+            is_day: t.current.is_day == 1,
+            feelslike_c: Some(t.current.feelslike_c as f32),
+            windchill_c: Some(t.current.windchill_c as f32),
+            heatindex_c: Some(t.current.heatindex_c as f32),
+            dewpoint_c: Some(t.current.dewpoint_c as f32),
+            humidity: Some(t.current.humidity as f32),
+            wind_kph: Some(t.current.wind_kph as f32),
+            wind_degree: Some(t.current.wind_degree as f32),
+            gust_kph: Some(t.current.gust_kph as f32),
+            pressure_mb: Some(t.current.pressure_mb as f32),
+            precip_mm: Some(t.current.precip_mm as f32),
+            cloud: Some(t.current.cloud as f32),
+            uv: Some(t.current.uv as f32),
+            vis_km: Some(t.current.vis_km as f32),
+            air_quality: t.current.air_quality.map(AirQuality::from),
+        }
+    }
+}
+
+/// weatherapi.com, which needs an API key but accepts any kind of location query.
+pub struct WeatherApi {
+    http: HttpClient,
+    config: WeatherConfig,
+}
+
+impl WeatherApi {
+    pub fn new(config: &WeatherConfig) -> Result<WeatherApi, WeatherError> {
+        Ok(WeatherApi { http: HttpClient::shared(&config.http)?, config: config.clone() })
+    }
+}
+
+impl WeatherProvider for WeatherApi {
+    fn name(&self) -> &'static str {
+        "WeatherAPI"
+    }
+
+    fn current<'a>(&'a self, query: &'a LocationQuery) -> WeatherFuture<'a, Observation> {
+        Box::pin(async move {
+            let resp: Todo = self.http.get_json(current_url(&self.config, query)?, &query.to_string()).await?;
+            Ok(resp.into())
+        })
+    }
+
+    fn forecast<'a>(&'a self, query: &'a LocationQuery, days: u32) -> WeatherFuture<'a, Forecast> {
+        Box::pin(async move {
+            let resp: ForecastJson = self.http.get_json(forecast_url(&self.config, query, days)?, &query.to_string()).await?;
+            resp.try_into()
+        })
+    }
+
+    fn alerts<'a>(&'a self, query: &'a LocationQuery) -> WeatherFuture<'a, WeatherAlerts> {
+        Box::pin(async move {
+            let resp: AlertsJson = self.http.get_json(alerts_url(&self.config, query)?, &query.to_string()).await?;
+            Ok(resp.into())
+        })
+    }
+
+    fn astronomy<'a>(&'a self, query: &'a LocationQuery, date: NaiveDate) -> WeatherFuture<'a, Astronomy> {
+        Box::pin(async move {
+            let resp: AstronomyJson = self.http.get_json(astronomy_url(&self.config, query, date)?, &query.to_string()).await?;
+            Ok(weatherapi_astronomy(resp, date))
+        })
+    }
+
+    fn history<'a>(&'a self, query: &'a LocationQuery, from: NaiveDate, to: NaiveDate) -> WeatherFuture<'a, WeatherHistory> {
+        Box::pin(async move {
+            let resp: HistoryJson = self.http.get_json(history_url(&self.config, query, from, to)?, &query.to_string()).await?;
+            resp.try_into()
+        })
+    }
+}
+
+/// Builds the `current.json` request URL, with the query string properly encoded.
+pub fn current_url(config: &WeatherConfig, query: &LocationQuery) -> Result<reqwest::Url, WeatherError> {
+    let aqi = if config.air_quality { "yes" } else { "no" };
+    endpoint(&config.base_url, "current.json", &[("key", api_key(config)?), ("q", &query.weatherapi_q()), ("aqi", aqi)])
+}
+
+/// Builds the `forecast.json` request URL for `days` days, today included.
+pub fn forecast_url(config: &WeatherConfig, query: &LocationQuery, days: u32) -> Result<reqwest::Url, WeatherError> {
+    endpoint(&config.base_url, "forecast.json", &[
+        ("key", api_key(config)?),
+        ("q", &query.weatherapi_q()),
+        ("days", &days.to_string()),
+        ("aqi", "no"),
+        ("alerts", "no"),
+    ])
+}
+
+/// Builds the `alerts.json` request URL.
+pub fn alerts_url(config: &WeatherConfig, query: &LocationQuery) -> Result<reqwest::Url, WeatherError> {
+    endpoint(&config.base_url, "alerts.json", &[("key", api_key(config)?), ("q", &query.weatherapi_q())])
+}
+
+/// Builds the `astronomy.json` request URL for `date`.
+pub fn astronomy_url(config: &WeatherConfig, query: &LocationQuery, date: NaiveDate) -> Result<reqwest::Url, WeatherError> {
+    endpoint(&config.base_url, "astronomy.json", &[("key", api_key(config)?), ("q", &query.weatherapi_q()), ("dt", &date.to_string())])
+}
+
+/// Builds the `history.json` request URL for the days from `from` to `to`.
+pub fn history_url(config: &WeatherConfig, query: &LocationQuery, from: NaiveDate, to: NaiveDate) -> Result<reqwest::Url, WeatherError> {
+    endpoint(&config.base_url, "history.json", &[
+        ("key", api_key(config)?),
+        ("q", &query.weatherapi_q()),
+        ("dt", &from.to_string()),
+        ("end_dt", &to.to_string()),
+    ])
+}
+
+fn api_key(config: &WeatherConfig) -> Result<&str, WeatherError> {
+    config.api_key.as_deref().filter(|k| !k.is_empty()).ok_or(WeatherError::MissingApiKey)
+}
+
+#[test]
+fn test_current_url() {
+    let config = WeatherConfig { api_key: Some("abc".to_string()), ..WeatherConfig::default() };
+    assert_eq!(
+        current_url(&config, &LocationQuery::City("New York, NY & more".to_string())).unwrap().as_str(),
+        "https://api.weatherapi.com/v1/current.json?key=abc&q=New+York%2C+NY+%26+more&aqi=no");
+    let proxy = WeatherConfig { base_url: "http://localhost:8080/weather/".to_string(), ..config };
+    let zip = LocationQuery::postal_code("20500", None).unwrap();
+    assert_eq!(
+        current_url(&proxy, &zip).unwrap().as_str(),
+        "http://localhost:8080/weather/current.json?key=abc&q=20500&aqi=no");
+    assert_eq!(
+        current_url(&proxy, &LocationQuery::postal_code("75001", Some("fr")).unwrap()).unwrap().as_str(),
+        "http://localhost:8080/weather/current.json?key=abc&q=75001%2CFR&aqi=no");
+    assert_eq!(
+        current_url(&proxy, &LocationQuery::iata("lhr").unwrap()).unwrap().as_str(),
+        "http://localhost:8080/weather/current.json?key=abc&q=iata%3ALHR&aqi=no");
+    let with_aqi = WeatherConfig { air_quality: true, ..proxy.clone() };
+    assert_eq!(current_url(&with_aqi, &zip).unwrap().as_str(), "http://localhost:8080/weather/current.json?key=abc&q=20500&aqi=yes");
+    assert_eq!(
+        forecast_url(&proxy, &zip, 3).unwrap().as_str(),
+        "http://localhost:8080/weather/forecast.json?key=abc&q=20500&days=3&aqi=no&alerts=no");
+    assert_eq!(alerts_url(&proxy, &zip).unwrap().as_str(), "http://localhost:8080/weather/alerts.json?key=abc&q=20500");
+    let day = |d: u32| NaiveDate::from_ymd_opt(2024, 8, d).unwrap();
+    assert_eq!(
+        history_url(&proxy, &zip, day(1), day(3)).unwrap().as_str(),
+        "http://localhost:8080/weather/history.json?key=abc&q=20500&dt=2024-08-01&end_dt=2024-08-03");
+    assert_eq!(
+        astronomy_url(&proxy, &zip, day(14)).unwrap().as_str(),
+        "http://localhost:8080/weather/astronomy.json?key=abc&q=20500&dt=2024-08-14");
+}
+
+#[test]
+fn test_current_url_errors() {
+    let zip = LocationQuery::postal_code("20500", None).unwrap();
+    assert!(matches!(current_url(&WeatherConfig::default(), &zip), Err(WeatherError::MissingApiKey)));
+    let config = WeatherConfig {
+        api_key: Some("abc".to_string()),
+        base_url: "not a url".to_string(),
+        ..WeatherConfig::default()
+    };
+    assert!(matches!(current_url(&config, &zip), Err(WeatherError::InvalidUrl(_))));
+}
+
+#[test]
+fn test_todo_into_observation() {
+    let todo: Todo = serde_json::from_str(r#"{
+        "location": {"name": "Juneau", "region": "Alaska", "country": "USA", "lat": 58.3, "lon": -134.42,
+            "tz_id": "America/Juneau", "localtime_epoch": 1723654611, "localtime": "2024-08-14 8:56"},
+        "current": {"last_updated_epoch": 1723654500, "last_updated": "2024-08-14 08:55", "temp_c": 11.1, "temp_f": 52.0,
+            "is_day": 1, "condition": {"text": "Overcast", "icon": "//cdn.weatherapi.com/weather/64x64/day/122.png", "code": 1009},
+            "wind_mph": 3.8, "wind_kph": 6.1, "wind_degree": 150, "wind_dir": "SSE", "pressure_mb": 1013.0, "pressure_in": 29.91,
+            "precip_mm": 0.0, "precip_in": 0.0, "humidity": 87, "cloud": 100, "feelslike_c": 10.6, "feelslike_f": 51.0,
+            "windchill_c": 10.6, "windchill_f": 51.0, "heatindex_c": 11.1, "heatindex_f": 52.0, "dewpoint_c": 9.0,
+            "dewpoint_f": 48.2, "vis_km": 16.0, "vis_miles": 9.0, "uv": 3.0, "gust_mph": 5.0, "gust_kph": 8.0}
+    }"#).unwrap();
+    assert_eq!(todo.location.local_datetime().unwrap().to_string(), "2024-08-14 08:56:51 AKDT");
+    let obs: Observation = todo.into();
+    assert_eq!(obs.place.name, "Juneau");
+    assert_eq!(obs.place.region, "Alaska");
+    assert_eq!(obs.place.tz_id, "America/Juneau");
+    assert_eq!(obs.temp_c, 11.1);
+    assert_eq!(obs.condition, "Overcast");
+    assert_eq!(obs.icon, Some(ConditionIcon::Cloudy));
+    assert_eq!(obs.observed_epoch, 1723654500);
+    assert_eq!(obs.local_time.unwrap().to_string(), "2024-08-14 08:55:00");
+    assert_eq!(obs.observed_at().unwrap().to_rfc3339(), "2024-08-14T08:55:00-08:00");
+    assert!(obs.is_day);
+    assert_eq!(obs.feelslike_c, Some(10.6));
+    assert_eq!(obs.wind_degree, Some(150.0));
+    assert_eq!(obs.vis_km, Some(16.0));
+}
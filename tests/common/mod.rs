@@ -0,0 +1,175 @@
+//! A local stand-in for the weather APIs, serving recorded responses from `tests/fixtures`.
+#![allow(dead_code)]
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use temperatureconverter::config::{HttpConfig, WeatherConfig};
+
+#[derive(Debug, Clone)]
+pub struct Route {
+    /// Matched against the start of the request path, e.g. "/current.json".
+    pub path: String,
+    /// If set, the raw query string must contain this, e.g. "q=99801".
+    pub query: Option<String>,
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub fixture: String,
+    /// How many times the route answers before falling through to later routes. `None` answers forever.
+    pub times: Option<usize>,
+    /// How long to wait before answering.
+    pub delay: Duration,
+}
+
+pub fn route(path: &str, status: u16, fixture: &str) -> Route {
+    Route {
+        path: path.to_string(),
+        query: None,
+        status,
+        headers: Vec::new(),
+        fixture: fixture.to_string(),
+        times: None,
+        delay: Duration::ZERO,
+    }
+}
+
+impl Route {
+    pub fn query(mut self, query: &str) -> Route {
+        self.query = Some(query.to_string());
+        self
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Route {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn times(mut self, times: usize) -> Route {
+        self.times = Some(times);
+        self
+    }
+
+    pub fn delay(mut self, delay: Duration) -> Route {
+        self.delay = delay;
+        self
+    }
+}
+
+pub struct MockServer {
+    pub url: String,
+    requests: Arc<Mutex<Vec<String>>>,
+    bodies: Arc<Mutex<Vec<(String, String)>>>,
+}
+
+impl MockServer {
+    /// Serves `routes` on a free local port until the test process exits. Unmatched requests get a 404.
+    pub fn start(routes: Vec<Route>) -> MockServer {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let bodies = Arc::new(Mutex::new(Vec::new()));
+        let routes = Arc::new(Mutex::new(routes));
+
+        let (recorded, posted) = (requests.clone(), bodies.clone());
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let (recorded, posted, routes) = (recorded.clone(), posted.clone(), routes.clone());
+                thread::spawn(move || handle(stream, &recorded, &posted, &routes));
+            }
+        });
+
+        MockServer { url, requests, bodies }
+    }
+
+    /// Request targets (path and query) received so far, in order.
+    pub fn requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// The target and body of each request that sent one, such as a webhook POST, in order.
+    pub fn bodies(&self) -> Vec<(String, String)> {
+        self.bodies.lock().unwrap().clone()
+    }
+
+    /// A WeatherAPI config pointed at this server, with every other provider pointed here too.
+    /// Retries back off for a millisecond and requests aren't rate limited, so tests stay fast.
+    pub fn weather_config(&self) -> WeatherConfig {
+        WeatherConfig {
+            api_key: Some("test-key".to_string()),
+            base_url: self.url.clone(),
+            open_meteo_url: self.url.clone(),
+            archive_url: self.url.clone(),
+            geocoding_url: self.url.clone(),
+            nws_url: self.url.clone(),
+            http: HttpConfig { backoff_ms: 1, requests_per_second: 0.0, ..HttpConfig::default() },
+            ..WeatherConfig::default()
+        }
+    }
+}
+
+pub fn fixture(name: &str) -> String {
+    std::fs::read_to_string(format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name)).unwrap()
+}
+
+fn handle(stream: TcpStream, recorded: &Mutex<Vec<String>>, posted: &Mutex<Vec<(String, String)>>, routes: &Mutex<Vec<Route>>) {
+    let mut reader = BufReader::new(&stream);
+    let mut request_line = String::new();
+    if reader.read_line(&mut request_line).is_err() {
+        return;
+    }
+    let mut length = 0;
+    loop {
+        let mut header = String::new();
+        match reader.read_line(&mut header) {
+            Ok(0) | Err(_) => break,
+            Ok(_) if header.trim().is_empty() => break,
+            Ok(_) => {}
+        }
+        if let Some((_, value)) = header.split_once(':').filter(|(name, _)| name.eq_ignore_ascii_case("content-length")) {
+            length = value.trim().parse().unwrap_or(0);
+        }
+    }
+
+    let target = request_line.split_whitespace().nth(1).unwrap_or("/").to_string();
+    recorded.lock().unwrap().push(target.clone());
+    if length > 0 {
+        let mut body = vec![0; length];
+        if reader.read_exact(&mut body).is_ok() {
+            posted.lock().unwrap().push((target.clone(), String::from_utf8_lossy(&body).to_string()));
+        }
+    }
+    let (path, query) = target.split_once('?').unwrap_or((&target, ""));
+
+    let matched = {
+        let mut routes = routes.lock().unwrap();
+        let found = routes.iter_mut().find(|r| {
+            path.starts_with(&r.path)
+                && r.query.as_ref().is_none_or(|q| query.contains(q.as_str()))
+                && r.times != Some(0)
+        });
+        found.map(|r| {
+            r.times = r.times.map(|t| t - 1);
+            r.clone()
+        })
+    };
+
+    if let Some(delay) = matched.as_ref().map(|r| r.delay) {
+        thread::sleep(delay);
+    }
+    let (status, headers, body) = match matched {
+        Some(r) => (r.status, r.headers, fixture(&r.fixture)),
+        None => (404, Vec::new(), "{\"error\":{\"code\":404,\"message\":\"Not found\"}}".to_string()),
+    };
+    let mut response = format!(
+        "HTTP/1.1 {} Mock\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n",
+        status, body.len());
+    for (name, value) in headers {
+        response += &format!("{}: {}\r\n", name, value);
+    }
+    response += "\r\n";
+    response += &body;
+    let _ = (&stream).write_all(response.as_bytes());
+}
@@ -0,0 +1,992 @@
+mod common;
+
+use std::process::Command;
+
+use common::{route, MockServer};
+
+use temperatureconverter::config::{CacheConfig, HistoryConfig, HttpConfig, ThresholdConfig, WeatherConfig};
+use temperatureconverter::*;
+
+fn location(input: &str) -> LocationQuery {
+    input.parse().unwrap()
+}
+
+fn weatherapi(status: u16, fixture: &str) -> MockServer {
+    MockServer::start(vec![route("/current.json", status, fixture)])
+}
+
+#[tokio::test]
+async fn test_weatherapi_success() {
+    let server = weatherapi(200, "weatherapi_current.json");
+    let obs = get_observation(&server.weather_config(), &location("Juneau, AK")).await.unwrap();
+    assert_eq!(obs.place.name, "Juneau");
+    assert_eq!(obs.place.region, "Alaska");
+    assert_eq!(obs.temp_c, 11.1);
+    assert_eq!(obs.condition, "Overcast");
+    assert_eq!(icon_image(obs.icon.unwrap(), obs.is_day).uri, "bytes://icons/cloudy.png");
+    assert_eq!(server.requests(), vec!["/current.json?key=test-key&q=Juneau%2C+AK&aqi=no"]);
+}
+
+#[tokio::test]
+async fn test_get_current_temp_with() {
+    let server = weatherapi(200, "weatherapi_current.json");
+    let t = get_current_temp_with(&server.weather_config(), "99801").await.unwrap();
+    assert_eq!(t, ("Juneau".to_string(), "Alaska".to_string(), 11.1));
+}
+
+#[tokio::test]
+async fn test_weatherapi_unknown_location() {
+    let server = weatherapi(400, "weatherapi_no_location.json");
+    let err = get_observation(&server.weather_config(), &location("00000")).await.unwrap_err();
+    assert_eq!(err, WeatherError::LocationNotFound("00000".to_string()));
+    assert_eq!(err.to_string(), "no matching location found for 00000");
+}
+
+#[tokio::test]
+async fn test_weatherapi_invalid_key() {
+    let server = weatherapi(401, "weatherapi_invalid_key.json");
+    let err = get_observation(&server.weather_config(), &location("99801")).await.unwrap_err();
+    assert!(matches!(err, WeatherError::InvalidApiKey(_)));
+    assert!(!err.to_string().contains("test-key"));
+}
+
+#[tokio::test]
+async fn test_weatherapi_rate_limited() {
+    let server = weatherapi(429, "rate_limited.json");
+    assert_eq!(
+        get_observation(&server.weather_config(), &location("99801")).await,
+        Err(WeatherError::QuotaExceeded("Too many requests".to_string())));
+}
+
+#[tokio::test]
+async fn test_retry_after() {
+    let server = MockServer::start(vec![
+        route("/current.json", 429, "rate_limited.json").header("Retry-After", "0").times(1),
+        route("/current.json", 503, "rate_limited.json").times(1),
+        route("/current.json", 200, "weatherapi_current.json"),
+    ]);
+    let obs = get_observation(&server.weather_config(), &location("99801")).await.unwrap();
+    assert_eq!(obs.place.name, "Juneau");
+    assert_eq!(server.requests().len(), 3);
+
+    // Retries run out, then the last error is reported.
+    let server = weatherapi(429, "rate_limited.json");
+    let config = WeatherConfig { http: HttpConfig { retries: 2, ..server.weather_config().http }, ..server.weather_config() };
+    assert!(matches!(get_observation(&config, &location("99801")).await, Err(WeatherError::QuotaExceeded(_))));
+    assert_eq!(server.requests().len(), 3);
+
+    // A wait longer than max_backoff_ms is given up on straight away.
+    let server = MockServer::start(vec![route("/current.json", 429, "rate_limited.json").header("Retry-After", "120")]);
+    assert!(matches!(get_observation(&server.weather_config(), &location("99801")).await, Err(WeatherError::QuotaExceeded(_))));
+    assert_eq!(server.requests().len(), 1);
+}
+
+#[tokio::test]
+async fn test_read_timeout() {
+    let server = MockServer::start(vec![
+        route("/current.json", 200, "weatherapi_current.json").delay(std::time::Duration::from_millis(1_500)).times(1),
+        route("/current.json", 200, "weatherapi_current.json"),
+    ]);
+    let http = HttpConfig { read_timeout_secs: 1, ..server.weather_config().http };
+    let config = WeatherConfig { http: HttpConfig { retries: 0, ..http.clone() }, ..server.weather_config() };
+    assert_eq!(get_observation(&config, &location("99801")).await, Err(WeatherError::Timeout));
+    // The timed out request is tried again.
+    let server = MockServer::start(vec![
+        route("/current.json", 200, "weatherapi_current.json").delay(std::time::Duration::from_millis(1_500)).times(1),
+        route("/current.json", 200, "weatherapi_current.json"),
+    ]);
+    let config = WeatherConfig { http, ..server.weather_config() };
+    assert!(get_observation(&config, &location("99801")).await.is_ok());
+    assert_eq!(server.requests().len(), 2);
+}
+
+#[tokio::test]
+async fn test_rate_limit() {
+    let server = weatherapi(200, "weatherapi_current.json");
+    let config = WeatherConfig { http: HttpConfig { requests_per_second: 20.0, ..server.weather_config().http }, ..server.weather_config() };
+    let start = std::time::Instant::now();
+    for _ in 0..4 {
+        get_observation(&config, &location("99801")).await.unwrap();
+    }
+    assert!(start.elapsed() >= std::time::Duration::from_millis(150));
+    assert_eq!(server.requests().len(), 4);
+}
+
+#[tokio::test]
+async fn test_weatherapi_quota_exceeded() {
+    let server = weatherapi(403, "weatherapi_quota.json");
+    assert!(matches!(get_observation(&server.weather_config(), &location("99801")).await, Err(WeatherError::QuotaExceeded(_))));
+}
+
+#[tokio::test]
+async fn test_weatherapi_server_error() {
+    let server = MockServer::start(vec![]);
+    let err = get_observation(&server.weather_config(), &location("99801")).await.unwrap_err();
+    assert_eq!(err, WeatherError::Http { status: 404, code: Some(404), message: "Not found".to_string() });
+    assert_eq!(err.to_string(), "the weather service returned HTTP 404 (error 404): Not found");
+}
+
+#[tokio::test]
+async fn test_connection_refused() {
+    let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let config = WeatherConfig { base_url: format!("http://127.0.0.1:{}", port), ..weatherapi(200, "weatherapi_current.json").weather_config() };
+    let err = get_observation(&config, &location("99801")).await.unwrap_err();
+    assert!(matches!(err, WeatherError::Transport(_)));
+    assert!(!err.to_string().contains("test-key"));
+}
+
+#[tokio::test]
+async fn test_weatherapi_malformed() {
+    let server = weatherapi(200, "malformed.json");
+    assert!(matches!(get_observation(&server.weather_config(), &location("99801")).await, Err(WeatherError::Decode(_))));
+}
+
+#[tokio::test]
+async fn test_weatherapi_missing_key() {
+    let server = weatherapi(200, "weatherapi_current.json");
+    let config = WeatherConfig { api_key: None, ..server.weather_config() };
+    assert!(matches!(get_observation(&config, &location("99801")).await, Err(WeatherError::MissingApiKey)));
+    assert!(server.requests().is_empty());
+}
+
+#[tokio::test]
+async fn test_get_observations() {
+    let server = MockServer::start(vec![
+        route("/current.json", 400, "weatherapi_no_location.json").query("q=00000"),
+        route("/current.json", 200, "weatherapi_current.json"),
+    ]);
+    let config = WeatherConfig { concurrency: 2, ..server.weather_config() };
+    let results = get_observations(&config, &[location("99801"), location("00000"), location("20500")]).await;
+    assert_eq!(results.len(), 3);
+    assert_eq!(results[0].as_ref().unwrap().place.name, "Juneau");
+    assert_eq!(results[1], Err(WeatherError::LocationNotFound("00000".to_string())));
+    assert!(results[2].is_ok());
+    assert_eq!(server.requests().len(), 3);
+    assert!(get_observations(&config, &[]).await.is_empty());
+}
+
+#[tokio::test]
+async fn test_open_meteo() {
+    let server = MockServer::start(vec![
+        route("/search", 200, "open_meteo_geocoding.json"),
+        route("/forecast", 200, "open_meteo_current.json"),
+    ]);
+    let config = WeatherConfig { provider: ProviderKind::OpenMeteo, ..server.weather_config() };
+    let obs = get_observation(&config, &location("99801")).await.unwrap();
+    assert_eq!(obs.place.name, "Juneau");
+    assert_eq!(obs.place.region, "Alaska");
+    assert_eq!(obs.place.tz_id, "America/Juneau");
+    assert_eq!(obs.temp_c, 11.4);
+    assert_eq!(obs.condition, "Overcast");
+    assert!(server.requests()[1].starts_with("/forecast?latitude=58.30194&longitude=-134.41972&"));
+}
+
+#[tokio::test]
+async fn test_open_meteo_geocoding_queries() {
+    let server = MockServer::start(vec![
+        route("/search", 200, "open_meteo_geocoding_paris.json"),
+        route("/forecast", 200, "open_meteo_current.json"),
+    ]);
+    let config = WeatherConfig { provider: ProviderKind::OpenMeteo, ..server.weather_config() };
+    let obs = get_observation(&config, &location("Paris, France")).await.unwrap();
+    assert_eq!((obs.place.region.as_str(), obs.place.country.as_str()), ("Île-de-France", "France"));
+    assert_eq!(server.requests()[0], "/search?name=Paris&count=10&format=json");
+
+    let postal = LocationQuery::postal_code("75001", Some("FR")).unwrap();
+    get_observation(&config, &postal).await.unwrap();
+    assert_eq!(server.requests()[2], "/search?name=75001&count=1&format=json&countryCode=FR");
+
+    assert!(matches!(get_observation(&config, &location("iata:CDG")).await, Err(WeatherError::Unsupported { .. })));
+    assert_eq!(server.requests().len(), 4);
+}
+
+#[tokio::test]
+async fn test_open_meteo_unknown_location() {
+    let server = MockServer::start(vec![route("/search", 200, "open_meteo_geocoding_empty.json")]);
+    let config = WeatherConfig { provider: ProviderKind::OpenMeteo, ..server.weather_config() };
+    assert!(matches!(get_observation(&config, &location("nowhere")).await, Err(WeatherError::LocationNotFound(_))));
+}
+
+#[tokio::test]
+async fn test_nws() {
+    let server = MockServer::start(vec![
+        route("/points/38.8977,-77.0365", 200, "nws_point.json"),
+        route("/gridpoints/LWX/96,70/stations", 200, "nws_stations.json"),
+        route("/stations/KDCA/observations/latest", 200, "nws_latest.json"),
+    ]);
+    let config = WeatherConfig { provider: ProviderKind::Nws, ..server.weather_config() };
+    let obs = get_observation(&config, &location("38.8977,-77.0365")).await.unwrap();
+    assert_eq!(obs.place.name, "Washington");
+    assert_eq!(obs.place.region, "DC");
+    assert_eq!(obs.temp_c, 26.1);
+    assert_eq!(server.requests().len(), 3);
+}
+
+#[tokio::test]
+async fn test_nws_outside_us() {
+    let server = MockServer::start(vec![route("/points/", 404, "nws_outside_us.json")]);
+    let config = WeatherConfig { provider: ProviderKind::Nws, ..server.weather_config() };
+    assert!(matches!(get_observation(&config, &location("48.8566,2.3522")).await, Err(WeatherError::LocationNotFound(_))));
+}
+
+fn run_cli(server: &MockServer, dir: &tempfile::TempDir, args: &[&str]) -> String {
+    let output = cli(server, dir, args).output().unwrap();
+    String::from_utf8(output.stdout).unwrap()
+}
+
+/// The CLI run in `dir` against `server`, with no config file unless one is given in `TEMPERATURE_CONVERTER_CONFIG`.
+fn cli(server: &MockServer, dir: &tempfile::TempDir, args: &[&str]) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_main-cli"));
+    command.args(args)
+        .current_dir(dir.path())
+        .env("TEMPERATURE_CONVERTER_CONFIG", dir.path().join("none.toml"))
+        .env("TEMPERATURE_CONVERTER_API_KEY", "test-key")
+        .env("TEMPERATURE_CONVERTER_BASE_URL", &server.url)
+        .env("TZ", "America/New_York");
+    command
+}
+
+#[test]
+fn test_cli_zip_lookup() {
+    let server = weatherapi(200, "weatherapi_current.json");
+    let dir = tempfile::tempdir().unwrap();
+    let out = run_cli(&server, &dir, &["-z", "99801"]);
+    assert!(out.starts_with("-= Retrieve temperature in Juneau, Alaska =-\n    Celsius: 11.1\n    Kelvin: 284.25"));
+    assert!(out.contains("\n    Observed: 2024-08-14 08:55 AKDT (2024-08-14 12:55 EDT your time)\n"), "{}", out);
+
+    let log = std::fs::read_to_string(dir.path().join("temperature-converter-log.txt")).unwrap();
+    let entries = history::parse_history(&log);
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].location.as_deref(), Some("Juneau, Alaska"));
+    assert_eq!(entries[0].app, Some(Application::CLI));
+    assert_eq!(entries[0].zone.as_deref(), Some("America/New_York"));
+    assert_eq!(entries[0].timestamp.offset().local_minus_utc(), 0);
+    assert!(log.trim_end().ends_with(" +00:00 America/New_York"), "{}", log);
+}
+
+#[test]
+fn test_cli_zip_lookup_error() {
+    let server = weatherapi(400, "weatherapi_no_location.json");
+    let dir = tempfile::tempdir().unwrap();
+    let out = run_cli(&server, &dir, &["-z", "00000", "--no-history"]);
+    assert!(!out.contains("Celsius"));
+    assert_eq!(out.trim(), "Weather lookup failed: no matching location found for 00000");
+    assert!(!dir.path().join("temperature-converter-log.txt").exists());
+}
+
+#[test]
+fn test_cli_location_options() {
+    let server = weatherapi(200, "weatherapi_current.json");
+    let dir = tempfile::tempdir().unwrap();
+    let out = run_cli(&server, &dir, &["-z", "2050", "--country", "us", "--no-history"]);
+    assert_eq!(out.trim(), "2050 is not a valid postal code for US");
+    let out = run_cli(&server, &dir, &["--iata", "JFKX", "--no-history"]);
+    assert_eq!(out.trim(), "JFKX is not a three letter airport code (ex: JFK)");
+    assert!(server.requests().is_empty());
+
+    run_cli(&server, &dir, &["--iata", "jnu", "--no-history"]);
+    run_cli(&server, &dir, &["-z", "sw1a 1aa", "--country", "GB", "--no-history"]);
+    run_cli(&server, &dir, &["forecast", "--coords", "58.3,-134.42", "--no-history"]);
+    let requests = server.requests();
+    assert!(requests[0].contains("&q=iata%3AJNU&"), "{}", requests[0]);
+    assert!(requests[1].contains("&q=SW1A+1AA%2CGB&"), "{}", requests[1]);
+    assert!(requests[2].contains("&q=58.3%2C-134.42&"), "{}", requests[2]);
+}
+
+#[test]
+fn test_cli_multiple_locations() {
+    let server = MockServer::start(vec![
+        route("/current.json", 400, "weatherapi_no_location.json").query("q=00000"),
+        route("/current.json", 200, "weatherapi_current.json"),
+    ]);
+    let dir = tempfile::tempdir().unwrap();
+    let out = run_cli(&server, &dir, &["-z", "99801", "--iata", "JFKX", "-z", "00000", "--scales", "C,F"]);
+    let lines: Vec<&str> = out.lines().collect();
+    assert_eq!(lines[0], "-= Weather at 3 locations =-");
+    assert!(lines[1].starts_with("    Location        Local time        Condition"), "{}", out);
+    assert!(lines[2].starts_with("    Juneau, Alaska  2024-08-14 08:55  Overcast"), "{}", out);
+    assert!(lines[2].ends_with("11.1°C / 52°F"), "{}", out);
+    assert!(lines[3].contains("JFKX is not a three letter airport code"), "{}", out);
+    assert!(lines[4].contains("Error: no matching location found for 00000"), "{}", out);
+    assert_eq!(server.requests().len(), 2);
+
+    let log = std::fs::read_to_string(dir.path().join("temperature-converter-log.txt")).unwrap();
+    let entries = temperatureconverter::history::parse_history(&log);
+    assert_eq!(entries.len(), 3);
+    assert_eq!(entries[0].location.as_deref(), Some("Juneau, Alaska"));
+
+    let out = run_cli(&server, &dir, &["forecast", "-z", "99801", "-z", "20500", "--no-history"]);
+    assert_eq!(out.trim(), "Enter one location at a time for this command (2 given)");
+}
+
+/// A configuration pointed at `server` that keeps its cache and history in `dir`.
+fn stored_config(server: &MockServer, dir: &tempfile::TempDir) -> Config {
+    let path = |name: &str| Some(dir.path().join(name).to_string_lossy().to_string());
+    Config {
+        weather: server.weather_config(),
+        cache: CacheConfig { path: path("cache.json"), ..CacheConfig::default() },
+        history: HistoryConfig { path: path("log.txt"), ..HistoryConfig::default() },
+        ..Config::default()
+    }
+}
+
+#[tokio::test]
+async fn test_cached_observations() {
+    let server = weatherapi(200, "weatherapi_current.json");
+    let dir = tempfile::tempdir().unwrap();
+    let config = stored_config(&server, &dir);
+    let zip = location("99801");
+
+    // The fixture was last updated in 2024, so it is never fresh enough to reuse.
+    let first = get_cached_observation(&config, &zip, false).await.unwrap();
+    assert_eq!(first.source, ObservationSource::Live);
+    get_cached_observation(&config, &zip, false).await.unwrap();
+    assert_eq!(server.requests().len(), 2);
+
+    let mut stored = WeatherCache::default();
+    let now = chrono::Utc::now().timestamp();
+    let fresh = Observation { observed_epoch: now - 60, ..first.observation };
+    stored.insert(cache_key(ProviderKind::WeatherApi, &location("99801")), fresh.clone(), now - 30);
+    stored.save(&config.cache.cache_path()).unwrap();
+    let hit = get_cached_observation(&config, &zip, false).await.unwrap();
+    assert_eq!(hit.source, ObservationSource::Cache);
+    assert_eq!(hit.observation, fresh);
+    assert_eq!(server.requests().len(), 2);
+
+    assert_eq!(get_cached_observation(&config, &zip, true).await.unwrap().source, ObservationSource::Live);
+    let disabled = Config { cache: CacheConfig { enabled: false, ..config.cache.clone() }, ..config };
+    assert_eq!(get_cached_observation(&disabled, &zip, false).await.unwrap().source, ObservationSource::Live);
+    assert_eq!(server.requests().len(), 4);
+}
+
+#[tokio::test]
+async fn test_stale_fallback() {
+    let server = weatherapi(200, "weatherapi_current.json");
+    let dir = tempfile::tempdir().unwrap();
+    let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let mut config = stored_config(&server, &dir);
+    config.weather.base_url = format!("http://127.0.0.1:{}", port);
+    let now = chrono::Utc::now().timestamp();
+
+    // Nothing stored yet, so the transport error comes back.
+    let err = get_cached_observation(&config, &location("99801"), false).await.unwrap_err();
+    assert!(matches!(err, WeatherError::Transport(_)));
+
+    let mut stored = WeatherCache::default();
+    let old = Observation { temp_c: 9.0, observed_epoch: now - 7_200, ..Observation::default() };
+    stored.insert(cache_key(ProviderKind::WeatherApi, &location("99801")), old.clone(), now - 7_200);
+    stored.save(&config.cache.cache_path()).unwrap();
+    let stale = get_cached_observation(&config, &location("99801"), false).await.unwrap();
+    assert_eq!((stale.source, &stale.observation), (ObservationSource::Stale, &old));
+    assert!(stale.cache_note(now).unwrap().ends_with("observation fetched 2 h ago"));
+
+    // A place only known from the use history.
+    history::write_to_log(
+        &config.history,
+        "Temperature retrieved (\n-= Retrieve temperature in Paris, Ile-de-France =-\n    Celsius: 21.5\n)",
+        Application::CLI).unwrap();
+    let recorded = get_cached_observation(&config, &location("Paris"), false).await.unwrap();
+    assert_eq!(recorded.source, ObservationSource::History);
+    assert_eq!((recorded.observation.place.name.as_str(), recorded.observation.temp_c), ("Paris", 21.5));
+
+    // Errors from a reachable service don't fall back.
+    let missing = weatherapi(400, "weatherapi_no_location.json");
+    config.weather = missing.weather_config();
+    assert!(get_cached_observation(&config, &location("99801"), true).await.is_err());
+}
+
+#[tokio::test]
+async fn test_offline() {
+    let server = weatherapi(200, "weatherapi_current.json");
+    let dir = tempfile::tempdir().unwrap();
+    let mut config = stored_config(&server, &dir);
+    config.weather.offline = true;
+    let err = get_cached_observation(&config, &location("99801"), false).await.unwrap_err();
+    assert_eq!(err.to_string(), "working offline and no weather is stored for 99801");
+    assert!(matches!(get_forecast(&config.weather, &location("99801"), 3).await, Err(WeatherError::Offline(_))));
+    assert!(server.requests().is_empty());
+}
+
+#[test]
+fn test_cli_cache() {
+    let server = weatherapi(200, "weatherapi_current.json");
+    let dir = tempfile::tempdir().unwrap();
+    let now = chrono::Utc::now().timestamp();
+    let mut stored = WeatherCache::default();
+    let obs = Observation {
+        place: Place { name: "Juneau".to_string(), region: "Alaska".to_string(), ..Place::default() },
+        observed_epoch: now - 300,
+        temp_c: 12.0,
+        ..Observation::default()
+    };
+    stored.insert(cache_key(ProviderKind::WeatherApi, &location("99801")), obs, now - 180);
+    stored.save(&dir.path().join("temperature-converter-cache.json").to_string_lossy()).unwrap();
+
+    let out = run_cli(&server, &dir, &["-z", "99801", "--no-history"]);
+    assert!(out.starts_with("-= Retrieve temperature in Juneau, Alaska =-\n    (from cache, fetched 3 min ago)\n    Celsius: 12"), "{}", out);
+    let out = run_cli(&server, &dir, &["-z", "99801", "-z", "20500", "--no-history"]);
+    assert!(out.lines().nth(2).unwrap().ends_with("3 min ago"), "{}", out);
+    assert_eq!(server.requests().len(), 1);
+
+    let out = run_cli(&server, &dir, &["-z", "99801", "--refresh", "--no-history"]);
+    assert!(!out.contains("from cache"));
+    run_cli(&server, &dir, &["-z", "99801", "--no-cache", "--no-history"]);
+    assert_eq!(server.requests().len(), 3);
+}
+
+#[test]
+fn test_cli_offline() {
+    let server = weatherapi(200, "weatherapi_current.json");
+    let dir = tempfile::tempdir().unwrap();
+    let now = chrono::Utc::now().timestamp();
+    let mut stored = WeatherCache::default();
+    let obs = Observation {
+        place: Place { name: "Juneau".to_string(), region: "Alaska".to_string(), ..Place::default() },
+        observed_epoch: now - 7_300,
+        temp_c: 12.0,
+        ..Observation::default()
+    };
+    stored.insert(cache_key(ProviderKind::WeatherApi, &location("99801")), obs, now - 7_300);
+    stored.save(&dir.path().join("temperature-converter-cache.json").to_string_lossy()).unwrap();
+
+    let out = run_cli(&server, &dir, &["-z", "99801", "--offline", "--no-history"]);
+    assert!(out.starts_with(
+        "-= Retrieve temperature in Juneau, Alaska =-\n    (STALE: the weather service was not reached, showing the observation fetched 2 h ago)\n    Celsius: 12"),
+        "{}", out);
+    let out = run_cli(&server, &dir, &["-z", "99801", "-z", "20500", "--offline", "--no-history"]);
+    assert!(out.lines().nth(2).unwrap().ends_with("STALE 2 h ago"), "{}", out);
+    assert!(out.contains("Error: working offline and no weather is stored for 20500"), "{}", out);
+    assert!(server.requests().is_empty());
+}
+
+#[test]
+fn test_cli_offline_from_history() {
+    let server = weatherapi(200, "weatherapi_current.json");
+    let dir = tempfile::tempdir().unwrap();
+    let config = dir.path().join("nocache.toml");
+    std::fs::write(&config, "[cache]\nenabled = false\n[history]\nlocations = \"hash\"\n").unwrap();
+    let run = |args: &[&str]| {
+        let output = cli(&server, &dir, args).env("TEMPERATURE_CONVERTER_CONFIG", &config).output().unwrap();
+        String::from_utf8(output.stdout).unwrap()
+    };
+    run(&["-z", "99801"]);
+    assert!(!dir.path().join("temperature-converter-cache.json").exists());
+
+    // Nothing is cached, but the lookup by ZIP code is found in the use history by its hashed cache key.
+    let out = run(&["-z", "99801", "--offline"]);
+    assert!(out.starts_with("-= Retrieve temperature in #"), "{}", out);
+    assert!(out.contains("(STALE: the weather service was not reached") && out.contains("Celsius: 11.1"), "{}", out);
+    let out = run(&["-z", "20500", "--offline"]);
+    assert!(out.contains("working offline and no weather is stored for 20500"), "{}", out);
+    assert_eq!(server.requests().len(), 1);
+}
+
+#[test]
+fn test_cli_zip_details() {
+    let server = weatherapi(200, "weatherapi_current.json");
+    let dir = tempfile::tempdir().unwrap();
+    let out = run_cli(&server, &dir, &["-z", "99801", "--details", "--scales", "C,F", "--no-history"]);
+    assert!(out.contains("\n-= Details =-\n    Condition: Overcast (day)\n    Temperature: 11.1°C / 52°F\n"), "{}", out);
+    assert!(out.contains("    Feels like: "));
+    assert!(out.contains("    Wind: "));
+}
+
+#[tokio::test]
+async fn test_weatherapi_air_quality() {
+    let server = MockServer::start(vec![route("/current.json", 200, "weatherapi_current_aqi.json").query("aqi=yes")]);
+    let config = WeatherConfig { air_quality: true, ..server.weather_config() };
+    let obs = get_observation(&config, &location("99801")).await.unwrap();
+    assert_eq!(obs.air_quality, Some(AirQuality {
+        pm2_5: Some(5.55),
+        pm10: Some(7.2),
+        o3: Some(62.0),
+        no2: Some(1.4),
+        us_epa_index: Some(1),
+        gb_defra_index: Some(1),
+    }));
+    assert_eq!(server.requests(), vec!["/current.json?key=test-key&q=99801&aqi=yes"]);
+}
+
+#[test]
+fn test_cli_air_quality() {
+    let server = MockServer::start(vec![
+        route("/current.json", 200, "weatherapi_current_aqi.json").query("aqi=yes"),
+        route("/current.json", 200, "weatherapi_current.json"),
+    ]);
+    let dir = tempfile::tempdir().unwrap();
+    let out = run_cli(&server, &dir, &["-z", "99801", "--air-quality", "--no-history"]);
+    assert!(out.contains(
+        "\n-= Air quality =-\n    PM2.5: 5.6 µg/m³\n    PM10: 7.2 µg/m³\n    Ozone (O3): 62 µg/m³\n    Nitrogen dioxide (NO2): 1.4 µg/m³\n    \
+        US EPA index: 1 (Good)\n    UK DEFRA index: 1 (Low)"),
+        "{}", out);
+    let out = run_cli(&server, &dir, &["-z", "99801", "--no-history"]);
+    assert!(!out.contains("Air quality"));
+
+    // A cached observation without air quality is fetched again when it is asked for.
+    let now = chrono::Utc::now().timestamp();
+    let mut stored = WeatherCache::default();
+    let obs = Observation { observed_epoch: now - 60, temp_c: 12.0, ..Observation::default() };
+    stored.insert(cache_key(ProviderKind::WeatherApi, &location("20500")), obs, now - 60);
+    stored.save(&dir.path().join("temperature-converter-cache.json").to_string_lossy()).unwrap();
+    run_cli(&server, &dir, &["-z", "20500", "--no-history"]);
+    assert_eq!(server.requests().len(), 2);
+    let out = run_cli(&server, &dir, &["-z", "20500", "--air-quality", "--no-history"]);
+    assert!(out.contains("US EPA index: 1 (Good)"), "{}", out);
+    assert_eq!(server.requests().len(), 3);
+}
+
+#[tokio::test]
+async fn test_weatherapi_forecast() {
+    let server = MockServer::start(vec![route("/forecast.json", 200, "weatherapi_forecast.json")]);
+    let forecast = get_forecast(&server.weather_config(), &location("99801"), 2).await.unwrap();
+    assert_eq!(server.requests(), vec!["/forecast.json?key=test-key&q=99801&days=2&aqi=no&alerts=no"]);
+    assert_eq!(forecast.place.name, "Juneau");
+    assert_eq!(forecast.days.len(), 2);
+    let today = &forecast.days[0];
+    assert_eq!((today.min_c, today.max_c, today.avg_c), (9.1, 14.2, 11.5));
+    assert_eq!(today.precip_chance, Some(80.0));
+    assert_eq!(today.condition, "Patchy rain nearby");
+    assert_eq!(today.hours[1].local_time.to_string(), "2024-08-14 12:00:00");
+    assert_eq!(forecast.days[1].precip_chance, Some(5.0));
+}
+
+#[tokio::test]
+async fn test_weatherapi_forecast_bad_time() {
+    let server = MockServer::start(vec![route("/forecast.json", 200, "weatherapi_forecast_bad_time.json")]);
+    let err = get_forecast(&server.weather_config(), &location("99801"), 2).await.unwrap_err();
+    assert!(matches!(&err, WeatherError::Decode(m) if m.starts_with("invalid time \"14 Aug 12:00\"")), "{:?}", err);
+}
+
+#[tokio::test]
+async fn test_open_meteo_forecast() {
+    let server = MockServer::start(vec![
+        route("/search", 200, "open_meteo_geocoding.json"),
+        route("/forecast", 200, "open_meteo_forecast.json").query("forecast_days=2"),
+    ]);
+    let config = WeatherConfig { provider: ProviderKind::OpenMeteo, ..server.weather_config() };
+    let forecast = get_forecast(&config, &location("99801"), 2).await.unwrap();
+    assert_eq!(forecast.days.len(), 2);
+    assert_eq!((forecast.days[0].min_c, forecast.days[0].max_c), (9.5, 14.0));
+    assert_eq!(forecast.days[0].precip_chance, Some(80.0));
+    assert_eq!(forecast.days[1].precip_chance, None);
+}
+
+#[tokio::test]
+async fn test_nws_forecast() {
+    let server = MockServer::start(vec![
+        route("/points/38.8977,-77.0365", 200, "nws_point.json"),
+        route("/gridpoints/LWX/96,70/forecast/hourly", 200, "nws_forecast_hourly.json"),
+    ]);
+    let config = WeatherConfig { provider: ProviderKind::Nws, ..server.weather_config() };
+    let forecast = get_forecast(&config, &location("38.8977,-77.0365"), 2).await.unwrap();
+    assert_eq!(forecast.place.name, "Washington");
+    assert_eq!(forecast.days.len(), 2);
+    assert_eq!(forecast.days[0].hours.len(), 2);
+    assert_eq!(forecast.days[1].condition, "Chance Showers");
+}
+
+#[tokio::test]
+async fn test_weatherapi_alerts() {
+    let server = MockServer::start(vec![route("/alerts.json", 200, "weatherapi_alerts.json")]);
+    let alerts = get_alerts(&server.weather_config(), &location("99801")).await.unwrap();
+    assert_eq!(server.requests(), vec!["/alerts.json?key=test-key&q=99801"]);
+    assert_eq!(alerts.place.name, "Juneau");
+    assert_eq!(alerts.alerts.iter().map(|a| a.event.as_str()).collect::<Vec<_>>(), vec!["Flood Watch", "Heat Advisory"]);
+    let heat = &alerts.alerts[1];
+    assert_eq!((heat.severity, heat.urgency), (AlertSeverity::Moderate, AlertUrgency::Expected));
+    assert_eq!(heat.areas, vec!["City and Borough of Juneau", "Haines Borough"]);
+    assert_eq!(heat.expires.unwrap().to_rfc3339(), "2024-08-14T20:00:00-08:00");
+    assert_eq!(heat.instruction, "Drink plenty of fluids.");
+}
+
+#[tokio::test]
+async fn test_nws_alerts() {
+    let server = MockServer::start(vec![
+        route("/points/38.8977,-77.0365", 200, "nws_point.json"),
+        route("/alerts/active", 200, "nws_alerts.json").query("point=38.8977%2C-77.0365"),
+    ]);
+    let config = WeatherConfig { provider: ProviderKind::Nws, ..server.weather_config() };
+    let alerts = get_alerts(&config, &location("38.8977,-77.0365")).await.unwrap();
+    assert_eq!((alerts.place.name.as_str(), alerts.alerts.len()), ("Washington", 1));
+    assert_eq!(alerts.alerts[0].event, "Heat Advisory");
+
+    let config = WeatherConfig { provider: ProviderKind::OpenMeteo, ..server.weather_config() };
+    assert!(matches!(get_alerts(&config, &location("38.8977,-77.0365")).await, Err(WeatherError::Unsupported { .. })));
+}
+
+#[test]
+fn test_cli_alerts() {
+    let server = MockServer::start(vec![
+        route("/current.json", 200, "weatherapi_current.json"),
+        route("/alerts.json", 200, "weatherapi_alerts.json"),
+    ]);
+    let dir = tempfile::tempdir().unwrap();
+    let out = run_cli(&server, &dir, &["-z", "99801", "--alerts"]);
+    assert!(out.contains(
+        "\n-= Alerts (2) =-\n    Flood Watch (Severe, Future) until 2024-08-15 18:00 -08:00\n        Flood Watch issued August 14\n"),
+        "{}", out);
+    assert!(out.contains("    Heat Advisory (Moderate, Expected) until 2024-08-14 20:00 -08:00\n"), "{}", out);
+
+    let log = std::fs::read_to_string(dir.path().join("temperature-converter-log.txt")).unwrap();
+    let entries = history::parse_history(&log);
+    assert_eq!(entries.iter().map(|e| e.operation).collect::<Vec<_>>(), vec![
+        history::Operation::WeatherLookup, history::Operation::WeatherAlert, history::Operation::WeatherAlert,
+    ]);
+    assert_eq!(entries[1].location.as_deref(), Some("Juneau, Alaska"));
+    assert!(entries[2].text.contains("    Event: Heat Advisory\n"));
+
+    let out = run_cli(&server, &dir, &["-z", "99801", "--no-history"]);
+    assert!(!out.contains("Alerts"));
+}
+
+#[test]
+fn test_cli_forecast() {
+    let server = MockServer::start(vec![route("/forecast.json", 200, "weatherapi_forecast.json")]);
+    let dir = tempfile::tempdir().unwrap();
+    let out = run_cli(&server, &dir, &["forecast", "99801", "--days", "2", "--scales", "F"]);
+    assert!(out.starts_with("-= Forecast for Juneau, Alaska =-\n    Wed 2024-08-14: Patchy rain nearby, 80% chance of precipitation\n        min 48.4°F, max 57.6°F, avg 52.7°F\n"), "{}", out);
+    assert!(!out.contains("12:00"));
+
+    let log = std::fs::read_to_string(dir.path().join("temperature-converter-log.txt")).unwrap();
+    let entries = history::parse_history(&log);
+    assert_eq!(entries[0].operation, history::Operation::WeatherLookup);
+    assert_eq!(entries[0].location.as_deref(), Some("Juneau, Alaska"));
+
+    let out = run_cli(&server, &dir, &["forecast", "99801", "--days", "30"]);
+    assert_eq!(out.trim(), "--days must be a number from 1 to 14");
+}
+
+fn date(day: u32) -> chrono::NaiveDate {
+    chrono::NaiveDate::from_ymd_opt(2024, 8, day).unwrap()
+}
+
+#[tokio::test]
+async fn test_weatherapi_history() {
+    let server = MockServer::start(vec![route("/history.json", 200, "weatherapi_history.json")]);
+    let history = get_historical_weather(&server.weather_config(), &location("99801"), date(1), date(2)).await.unwrap();
+    assert_eq!(server.requests(), vec!["/history.json?key=test-key&q=99801&dt=2024-08-01&end_dt=2024-08-02"]);
+    assert_eq!(history.place.name, "Juneau");
+    assert_eq!(history.days.len(), 2);
+    assert_eq!((history.days[0].min_c, history.days[0].max_c, history.days[0].avg_c), (10.0, 16.0, 12.8));
+    assert_eq!(history.days[0].precip_mm, Some(2.0));
+    assert_eq!(history.days[0].hours[1].humidity, Some(70.0));
+    assert_eq!(history.days[1].condition, "Partly cloudy");
+}
+
+#[tokio::test]
+async fn test_weatherapi_history_bad_date() {
+    let server = MockServer::start(vec![route("/history.json", 200, "weatherapi_history_bad_date.json")]);
+    let err = get_historical_weather(&server.weather_config(), &location("99801"), date(1), date(2)).await.unwrap_err();
+    assert!(matches!(&err, WeatherError::Decode(m) if m.starts_with("invalid date \"08/02/2024\"")), "{:?}", err);
+}
+
+#[tokio::test]
+async fn test_open_meteo_history() {
+    let server = MockServer::start(vec![
+        route("/search", 200, "open_meteo_geocoding.json"),
+        route("/archive", 200, "open_meteo_archive.json").query("start_date=2024-08-01&end_date=2024-08-01"),
+    ]);
+    let config = WeatherConfig { provider: ProviderKind::OpenMeteo, ..server.weather_config() };
+    let history = get_historical_weather(&config, &location("99801"), date(1), date(1)).await.unwrap();
+    assert_eq!(history.days.len(), 1);
+    assert_eq!((history.days[0].min_c, history.days[0].max_c), (10.0, 16.0));
+    assert_eq!(history.days[0].condition, "Rain");
+}
+
+#[tokio::test]
+async fn test_weatherapi_astronomy() {
+    let server = MockServer::start(vec![route("/astronomy.json", 200, "weatherapi_astronomy.json")]);
+    let astro = get_astronomy(&server.weather_config(), &location("99801"), date(14)).await.unwrap();
+    assert_eq!(server.requests(), vec!["/astronomy.json?key=test-key&q=99801&dt=2024-08-14"]);
+    assert_eq!((astro.place.name.as_str(), astro.place.tz_id.as_str()), ("Juneau", "America/Juneau"));
+    assert_eq!(astro.sunset.unwrap().to_string(), "21:23:00");
+    assert_eq!(astro.moonset, None);
+    assert_eq!((astro.moon_phase.as_str(), astro.moon_illumination), ("Waxing Gibbous", Some(72.0)));
+    assert_eq!((astro.is_sun_up, astro.is_moon_up), (Some(true), Some(false)));
+
+    let astro = get_astronomy(&server.weather_config(), &location("99801"), date(15)).await.unwrap();
+    assert_eq!(astro.is_sun_up, None);
+}
+
+#[tokio::test]
+async fn test_open_meteo_astronomy() {
+    let server = MockServer::start(vec![
+        route("/search", 200, "open_meteo_geocoding.json"),
+        route("/forecast", 200, "open_meteo_sun.json").query("daily=sunrise%2Csunset&start_date=2024-08-14&end_date=2024-08-14"),
+    ]);
+    let config = WeatherConfig { provider: ProviderKind::OpenMeteo, ..server.weather_config() };
+    let astro = get_astronomy(&config, &location("99801"), date(14)).await.unwrap();
+    assert_eq!(astro.place.tz_id, "America/Juneau");
+    assert_eq!(astro.sunrise.unwrap().to_string(), "05:08:00");
+    assert_eq!((astro.moonrise, astro.moon_phase.as_str()), (None, "Waxing Gibbous"));
+
+    let config = WeatherConfig { provider: ProviderKind::Nws, ..server.weather_config() };
+    let err = get_astronomy(&config, &location("99801"), date(14)).await.unwrap_err();
+    assert_eq!(err, WeatherError::Unsupported { provider: "National Weather Service", feature: "astronomy" });
+}
+
+#[test]
+fn test_cli_astronomy() {
+    let server = MockServer::start(vec![route("/astronomy.json", 200, "weatherapi_astronomy.json")]);
+    let dir = tempfile::tempdir().unwrap();
+    let out = run_cli(&server, &dir, &["astronomy", "99801", "--date", "2024-08-14"]);
+    assert!(out.starts_with("-= Astronomy for Juneau, Alaska =-\n    Date: 2024-08-14 (times in America/Juneau)\n    Sunrise: 05:08\n    \
+        Sunset: 21:23\n    Moonrise: 15:12\n    Moonset: none\n    Moon phase: Waxing Gibbous (72% illuminated)\n"), "{}", out);
+
+    let log = std::fs::read_to_string(dir.path().join("temperature-converter-log.txt")).unwrap();
+    let entries = history::parse_history(&log);
+    assert_eq!(entries[0].operation, history::Operation::WeatherLookup);
+    assert_eq!(entries[0].location.as_deref(), Some("Juneau, Alaska"));
+
+    let out = run_cli(&server, &dir, &["astronomy", "99801", "--date", "tomorrowish"]);
+    assert_eq!(out.trim(), "invalid date tomorrowish");
+}
+
+#[test]
+fn test_cli_astronomy_redacted() {
+    let server = MockServer::start(vec![route("/astronomy.json", 200, "weatherapi_astronomy.json")]);
+    let dir = tempfile::tempdir().unwrap();
+    let config = dir.path().join("private.toml");
+    std::fs::write(&config, "[history]\nlocations = \"redact\"\n").unwrap();
+    let output = cli(&server, &dir, &["astronomy", "99801", "--date", "2024-08-14"]).env("TEMPERATURE_CONVERTER_CONFIG", &config).output().unwrap();
+    assert!(String::from_utf8(output.stdout).unwrap().starts_with("-= Astronomy for Juneau, Alaska =-\n"));
+
+    let log = std::fs::read_to_string(dir.path().join("temperature-converter-log.txt")).unwrap();
+    assert!(!log.contains("Juneau") && !log.contains("Alaska") && !log.contains("99801"), "{}", log);
+    assert_eq!(history::parse_history(&log)[0].location.as_deref(), Some("[redacted]"));
+}
+
+#[tokio::test]
+async fn test_history_errors() {
+    let server = MockServer::start(vec![]);
+    let err = get_historical_weather(&server.weather_config(), &location("99801"), date(3), date(1)).await.unwrap_err();
+    assert!(matches!(err, WeatherError::InvalidDate(_)));
+    let config = WeatherConfig { provider: ProviderKind::Nws, ..server.weather_config() };
+    let err = get_historical_weather(&config, &location("99801"), date(1), date(1)).await.unwrap_err();
+    assert_eq!(err, WeatherError::Unsupported { provider: "National Weather Service", feature: "historical weather" });
+    assert!(server.requests().is_empty());
+}
+
+#[test]
+fn test_cli_weather_date() {
+    let server = MockServer::start(vec![route("/history.json", 200, "weatherapi_history.json")]);
+    let dir = tempfile::tempdir().unwrap();
+    let out = run_cli(&server, &dir, &["weather", "99801", "--date", "2024-08-01", "--until", "2024-08-02", "--scales", "K"]);
+    assert!(out.starts_with("-= Weather history for Juneau, Alaska =-\n    Thu 2024-08-01: Light rain, 2 mm precipitation\n        min 283.2K, max 289.2K, avg 285.9K\n"), "{}", out);
+
+    let log = std::fs::read_to_string(dir.path().join("temperature-converter-log.txt")).unwrap();
+    let entries = history::parse_history(&log);
+    assert_eq!(entries[0].operation, history::Operation::WeatherLookup);
+    assert_eq!(entries[0].location.as_deref(), Some("Juneau, Alaska"));
+
+    let out = run_cli(&server, &dir, &["weather", "99801", "--date", "August 1st", "--no-history"]);
+    assert!(!out.contains("Weather history for"));
+}
+
+#[test]
+fn test_cli_watch() {
+    let server = weatherapi(200, "weatherapi_current.json");
+    let dir = tempfile::tempdir().unwrap();
+    let child = cli(&server, &dir, &["-z", "99801", "--watch", "100ms"])
+        .stdout(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(800));
+    Command::new("kill").args(["-INT", &child.id().to_string()]).status().unwrap();
+    let output = child.wait_with_output().unwrap();
+    let out = String::from_utf8(output.stdout).unwrap();
+    assert!(output.status.success(), "{}", out);
+    // The conditions never change, so only the first poll is printed and recorded.
+    assert_eq!(out.matches("] Juneau, Alaska: 11.1°C, Overcast").count(), 1, "{}", out);
+    assert!(out.trim_end().ends_with("Stopped watching 99801"), "{}", out);
+    assert!(server.requests().len() > 1);
+
+    let log = std::fs::read_to_string(dir.path().join("temperature-converter-log.txt")).unwrap();
+    let entries = history::parse_history(&log);
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].location.as_deref(), Some("Juneau, Alaska"));
+
+    let out = run_cli(&server, &dir, &["-z", "99801", "--watch", "soon"]);
+    assert_eq!(out.trim(), "invalid interval soon (use e.g. 30s, 5m or 1h)");
+}
+
+#[tokio::test]
+async fn test_threshold_notifiers() {
+    let server = MockServer::start(vec![
+        route("/current.json", 200, "weatherapi_current.json"),
+        route("/hook", 200, "webhook_ok.json"),
+    ]);
+    let dir = tempfile::tempdir().unwrap();
+    let out = dir.path().join("notified.txt");
+    let rule = |name: &str, comparison, threshold: &str, notify| ThresholdRule {
+        name: name.to_string(),
+        location: "99801".to_string(),
+        comparison,
+        threshold: Threshold::try_from(threshold.to_string()).unwrap(),
+        notify,
+        ..ThresholdRule::default()
+    };
+    let config = Config {
+        thresholds: ThresholdConfig {
+            state_path: Some(dir.path().join("rules.json").to_string_lossy().to_string()),
+            rules: vec![
+                rule("Mild", Comparison::Above, "50F", vec![
+                    Notifier::Webhook { url: format!("{}/hook", server.url) },
+                    Notifier::Command {
+                        command: "sh".to_string(),
+                        args: vec!["-c".to_string(), format!("echo \"$TEMPERATURE_CONVERTER_MESSAGE\" > {}", out.display())],
+                    },
+                ]),
+                rule("Freezing", Comparison::Below, "0C", vec![Notifier::Stdout]),
+                rule("Broken", Comparison::Above, "10C", vec![Notifier::Webhook { url: format!("{}/missing", server.url) }]),
+            ],
+        },
+        ..stored_config(&server, &dir)
+    };
+    let zip = location("99801");
+
+    let cached = get_cached_observation(&config, &zip, true).await.unwrap();
+    let events = check_thresholds(&config, &[(&zip, &cached)]).await;
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0], ThresholdEvent {
+        rule: "Mild".to_string(),
+        message: "Mild: Juneau, Alaska is 52°F, above 50°F".to_string(),
+        failures: Vec::new(),
+    });
+    assert_eq!(events[1].failures, vec![format!("webhook {}/missing: HTTP 404", server.url)]);
+    assert_eq!(std::fs::read_to_string(&out).unwrap(), "Mild: Juneau, Alaska is 52°F, above 50°F\n");
+    let posted = server.bodies();
+    assert_eq!(posted.iter().map(|(target, _)| target.as_str()).collect::<Vec<_>>(), vec!["/hook", "/missing"]);
+    let body: serde_json::Value = serde_json::from_str(&posted[0].1).unwrap();
+    assert_eq!(body["rule"], "Mild");
+    assert_eq!(body["location"], "Juneau, Alaska");
+    assert_eq!(body["threshold"], "50°F");
+    assert_eq!(body["comparison"], "above");
+
+    // Still above the threshold on the next fetch, so nothing is sent again.
+    let cached = get_cached_observation(&config, &zip, true).await.unwrap();
+    assert!(check_thresholds(&config, &[(&zip, &cached)]).await.is_empty());
+    assert_eq!(server.bodies().len(), 2);
+}
+
+#[test]
+fn test_cli_threshold_rules() {
+    let server = weatherapi(200, "weatherapi_current.json");
+    let dir = tempfile::tempdir().unwrap();
+    let config = dir.path().join("rules.toml");
+    std::fs::write(&config, "[[thresholds.rules]]\nlocation = \"Juneau\"\nthreshold = \"10C\"\n\
+        [[thresholds.rules]]\nlocation = \"99801\"\nthreshold = \"5C\"\nnotify = [{ type = \"command\", command = \"false\" }]\n").unwrap();
+    let output = cli(&server, &dir, &["-z", "99801"]).env("TEMPERATURE_CONVERTER_CONFIG", &config).output().unwrap();
+    let out = String::from_utf8(output.stdout).unwrap();
+    assert!(out.starts_with("Juneau above 10°C: Juneau, Alaska is 11.1°C, above 10°C\n-= Retrieve temperature in Juneau, Alaska =-\n"), "{}", out);
+    assert!(out.contains("\nNotifying 99801 above 5°C failed: false: exit status: 1"), "{}", out);
+    assert!(dir.path().join("temperature-converter-rules.json").exists());
+
+    let output = cli(&server, &dir, &["-z", "99801"]).env("TEMPERATURE_CONVERTER_CONFIG", &config).output().unwrap();
+    let out = String::from_utf8(output.stdout).unwrap();
+    assert!(out.starts_with("-= Retrieve temperature in Juneau, Alaska =-\n"), "{}", out);
+}
+
+#[tokio::test]
+async fn test_daemon_answers_from_its_poll() {
+    let server = weatherapi(200, "weatherapi_current.json");
+    let dir = tempfile::tempdir().unwrap();
+    let config = stored_config(&server, &dir);
+    let daemon = Daemon::new(config.clone(), std::time::Duration::from_secs(600));
+    let zip = location("99801");
+    assert_eq!(daemon.poll(&[("99801".to_string(), zip.clone())]).await, "Polled 1 of 1 locations");
+    assert_eq!(server.requests().len(), 1);
+    let entries = history::read_entries(&config.history).unwrap();
+    assert_eq!((entries.len(), entries[0].app), (1, Some(Application::Daemon)));
+    assert_eq!(entries[0].location.as_deref(), Some("Juneau, Alaska"));
+
+    let ask = |queries: Vec<LocationQuery>, refresh, ttl_minutes| DaemonRequest::Current {
+        queries,
+        refresh,
+        provider: ProviderKind::WeatherApi,
+        air_quality: false,
+        ttl_minutes,
+    };
+    let DaemonResponse::Current(answers) = daemon.answer(ask(vec![zip.clone()], false, 10)).await else { panic!("refused") };
+    assert_eq!(answers[0].as_ref().unwrap().source, ObservationSource::Cache);
+    assert_eq!(answers[0].as_ref().unwrap().cached.observation.place.name, "Juneau");
+    assert_eq!(server.requests().len(), 1);
+
+    // An asker with a shorter cache TTL than the poll interval isn't answered with anything older than it.
+    let DaemonResponse::Current(answers) = daemon.answer(ask(vec![zip.clone()], false, 0)).await else { panic!("refused") };
+    assert_eq!(answers[0].as_ref().unwrap().source, ObservationSource::Live);
+    assert_eq!(server.requests().len(), 2);
+
+    // Places it doesn't poll, and refreshes, are looked up for the asker.
+    let DaemonResponse::Current(answers) = daemon.answer(ask(vec![location("20500"), zip.clone()], true, 10)).await else { panic!("refused") };
+    assert_eq!(answers.iter().map(|a| a.as_ref().unwrap().source).collect::<Vec<_>>(), vec![ObservationSource::Live; 2]);
+    assert_eq!(server.requests().len(), 4);
+
+    let other = DaemonRequest::Current { queries: vec![zip], refresh: false, provider: ProviderKind::Nws, air_quality: false, ttl_minutes: 10 };
+    assert!(matches!(daemon.answer(other).await, DaemonResponse::Refused(_)));
+}
+
+#[tokio::test]
+async fn test_daemon_poll_skips_stale_results() {
+    let server = weatherapi(200, "weatherapi_current.json");
+    let dir = tempfile::tempdir().unwrap();
+    let mut config = stored_config(&server, &dir);
+    let zip = location("99801");
+    let daemon = Daemon::new(config.clone(), std::time::Duration::from_secs(600));
+    daemon.poll(&[("99801".to_string(), zip.clone())]).await;
+
+    // The provider is down, so the cached observation comes back stale and isn't recorded as a new one.
+    let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    config.weather.base_url = format!("http://127.0.0.1:{}", port);
+    let daemon = Daemon::new(config.clone(), std::time::Duration::from_secs(600));
+    let polled = daemon.poll(&[("99801".to_string(), zip)]).await;
+    assert!(polled.starts_with("Polled 0 of 1 locations\n    99801: STALE: the weather service was not reached"), "{}", polled);
+    assert_eq!(history::read_entries(&config.history).unwrap().len(), 1);
+}
+
+#[test]
+fn test_cli_reads_from_daemon() {
+    let server = weatherapi(200, "weatherapi_current.json");
+    let dir = tempfile::tempdir().unwrap();
+    let config = dir.path().join("daemon.toml");
+    std::fs::write(&config, "[daemon]\nlocations = [\"99801\"]\n").unwrap();
+    let mut daemon = Command::new(env!("CARGO_BIN_EXE_main-daemon"))
+        .current_dir(dir.path())
+        .env("TEMPERATURE_CONVERTER_CONFIG", &config)
+        .env("TEMPERATURE_CONVERTER_API_KEY", "test-key")
+        .env("TEMPERATURE_CONVERTER_BASE_URL", &server.url)
+        .env("TZ", "America/New_York")
+        .stdout(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    let log = dir.path().join("temperature-converter-log.txt");
+    for _ in 0..100 {
+        if log.exists() && dir.path().join("temperature-converter.sock").exists() {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(50));
+    }
+    assert_eq!(server.requests().len(), 1);
+
+    let cli_with_daemon = |args: &[&str]| {
+        let output = cli(&server, &dir, args).env("TEMPERATURE_CONVERTER_CONFIG", &config).output().unwrap();
+        String::from_utf8(output.stdout).unwrap()
+    };
+    let out = cli_with_daemon(&["-z", "99801"]);
+    assert!(out.starts_with("-= Retrieve temperature in Juneau, Alaska =-\n    (from the daemon, fetched "), "{}", out);
+    assert_eq!(server.requests().len(), 1);
+    let out = cli_with_daemon(&["-z", "99801", "--no-daemon"]);
+    assert!(out.starts_with("-= Retrieve temperature in Juneau, Alaska =-\n    Celsius: 11.1\n"), "{}", out);
+    assert_eq!(server.requests().len(), 2);
+
+    Command::new("kill").args(["-TERM", &daemon.id().to_string()]).status().unwrap();
+    assert!(daemon.wait().unwrap().success());
+    let mut out = String::new();
+    std::io::Read::read_to_string(&mut daemon.stdout.take().unwrap(), &mut out).unwrap();
+    assert!(out.starts_with("Polling 99801 every 900s, answering on temperature-converter.sock\n"), "{}", out);
+    assert!(out.contains("] Polled 1 of 1 locations\n"), "{}", out);
+    assert!(!dir.path().join("temperature-converter.sock").exists());
+
+    let entries = history::parse_history(&std::fs::read_to_string(&log).unwrap());
+    assert_eq!(entries.iter().map(|e| e.app).collect::<Vec<_>>(), vec![Some(Application::Daemon), Some(Application::CLI), Some(Application::CLI)]);
+}